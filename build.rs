@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Embeds the git commit and build time reported by `/version`. Builds
+/// without a `.git` directory (e.g. Docker) can pass `GIT_HASH` instead.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    rerun_on_new_commits();
+
+    let git_hash = std::env::var("GIT_HASH").ok().filter(|hash| !hash.is_empty()).or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|hash| hash.trim().to_string())
+    });
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible.
+    let build_time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+
+    println!("cargo:rustc-env=MQV_GIT_HASH={}", git_hash.unwrap_or_else(|| "unknown".to_string()));
+    println!("cargo:rustc-env=MQV_BUILD_TIME={}", build_time);
+}
+
+/// Reruns when HEAD moves: on checkout (`.git/HEAD`) and on commit (the
+/// branch ref, or `packed-refs` once `git gc` packed it). Missing paths are
+/// left out, as Cargo would rerun the script on every build for them.
+fn rerun_on_new_commits() {
+    let git_dir = Path::new(".git");
+    let mut watched = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+    if let Ok(head) = fs::read_to_string(git_dir.join("HEAD"))
+        && let Some(reference) = head.trim().strip_prefix("ref: ")
+    {
+        watched.push(git_dir.join(reference));
+    }
+    for path in watched.into_iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
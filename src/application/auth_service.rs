@@ -1,19 +1,39 @@
+use crate::application::user_service;
 use crate::domain::auth::Claims;
 use crate::infrastructure::app_state::AppState;
 use crate::interface::dto::{LoginRequest, LoginResponse};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{encode, EncodingKey, Header};
-use log::debug;
+use log::{debug, error};
+
+/// Checks the built-in account from the configuration first, then the
+/// accounts in the `users` table.
+fn verify_credentials(req: &LoginRequest, app_state: &AppState) -> bool {
+    if let (Some(user_name), Some(password)) = (&app_state.auth.user_name, &app_state.auth.password)
+        && req.username.eq(user_name.as_str())
+        && req.password.eq(password.as_str())
+    {
+        return true;
+    }
+
+    let connection = app_state.accounts.lock().unwrap();
+    match user_service::find_user(&connection, &req.username) {
+        Ok(Some(user)) => {
+            user_service::verify_password(&req.password, &user.password_hash, &app_state.auth.salt_key)
+        }
+        Ok(None) => false,
+        Err(e) => {
+            error!("Failed to look up user {}: {}", req.username, e);
+            false
+        }
+    }
+}
 
 pub fn login_user(req: LoginRequest, app_state: &AppState) -> Option<LoginResponse> {
-    debug!(
-        "Login request: username: {}, password: {}",
-        req.username, req.password
-    );
+    debug!("Login request: username: {}", req.username);
 
-    if req.username.eq(app_state.user_name.as_str()) && req.password.eq(app_state.password.as_str())
-    {
-        let exp = Utc::now() + Duration::hours(24);
+    if verify_credentials(&req, app_state) {
+        let exp = Utc::now() + Duration::hours(app_state.auth.token_ttl_hours);
         let claims = Claims {
             sub: req.username,
             exp: exp.timestamp() as usize,
@@ -21,7 +41,7 @@ pub fn login_user(req: LoginRequest, app_state: &AppState) -> Option<LoginRespon
         let token = encode(
             &Header::default(),
             &claims,
-            &EncodingKey::from_secret(app_state.secret_value.as_bytes()),
+            &EncodingKey::from_secret(app_state.auth.secret_value.as_bytes()),
         )
         .unwrap();
         Some(LoginResponse { token })
@@ -0,0 +1,276 @@
+use crate::domain::catalog::{CatalogEntry, CatalogKind, CatalogMetadata, ParsedCatalogRecord};
+use crate::domain::import::{DuplicatePolicy, ImportReport, RejectedRecord};
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+
+const CATALOG_TABLE: &str = "catalog";
+
+const CATALOG_COLUMNS: &str = "kind, name, display_name, description, owner_team, contact, criticality, tags, \
+     expected_tps_min, expected_tps_max, updated_at";
+
+/// Maps a row selected with `CATALOG_COLUMNS`.
+fn catalog_row(row: &Row<'_>) -> rusqlite::Result<CatalogEntry> {
+    let kind: String = row.get(0)?;
+    let criticality: Option<String> = row.get(6)?;
+    let tags: String = row.get(7)?;
+    Ok(CatalogEntry {
+        kind: kind.parse().unwrap_or(CatalogKind::Function),
+        name: row.get(1)?,
+        metadata: CatalogMetadata {
+            display_name: row.get(2)?,
+            description: row.get(3)?,
+            owner_team: row.get(4)?,
+            contact: row.get(5)?,
+            criticality: criticality.and_then(|value| value.parse().ok()),
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+            expected_tps_min: row.get(8)?,
+            expected_tps_max: row.get(9)?,
+        },
+        updated_at: row.get(10)?,
+    })
+}
+
+/// Entries ordered by kind and name, of one kind when given.
+pub fn list_entries(
+    connection: &Connection,
+    kind: Option<CatalogKind>,
+) -> Result<Vec<CatalogEntry>, Box<dyn std::error::Error>> {
+    let sql = format!(
+        "SELECT {} FROM {} WHERE ?1 IS NULL OR kind = ?1 ORDER BY kind, name",
+        CATALOG_COLUMNS, CATALOG_TABLE
+    );
+    let mut stmt = connection.prepare(&sql)?;
+    let entries = stmt
+        .query_map([kind.map(|kind| kind.as_str())], catalog_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// Metadata of every entry of `kind`, by name.
+pub fn metadata_by_name(
+    connection: &Connection,
+    kind: CatalogKind,
+) -> Result<HashMap<String, CatalogMetadata>, Box<dyn std::error::Error>> {
+    Ok(list_entries(connection, Some(kind))?
+        .into_iter()
+        .map(|entry| (entry.name, entry.metadata))
+        .collect())
+}
+
+pub fn find_entry(
+    connection: &Connection,
+    kind: CatalogKind,
+    name: &str,
+) -> Result<Option<CatalogEntry>, Box<dyn std::error::Error>> {
+    let sql = format!("SELECT {} FROM {} WHERE kind = ?1 AND name = ?2", CATALOG_COLUMNS, CATALOG_TABLE);
+    let entry = connection
+        .query_row(&sql, params![kind.as_str(), name], catalog_row)
+        .optional()?;
+    Ok(entry)
+}
+
+/// Creates or replaces the entry. Returns `true` when it was created.
+pub fn upsert_entry(
+    connection: &Connection,
+    kind: CatalogKind,
+    name: &str,
+    metadata: &CatalogMetadata,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let exists = find_entry(connection, kind, name)?.is_some();
+    write_entry(connection, kind, name, metadata)?;
+    Ok(!exists)
+}
+
+fn write_entry(connection: &Connection, kind: CatalogKind, name: &str, metadata: &CatalogMetadata) -> rusqlite::Result<()> {
+    let sql = format!(
+        "INSERT OR REPLACE INTO {} ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        CATALOG_TABLE, CATALOG_COLUMNS
+    );
+    let tags = serde_json::to_string(&metadata.tags).unwrap_or_else(|_| "[]".to_string());
+    connection.execute(
+        &sql,
+        params![
+            kind.as_str(),
+            name,
+            metadata.display_name,
+            metadata.description,
+            metadata.owner_team,
+            metadata.contact,
+            metadata.criticality.map(|criticality| criticality.as_str()),
+            tags,
+            metadata.expected_tps_min,
+            metadata.expected_tps_max,
+            Local::now(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Deletes the entry. Returns `false` when there was none.
+pub fn delete_entry(connection: &Connection, kind: CatalogKind, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let sql = format!("DELETE FROM {} WHERE kind = ?1 AND name = ?2", CATALOG_TABLE);
+    Ok(connection.execute(&sql, params![kind.as_str(), name])? > 0)
+}
+
+/// Writes imported entries in a single transaction. An entry that already
+/// exists is a duplicate; invalid entries and, with
+/// `DuplicatePolicy::Reject`, duplicates are reported in `rejected`
+/// without aborting the rest; `dry_run` validates and rolls back.
+pub fn import_entries(
+    connection: &mut Connection,
+    records: Vec<ParsedCatalogRecord>,
+    duplicate_policy: DuplicatePolicy,
+    dry_run: bool,
+) -> Result<ImportReport, Box<dyn std::error::Error>> {
+    let mut report = ImportReport::default();
+    let tx = connection.transaction()?;
+    for (index, record) in records {
+        let record = record.and_then(|mut record| {
+            record.name = record.name.trim().to_string();
+            record.metadata = record.metadata.normalized();
+            if record.name.is_empty() {
+                return Err("name must not be empty".to_string());
+            }
+            record.metadata.validate().map(|_| record)
+        });
+        let record = match record {
+            Ok(record) => record,
+            Err(reason) => {
+                report.rejected.push(RejectedRecord { index, reason });
+                continue;
+            }
+        };
+        let exists = find_entry(&tx, record.kind, &record.name)?.is_some();
+        match (exists, duplicate_policy) {
+            (false, _) => report.inserted += 1,
+            (true, DuplicatePolicy::Replace) => report.replaced += 1,
+            (true, DuplicatePolicy::Skip) => {
+                report.skipped += 1;
+                continue;
+            }
+            (true, DuplicatePolicy::Reject) => {
+                let reason = format!("{} '{}' is already in the catalog", record.kind.as_str(), record.name);
+                report.rejected.push(RejectedRecord { index, reason });
+                continue;
+            }
+        }
+        write_entry(&tx, record.kind, &record.name, &record.metadata)?;
+    }
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::catalog::CatalogRecord;
+
+    fn database() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        crate::infrastructure::schema::migrate(&mut connection).unwrap();
+        connection
+    }
+
+    fn record(index: usize, kind: CatalogKind, name: &str, metadata: CatalogMetadata) -> ParsedCatalogRecord {
+        (index, Ok(CatalogRecord { kind, name: name.to_string(), metadata }))
+    }
+
+    fn owned_by(team: &str) -> CatalogMetadata {
+        CatalogMetadata { owner_team: Some(team.to_string()), ..Default::default() }
+    }
+
+    fn owner(connection: &Connection, kind: CatalogKind, name: &str) -> Option<String> {
+        find_entry(connection, kind, name).unwrap().and_then(|entry| entry.metadata.owner_team)
+    }
+
+    #[test]
+    fn import_normalizes_and_rejects_invalid_entries() {
+        let mut connection = database();
+        let records = vec![
+            record(
+                1,
+                CatalogKind::System,
+                " SYS-A ",
+                CatalogMetadata {
+                    owner_team: Some("  ".to_string()),
+                    tags: vec![" Tier-1".to_string(), "core".to_string(), "tier-1".to_string()],
+                    ..Default::default()
+                },
+            ),
+            record(2, CatalogKind::System, "  ", CatalogMetadata::default()),
+            record(
+                3,
+                CatalogKind::Function,
+                "PAY",
+                CatalogMetadata { expected_tps_min: Some(5.0), expected_tps_max: Some(1.0), ..Default::default() },
+            ),
+            (4, Err("entry must be a mapping".to_string())),
+        ];
+        let report = import_entries(&mut connection, records, DuplicatePolicy::Skip, false).unwrap();
+        assert_eq!(report.inserted, 1);
+        let rejected: Vec<(usize, &str)> =
+            report.rejected.iter().map(|rejected| (rejected.index, rejected.reason.as_str())).collect();
+        assert_eq!(
+            rejected,
+            vec![
+                (2, "name must not be empty"),
+                (3, "expected_tps_min must not exceed expected_tps_max"),
+                (4, "entry must be a mapping"),
+            ]
+        );
+        let sys_a = find_entry(&connection, CatalogKind::System, "SYS-A").unwrap().unwrap();
+        assert_eq!(sys_a.metadata.owner_team, None);
+        assert_eq!(sys_a.metadata.tags, vec!["core", "tier-1"]);
+    }
+
+    #[test]
+    fn duplicates_follow_the_policy() {
+        let mut connection = database();
+        upsert_entry(&connection, CatalogKind::System, "SYS-A", &owned_by("core")).unwrap();
+        let again = || vec![record(1, CatalogKind::System, "SYS-A", owned_by("payments"))];
+
+        let report = import_entries(&mut connection, again(), DuplicatePolicy::Skip, false).unwrap();
+        assert_eq!((report.inserted, report.skipped), (0, 1));
+        assert_eq!(owner(&connection, CatalogKind::System, "SYS-A").as_deref(), Some("core"));
+
+        let report = import_entries(&mut connection, again(), DuplicatePolicy::Reject, false).unwrap();
+        assert_eq!(report.rejected[0].reason, "system 'SYS-A' is already in the catalog");
+        assert_eq!(owner(&connection, CatalogKind::System, "SYS-A").as_deref(), Some("core"));
+
+        let report = import_entries(&mut connection, again(), DuplicatePolicy::Replace, false).unwrap();
+        assert_eq!(report.replaced, 1);
+        assert_eq!(owner(&connection, CatalogKind::System, "SYS-A").as_deref(), Some("payments"));
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let mut connection = database();
+        let records = vec![record(1, CatalogKind::Function, "PAY", owned_by("payments"))];
+        let report = import_entries(&mut connection, records, DuplicatePolicy::Skip, true).unwrap();
+        assert_eq!(report.inserted, 1);
+        assert!(list_entries(&connection, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn entries_are_kept_apart_by_kind() {
+        let connection = database();
+        assert!(upsert_entry(&connection, CatalogKind::System, "PAY", &owned_by("core")).unwrap());
+        assert!(upsert_entry(&connection, CatalogKind::Function, "PAY", &owned_by("payments")).unwrap());
+        assert!(!upsert_entry(&connection, CatalogKind::Function, "PAY", &owned_by("cards")).unwrap());
+
+        let functions = metadata_by_name(&connection, CatalogKind::Function).unwrap();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions["PAY"].owner_team.as_deref(), Some("cards"));
+        assert_eq!(owner(&connection, CatalogKind::System, "PAY").as_deref(), Some("core"));
+
+        assert!(delete_entry(&connection, CatalogKind::System, "PAY").unwrap());
+        assert!(!delete_entry(&connection, CatalogKind::System, "PAY").unwrap());
+        let kinds: Vec<CatalogKind> = list_entries(&connection, None).unwrap().into_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![CatalogKind::Function]);
+    }
+}
@@ -0,0 +1,484 @@
+use crate::domain::catalog::CatalogMetadata;
+use crate::domain::chargeback::{
+    AllocationUnit, ChargeAmounts, ChargebackMonth, ChargebackPolicy, ChargebackReport, ConsumerCharge, CostBasis,
+    FunctionCharge, FunctionUsage, PoolSplit,
+};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+
+const MQ_USAGE_TABLE: &str = "mq_data";
+
+/// work_total per system and function in `month`, summed over rows of
+/// every granularity.
+pub fn get_monthly_usage(
+    connection: &Connection,
+    month: &ChargebackMonth,
+) -> Result<Vec<FunctionUsage>, Box<dyn std::error::Error>> {
+    let sql = format!(
+        "SELECT system_name, mq_function, SUM(work_total) FROM {}
+         WHERE date_time >= ?1 AND date_time < ?2
+         GROUP BY system_name, mq_function ORDER BY system_name, mq_function",
+        MQ_USAGE_TABLE
+    );
+    let mut stmt = connection.prepare(&sql)?;
+    let usage = stmt
+        .query_map([month.start().to_rfc3339(), month.end().to_rfc3339()], |row| {
+            Ok(FunctionUsage {
+                system_name: row.get(0)?,
+                mq_function: row.get(1)?,
+                work_total: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(usage)
+}
+
+/// Rounds an amount to cents.
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Splits `amount` over `weights` pro rata, or evenly when they sum to 0.
+fn pro_rata(amount: f64, weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    weights
+        .iter()
+        .map(|weight| if total > 0.0 { amount * weight / total } else { amount / weights.len() as f64 })
+        .collect()
+}
+
+struct Consumer {
+    name: String,
+    team: Option<String>,
+    functions: Vec<FunctionUsage>,
+    work_total: f64,
+    usage_cost: f64,
+    shared_cost: f64,
+    minimum_adjustment: f64,
+}
+
+/// Raises consumers below `minimum` to it. With a fixed cost the raises are
+/// taken from the others pro rata to work_total, repeatedly, until no one
+/// is left below the minimum; with a rate they add to the total. Returns
+/// what the raises cost beyond a fixed cost too small to fund them, in
+/// which case the consumers above the minimum keep none of it.
+fn apply_minimum(consumers: &mut [Consumer], minimum: f64, basis: CostBasis) -> f64 {
+    if minimum <= 0.0 || consumers.is_empty() {
+        return 0.0;
+    }
+    let CostBasis::Fixed(cost) = basis else {
+        for consumer in consumers.iter_mut() {
+            consumer.minimum_adjustment = (minimum - consumer.usage_cost - consumer.shared_cost).max(0.0);
+        }
+        return 0.0;
+    };
+    let mut pinned = vec![false; consumers.len()];
+    let mut usage = vec![0.0; consumers.len()];
+    loop {
+        let mut free = cost;
+        for (idx, consumer) in consumers.iter().enumerate() {
+            if pinned[idx] {
+                usage[idx] = (minimum - consumer.shared_cost).max(0.0);
+                free -= usage[idx];
+            }
+        }
+        // Raises beyond the cost leave the others nothing, never a negative share.
+        let open: Vec<usize> = (0..consumers.len()).filter(|idx| !pinned[*idx]).collect();
+        let weights: Vec<f64> = open.iter().map(|idx| consumers[*idx].work_total).collect();
+        for (idx, amount) in open.iter().zip(pro_rata(free.max(0.0), &weights)) {
+            usage[*idx] = amount;
+        }
+        let below: Vec<usize> = open
+            .into_iter()
+            .filter(|idx| usage[*idx] + consumers[*idx].shared_cost < minimum)
+            .collect();
+        if below.is_empty() {
+            break;
+        }
+        for idx in below {
+            pinned[idx] = true;
+        }
+    }
+    let charged: f64 = usage.iter().sum();
+    for (consumer, usage) in consumers.iter_mut().zip(usage) {
+        consumer.minimum_adjustment = usage - consumer.usage_cost;
+    }
+    (charged - cost).max(0.0)
+}
+
+/// Rounds `amounts` to cents that add up to `total`: each is rounded down
+/// and the cents left over go to the largest remainders, or come off the
+/// smallest when `total` is below the rounded-down sum.
+fn split_cents(amounts: &[f64], total: f64) -> Vec<f64> {
+    // In cents, without the float noise below a millionth of a cent.
+    let scaled: Vec<f64> = amounts.iter().map(|amount| (amount * 1e8).round() / 1e6).collect();
+    let mut whole: Vec<i64> = scaled.iter().map(|amount| amount.floor() as i64).collect();
+    let mut order: Vec<usize> = (0..amounts.len()).collect();
+    order.sort_by(|a, b| (scaled[*b] - whole[*b] as f64).total_cmp(&(scaled[*a] - whole[*a] as f64)));
+    let left = (total * 100.0).round() as i64 - whole.iter().sum::<i64>();
+    if left >= 0 {
+        for idx in order.iter().cycle().take(left as usize) {
+            whole[*idx] += 1;
+        }
+    } else {
+        for idx in order.iter().rev().cycle().take(left.unsigned_abs() as usize) {
+            whole[*idx] -= 1;
+        }
+    }
+    whole.into_iter().map(|amount| amount as f64 / 100.0).collect()
+}
+
+/// Amounts already rounded to cents; the minimum adjustment is what makes
+/// up the total.
+fn amounts(work_total: f64, month_work: f64, usage_cost: f64, shared_cost: f64, total_cost: f64) -> ChargeAmounts {
+    ChargeAmounts {
+        work_total,
+        share: if month_work > 0.0 { work_total / month_work } else { 0.0 },
+        usage_cost,
+        shared_cost,
+        minimum_adjustment: cents(total_cost - usage_cost - shared_cost),
+        total_cost,
+    }
+}
+
+/// Allocates the cost of `month` to the teams or systems in `usage`.
+/// `systems` is the catalog metadata of systems, for their owning team.
+pub fn build_report(
+    month: &ChargebackMonth,
+    policy: &ChargebackPolicy,
+    basis: CostBasis,
+    allocate_by: AllocationUnit,
+    usage: Vec<FunctionUsage>,
+    systems: &HashMap<String, CatalogMetadata>,
+) -> ChargebackReport {
+    let team_of = |system: &str| {
+        systems
+            .get(system)
+            .and_then(|metadata| metadata.owner_team.clone())
+            .unwrap_or_else(|| policy.unassigned_team.clone())
+    };
+    let mut grouped: BTreeMap<String, Vec<FunctionUsage>> = BTreeMap::new();
+    for row in usage {
+        let key = match allocate_by {
+            AllocationUnit::Team => team_of(&row.system_name),
+            AllocationUnit::System => row.system_name.clone(),
+        };
+        grouped.entry(key).or_default().push(row);
+    }
+    let mut consumers: Vec<Consumer> = grouped
+        .into_iter()
+        .map(|(name, functions)| Consumer {
+            team: (allocate_by == AllocationUnit::System).then(|| team_of(&name)),
+            work_total: functions.iter().fold(0.0, |sum, f| sum + f.work_total),
+            name,
+            functions,
+            usage_cost: 0.0,
+            shared_cost: 0.0,
+            minimum_adjustment: 0.0,
+        })
+        .collect();
+    let month_work = consumers.iter().fold(0.0, |sum, c| sum + c.work_total);
+    let mut unallocated = 0.0;
+
+    match basis {
+        CostBasis::Fixed(cost) if consumers.is_empty() => unallocated += cost,
+        CostBasis::Fixed(cost) => {
+            let weights: Vec<f64> = consumers.iter().map(|c| c.work_total).collect();
+            for (consumer, amount) in consumers.iter_mut().zip(pro_rata(cost, &weights)) {
+                consumer.usage_cost = amount;
+            }
+        }
+        CostBasis::Rate(rate) => {
+            for consumer in consumers.iter_mut() {
+                consumer.usage_cost = consumer.work_total * rate;
+            }
+        }
+    }
+
+    for pool in &policy.shared_pools {
+        let members: Vec<usize> = (0..consumers.len())
+            .filter(|idx| {
+                let consumer = &consumers[*idx];
+                pool.consumers.as_ref().is_none_or(|names| {
+                    names.iter().any(|name| name == &consumer.name || consumer.team.as_ref() == Some(name))
+                })
+            })
+            .collect();
+        if members.is_empty() {
+            unallocated += pool.cost;
+            continue;
+        }
+        let weights: Vec<f64> = match pool.split {
+            PoolSplit::Proportional => members.iter().map(|idx| consumers[*idx].work_total).collect(),
+            PoolSplit::Equal => vec![0.0; members.len()],
+        };
+        for (idx, amount) in members.iter().zip(pro_rata(pool.cost, &weights)) {
+            consumers[*idx].shared_cost += amount;
+        }
+    }
+
+    let minimum_overrun = apply_minimum(&mut consumers, policy.minimum_charge, basis);
+
+    // Rounded so that functions add up to their consumer and consumers to
+    // the report.
+    let rounded = |raw: Vec<f64>| {
+        let total = raw.iter().sum();
+        split_cents(&raw, cents(total))
+    };
+    let usage_costs = rounded(consumers.iter().map(|c| c.usage_cost).collect());
+    let shared_costs = rounded(consumers.iter().map(|c| c.shared_cost).collect());
+    let total_costs = rounded(consumers.iter().map(|c| c.usage_cost + c.shared_cost + c.minimum_adjustment).collect());
+    let totals = amounts(
+        month_work,
+        month_work,
+        cents(usage_costs.iter().sum()),
+        cents(shared_costs.iter().sum()),
+        cents(total_costs.iter().sum()),
+    );
+
+    let mut charges: Vec<ConsumerCharge> = consumers
+        .into_iter()
+        .zip(usage_costs.into_iter().zip(shared_costs).zip(total_costs))
+        .map(|(consumer, ((usage_cost, shared_cost), total_cost))| {
+            let consumer_amounts = amounts(consumer.work_total, month_work, usage_cost, shared_cost, total_cost);
+            let weights: Vec<f64> = consumer.functions.iter().map(|f| f.work_total).collect();
+            let parts = pro_rata(1.0, &weights);
+            let split = |amount: f64| split_cents(&parts.iter().map(|part| amount * part).collect::<Vec<_>>(), amount);
+            let function_usage = split(consumer_amounts.usage_cost);
+            let function_shared = split(consumer_amounts.shared_cost);
+            let function_adjustment = split(consumer_amounts.minimum_adjustment);
+            let mut functions: Vec<FunctionCharge> = consumer
+                .functions
+                .into_iter()
+                .zip(function_usage.into_iter().zip(function_shared).zip(function_adjustment))
+                .map(|(function, ((usage_cost, shared_cost), adjustment))| FunctionCharge {
+                    amounts: amounts(
+                        function.work_total,
+                        month_work,
+                        usage_cost,
+                        shared_cost,
+                        cents(usage_cost + shared_cost + adjustment),
+                    ),
+                    system_name: function.system_name,
+                    mq_function: function.mq_function,
+                })
+                .collect();
+            functions.sort_by(|a, b| b.amounts.work_total.total_cmp(&a.amounts.work_total));
+            ConsumerCharge {
+                amounts: consumer_amounts,
+                consumer: consumer.name,
+                team: consumer.team,
+                functions,
+            }
+        })
+        .collect();
+    charges.sort_by(|a, b| {
+        b.amounts.total_cost.total_cmp(&a.amounts.total_cost).then_with(|| a.consumer.cmp(&b.consumer))
+    });
+
+    ChargebackReport {
+        month: month.to_string(),
+        from: month.start(),
+        to: month.end(),
+        currency: policy.currency.clone(),
+        allocate_by,
+        platform_cost: match basis {
+            CostBasis::Fixed(cost) => Some(cost),
+            CostBasis::Rate(_) => None,
+        },
+        rate_per_work: match basis {
+            CostBasis::Fixed(_) => None,
+            CostBasis::Rate(rate) => Some(rate),
+        },
+        minimum_charge: policy.minimum_charge,
+        amounts: totals,
+        unallocated_cost: cents(unallocated),
+        minimum_overrun: cents(minimum_overrun),
+        consumers: charges,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::chargeback::SharedPool;
+
+    fn usage(rows: &[(&str, &str, f64)]) -> Vec<FunctionUsage> {
+        rows.iter()
+            .map(|(system, function, work)| FunctionUsage {
+                system_name: system.to_string(),
+                mq_function: function.to_string(),
+                work_total: *work,
+            })
+            .collect()
+    }
+
+    fn allocate(policy: &ChargebackPolicy, basis: CostBasis, rows: &[(&str, &str, f64)]) -> ChargebackReport {
+        let month = ChargebackMonth::parse("2026-09").unwrap();
+        build_report(&month, policy, basis, AllocationUnit::System, usage(rows), &HashMap::new())
+    }
+
+    fn pool(cost: f64, split: PoolSplit, consumers: Option<&[&str]>) -> SharedPool {
+        SharedPool {
+            name: "support".to_string(),
+            cost,
+            split,
+            consumers: consumers.map(|names| names.iter().map(|name| name.to_string()).collect()),
+        }
+    }
+
+    /// (consumer, usage_cost, shared_cost, minimum_adjustment, total_cost), by consumer.
+    fn charges(report: &ChargebackReport) -> Vec<(String, f64, f64, f64, f64)> {
+        let mut charges: Vec<_> = report
+            .consumers
+            .iter()
+            .map(|c| {
+                let a = &c.amounts;
+                (c.consumer.clone(), a.usage_cost, a.shared_cost, a.minimum_adjustment, a.total_cost)
+            })
+            .collect();
+        charges.sort_by(|a, b| a.0.cmp(&b.0));
+        charges
+    }
+
+    fn assert_adds_up(report: &ChargebackReport) {
+        let sum = |amounts: &mut dyn Iterator<Item = f64>| cents(amounts.sum());
+        assert_eq!(sum(&mut report.consumers.iter().map(|c| c.amounts.total_cost)), report.amounts.total_cost);
+        for consumer in &report.consumers {
+            let a = &consumer.amounts;
+            assert_eq!(cents(a.usage_cost + a.shared_cost + a.minimum_adjustment), a.total_cost);
+            assert_eq!(sum(&mut consumer.functions.iter().map(|f| f.amounts.usage_cost)), a.usage_cost);
+            assert_eq!(sum(&mut consumer.functions.iter().map(|f| f.amounts.shared_cost)), a.shared_cost);
+            assert_eq!(sum(&mut consumer.functions.iter().map(|f| f.amounts.total_cost)), a.total_cost);
+        }
+    }
+
+    #[test]
+    fn minimum_is_funded_by_the_consumers_above_it() {
+        let policy = ChargebackPolicy { minimum_charge: 100.0, ..Default::default() };
+        let report = allocate(&policy, CostBasis::Fixed(1000.0), &[("A", "PAY", 900.0), ("B", "PAY", 90.0), ("C", "PAY", 10.0)]);
+        assert_eq!(
+            charges(&report),
+            vec![
+                ("A".to_string(), 900.0, 0.0, -100.0, 800.0),
+                ("B".to_string(), 90.0, 0.0, 10.0, 100.0),
+                ("C".to_string(), 10.0, 0.0, 90.0, 100.0),
+            ]
+        );
+        assert_eq!((report.amounts.total_cost, report.minimum_overrun), (1000.0, 0.0));
+    }
+
+    #[test]
+    fn minimum_is_added_on_top_of_a_rate() {
+        let policy = ChargebackPolicy { minimum_charge: 5.0, ..Default::default() };
+        let report = allocate(&policy, CostBasis::Rate(0.01), &[("A", "PAY", 1000.0), ("B", "PAY", 100.0)]);
+        assert_eq!(
+            charges(&report),
+            vec![("A".to_string(), 10.0, 0.0, 0.0, 10.0), ("B".to_string(), 1.0, 0.0, 4.0, 5.0)]
+        );
+        assert_eq!((report.amounts.total_cost, report.minimum_overrun), (15.0, 0.0));
+    }
+
+    #[test]
+    fn minimums_beyond_the_fixed_cost_are_reported_not_taken_below_zero() {
+        let policy = ChargebackPolicy { minimum_charge: 60.0, ..Default::default() };
+        let report = allocate(&policy, CostBasis::Fixed(100.0), &[("A", "PAY", 70.0), ("B", "PAY", 20.0), ("C", "PAY", 10.0)]);
+        assert!(report.consumers.iter().all(|c| c.amounts.total_cost == 60.0));
+        assert_eq!((report.amounts.total_cost, report.minimum_overrun), (180.0, 80.0));
+        assert_adds_up(&report);
+
+        // A, above the minimum through its shared cost alone, gives up its
+        // whole usage cost but is not charged less than its shared cost.
+        let policy = ChargebackPolicy {
+            minimum_charge: 60.0,
+            shared_pools: vec![pool(90.0, PoolSplit::Equal, Some(&["A"]))],
+            ..Default::default()
+        };
+        let report = allocate(&policy, CostBasis::Fixed(100.0), &[("A", "PAY", 10.0), ("B", "PAY", 45.0), ("C", "PAY", 45.0)]);
+        assert_eq!(
+            charges(&report),
+            vec![
+                ("A".to_string(), 10.0, 90.0, -10.0, 90.0),
+                ("B".to_string(), 45.0, 0.0, 15.0, 60.0),
+                ("C".to_string(), 45.0, 0.0, 15.0, 60.0),
+            ]
+        );
+        assert_eq!(report.minimum_overrun, 20.0);
+        assert_adds_up(&report);
+    }
+
+    #[test]
+    fn zero_work_splits_the_cost_evenly() {
+        let report = allocate(&ChargebackPolicy::default(), CostBasis::Fixed(90.0), &[("A", "PAY", 0.0), ("B", "PAY", 0.0), ("C", "QRY", 0.0)]);
+        assert!(report.consumers.iter().all(|c| c.amounts.usage_cost == 30.0 && c.amounts.share == 0.0));
+        assert_eq!(report.amounts.total_cost, 90.0);
+    }
+
+    #[test]
+    fn pools_split_evenly_or_by_work() {
+        let rows = [("A", "PAY", 10.0), ("B", "PAY", 20.0), ("C", "PAY", 70.0)];
+        let shared = |split| {
+            let policy = ChargebackPolicy { shared_pools: vec![pool(300.0, split, None)], ..Default::default() };
+            charges(&allocate(&policy, CostBasis::Rate(0.0), &rows)).into_iter().map(|c| c.2).collect::<Vec<_>>()
+        };
+        assert_eq!(shared(PoolSplit::Equal), vec![100.0, 100.0, 100.0]);
+        assert_eq!(shared(PoolSplit::Proportional), vec![30.0, 60.0, 210.0]);
+
+        let policy = ChargebackPolicy {
+            shared_pools: vec![pool(300.0, PoolSplit::Proportional, Some(&["A", "C"]))],
+            ..Default::default()
+        };
+        let shared: Vec<f64> = charges(&allocate(&policy, CostBasis::Rate(0.0), &rows)).into_iter().map(|c| c.2).collect();
+        assert_eq!(shared, vec![37.5, 0.0, 262.5]);
+    }
+
+    #[test]
+    fn costs_nobody_takes_are_unallocated() {
+        let policy = ChargebackPolicy {
+            shared_pools: vec![pool(500.0, PoolSplit::Equal, Some(&["Z"]))],
+            ..Default::default()
+        };
+        let report = allocate(&policy, CostBasis::Fixed(1000.0), &[("A", "PAY", 10.0)]);
+        assert_eq!((report.amounts.total_cost, report.unallocated_cost), (1000.0, 500.0));
+
+        let empty = allocate(&policy, CostBasis::Fixed(1000.0), &[]);
+        assert!(empty.consumers.is_empty());
+        assert_eq!((empty.amounts.total_cost, empty.unallocated_cost), (0.0, 1500.0));
+    }
+
+    #[test]
+    fn rounded_amounts_add_up_to_the_cost() {
+        let rows = [("A", "PAY", 1.0), ("B", "PAY", 1.0), ("C", "PAY", 1.0)];
+        let report = allocate(&ChargebackPolicy::default(), CostBasis::Fixed(100.0), &rows);
+        let totals: Vec<f64> = charges(&report).into_iter().map(|c| c.4).collect();
+        assert_eq!(totals, vec![33.34, 33.33, 33.33]);
+        assert_eq!(report.amounts.total_cost, 100.0);
+        assert_adds_up(&report);
+
+        let rows = [("A", "PAY", 1.0), ("A", "QRY", 1.0), ("A", "ORD", 1.0), ("B", "PAY", 3.0)];
+        let policy = ChargebackPolicy {
+            minimum_charge: 0.07,
+            shared_pools: vec![pool(0.1, PoolSplit::Equal, None)],
+            ..Default::default()
+        };
+        for cost in [0.01, 0.1, 10.0, 1000.0 / 3.0] {
+            let report = allocate(&policy, CostBasis::Fixed(cost), &rows);
+            assert_eq!(report.amounts.total_cost, cents(cost + 0.1 + report.minimum_overrun), "cost {}", cost);
+            assert_adds_up(&report);
+        }
+    }
+
+    #[test]
+    fn systems_are_charged_to_their_catalog_team() {
+        let systems = HashMap::from([(
+            "A".to_string(),
+            CatalogMetadata { owner_team: Some("Payments".to_string()), ..Default::default() },
+        )]);
+        let month = ChargebackMonth::parse("2026-09").unwrap();
+        let rows = usage(&[("A", "PAY", 30.0), ("B", "PAY", 10.0)]);
+        let report =
+            build_report(&month, &ChargebackPolicy::default(), CostBasis::Fixed(400.0), AllocationUnit::Team, rows, &systems);
+        let teams: Vec<(String, f64)> = report.consumers.iter().map(|c| (c.consumer.clone(), c.amounts.total_cost)).collect();
+        assert_eq!(teams, vec![("Payments".to_string(), 300.0), ("unassigned".to_string(), 100.0)]);
+    }
+}
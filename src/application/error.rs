@@ -0,0 +1,110 @@
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+/// Problem with one field of a request.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    /// Stable code, e.g. `required`, `invalid`, `range_inverted`,
+    /// `range_too_large`, `unknown_function`, `malformed_json`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Error of an application operation, mapped to an HTTP status and a
+/// stable error code by the interface layer.
+#[derive(Debug)]
+pub enum AppError {
+    Validation { message: String, fields: Vec<FieldError> },
+    NotFound(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    /// The request body exceeds a configured limit.
+    PayloadTooLarge(String),
+    /// The detail is logged, never sent to the client.
+    Internal(String),
+}
+
+impl AppError {
+    /// Validation error not tied to a single field.
+    pub fn invalid(message: impl Into<String>) -> Self {
+        AppError::Validation {
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn field(field: &str, code: &'static str, message: impl Into<String>) -> Self {
+        Self::fields(vec![FieldError::new(field, code, message)])
+    }
+
+    pub fn fields(fields: Vec<FieldError>) -> Self {
+        let message = fields.iter().map(|f| f.message.as_str()).collect::<Vec<_>>().join("; ");
+        AppError::Validation { message, fields }
+    }
+
+    /// Internal error of `operation`, e.g. a failed query.
+    pub fn internal(operation: &str, e: impl fmt::Display) -> Self {
+        AppError::Internal(format!("Error in {}: {}", operation, e))
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation { .. } => "validation_failed",
+            AppError::NotFound(_) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::Conflict(_) => "conflict",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Message safe to show to the client.
+    pub fn public_message(&self) -> &str {
+        match self {
+            AppError::Validation { message, .. } => message,
+            AppError::NotFound(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Conflict(message)
+            | AppError::PayloadTooLarge(message) => message,
+            AppError::Internal(_) => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Internal(detail) => write!(f, "{}: {}", self.code(), detail),
+            _ => write!(f, "{}: {}", self.code(), self.public_message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<Box<dyn std::error::Error>> for AppError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::domain::import::{DuplicatePolicy, ImportReport, ParsedRecord, RejectedRecord};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+const MQ_USAGE_TABLE: &str = "mq_data";
+
+/// Writes per-minute usage records in a single transaction. A record is a
+/// duplicate of a row with the same function, system, time and queue
+/// dimensions; absent dimensions only match absent ones.
+///
+/// Invalid records and, with
+/// `DuplicatePolicy::Reject`, duplicates are reported in `rejected` without
+/// aborting the rest of the batch; `dry_run` validates and rolls back.
+pub fn import_usage(
+    connection: &mut Connection,
+    records: Vec<ParsedRecord>,
+    duplicate_policy: DuplicatePolicy,
+    dry_run: bool,
+) -> rusqlite::Result<ImportReport> {
+    let tx = connection.transaction()?;
+    let report = write_usage(&tx, records, duplicate_policy)?;
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+    Ok(report)
+}
+
+/// Writes usage records like [`import_usage`], within `tx`; the caller commits.
+pub fn write_usage(
+    tx: &Transaction,
+    records: Vec<ParsedRecord>,
+    duplicate_policy: DuplicatePolicy,
+) -> rusqlite::Result<ImportReport> {
+    let mut report = ImportReport::default();
+    {
+        let mut find = tx.prepare(&format!(
+            "SELECT id FROM {} WHERE mq_function = ?1 AND date_time = ?2 AND system_name = ?3 \
+             AND queue_manager IS ?4 AND queue_name IS ?5 AND channel IS ?6 AND granularity = 'minute'",
+            MQ_USAGE_TABLE
+        ))?;
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} (date_time, date, minute, system_name, mq_function, work_total, trans_per_sec, \
+             queue_manager, queue_name, channel, cur_depth, max_depth, oldest_msg_age_secs, granularity) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, 'minute')",
+            MQ_USAGE_TABLE
+        ))?;
+        let mut update = tx.prepare(&format!(
+            "UPDATE {} SET work_total = ?2, trans_per_sec = ?3, cur_depth = ?4, max_depth = ?5, \
+             oldest_msg_age_secs = ?6 WHERE id = ?1",
+            MQ_USAGE_TABLE
+        ))?;
+
+        for (index, record) in records {
+            let record = match record.and_then(|r| r.validate().map(|_| r)) {
+                Ok(record) => record,
+                Err(reason) => {
+                    report.rejected.push(RejectedRecord { index, reason });
+                    continue;
+                }
+            };
+            let date_time = record.date_time.to_rfc3339();
+            let (queue, depth) = (&record.queue, &record.depth);
+            let existing: Option<i64> = find
+                .query_row(
+                    params![
+                        record.mq_function,
+                        date_time,
+                        record.system_name,
+                        queue.queue_manager,
+                        queue.queue_name,
+                        queue.channel,
+                    ],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match (existing, duplicate_policy) {
+                (None, _) => {
+                    insert.execute(params![
+                        date_time,
+                        record.date,
+                        record.minute,
+                        record.system_name,
+                        record.mq_function,
+                        record.work_total,
+                        record.trans_per_sec,
+                        queue.queue_manager,
+                        queue.queue_name,
+                        queue.channel,
+                        depth.cur_depth,
+                        depth.max_depth,
+                        depth.oldest_msg_age_secs,
+                    ])?;
+                    report.inserted += 1;
+                }
+                (Some(_), DuplicatePolicy::Skip) => report.skipped += 1,
+                (Some(id), DuplicatePolicy::Replace) => {
+                    update.execute(params![
+                        id,
+                        record.work_total,
+                        record.trans_per_sec,
+                        depth.cur_depth,
+                        depth.max_depth,
+                        depth.oldest_msg_age_secs,
+                    ])?;
+                    report.replaced += 1;
+                }
+                (Some(_), DuplicatePolicy::Reject) => report.rejected.push(RejectedRecord {
+                    index,
+                    reason: format!(
+                        "duplicate of existing row for {} / {}{} at {}",
+                        record.mq_function,
+                        record.system_name,
+                        queue.columns().iter().map(|(_, value)| format!(" / {}", value)).collect::<String>(),
+                        date_time
+                    ),
+                }),
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,50 @@
+use crate::domain::import::IngestedFile;
+use rusqlite::{params, Connection, OptionalExtension};
+
+const INGESTED_FILES_TABLE: &str = "ingested_files";
+
+/// The earlier load of a file with this checksum, if any.
+pub fn find_ingested_file(connection: &Connection, checksum: &str) -> rusqlite::Result<Option<IngestedFile>> {
+    let sql = format!(
+        "SELECT checksum, file_name, format, loaded_at, inserted, replaced, skipped, rejected FROM {} WHERE checksum = ?1",
+        INGESTED_FILES_TABLE
+    );
+    connection
+        .query_row(&sql, [checksum], |row| {
+            let format: String = row.get(2)?;
+            Ok(IngestedFile {
+                checksum: row.get(0)?,
+                file_name: row.get(1)?,
+                format: format.parse().unwrap_or_default(),
+                loaded_at: row.get(3)?,
+                inserted: row.get(4)?,
+                replaced: row.get(5)?,
+                skipped: row.get(6)?,
+                rejected: row.get(7)?,
+            })
+        })
+        .optional()
+}
+
+pub fn record_ingested_file(connection: &Connection, file: &IngestedFile) -> rusqlite::Result<()> {
+    let sql = format!(
+        "INSERT OR REPLACE INTO {} (checksum, file_name, format, loaded_at, inserted, replaced, skipped, rejected) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        INGESTED_FILES_TABLE
+    );
+    connection.execute(
+        &sql,
+        params![
+            file.checksum,
+            file.file_name,
+            file.format.as_str(),
+            file.loaded_at,
+            file.inserted,
+            file.replaced,
+            file.skipped,
+            file.rejected,
+        ],
+    )?;
+    Ok(())
+}
+
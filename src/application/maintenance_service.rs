@@ -0,0 +1,73 @@
+use chrono::{DateTime, Duration, Local};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+const MAINTENANCE_TABLE: &str = "maintenance";
+
+/// Imports of at least this many records mark the database as busy.
+pub const LARGE_IMPORT_ROWS: usize = 10_000;
+
+/// Markers older than this are left over from a crashed process and ignored.
+const STALE_AFTER_HOURS: i64 = 6;
+
+/// A long-running write (migration, large import) that makes `/readyz`
+/// fail. Markers live in the database so every process sharing it sees
+/// them, including the `import` and `migrate` subcommands.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MaintenanceMarker {
+    pub operation: String,
+    pub started_at: DateTime<Local>,
+}
+
+/// Records the start of an operation; pass the returned id to [`end`].
+pub fn begin(connection: &Connection, operation: &str) -> rusqlite::Result<i64> {
+    let sql = format!(
+        "INSERT INTO {} (operation, started_at) VALUES (?1, ?2)",
+        MAINTENANCE_TABLE
+    );
+    connection.execute(&sql, params![operation, Local::now()])?;
+    Ok(connection.last_insert_rowid())
+}
+
+pub fn end(connection: &Connection, id: i64) -> rusqlite::Result<()> {
+    let sql = format!("DELETE FROM {} WHERE id = ?1", MAINTENANCE_TABLE);
+    connection.execute(&sql, [id])?;
+    Ok(())
+}
+
+/// Runs `operation` between [`begin`] and [`end`], removing the marker
+/// whether or not it succeeds.
+pub fn run<T, E: From<rusqlite::Error>>(
+    connection: &mut Connection,
+    name: &str,
+    operation: impl FnOnce(&mut Connection) -> Result<T, E>,
+) -> Result<T, E> {
+    let id = begin(connection, name)?;
+    let result = operation(connection);
+    end(connection, id)?;
+    result
+}
+
+pub fn active(connection: &Connection) -> rusqlite::Result<Vec<MaintenanceMarker>> {
+    let sql = format!(
+        "SELECT operation, started_at FROM {} ORDER BY started_at",
+        MAINTENANCE_TABLE
+    );
+    let cutoff = Local::now() - Duration::hours(STALE_AFTER_HOURS);
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MaintenanceMarker {
+            operation: row.get(0)?,
+            started_at: row.get(1)?,
+        })
+    })?;
+    let mut markers = Vec::new();
+    for marker in rows {
+        let marker = marker?;
+        if marker.started_at > cutoff {
+            markers.push(marker);
+        }
+    }
+    Ok(markers)
+}
@@ -1,2 +1,12 @@
 pub mod auth_service;
+pub mod catalog_service;
+pub mod chargeback_service;
+pub mod error;
+pub mod import_service;
+pub mod ingested_file_service;
+pub mod maintenance_service;
 pub mod mq_log_usage_service;
+pub mod mq_metrics_service;
+pub mod queue_depth_service;
+pub mod retention_service;
+pub mod user_service;
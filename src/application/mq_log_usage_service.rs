@@ -1,11 +1,15 @@
-use crate::domain::model::MQLogUsage;
-use chrono::{DateTime, Local};
+use crate::domain::model::{DepthMetrics, MQLogUsage, QueueDimensions, SearchCursor, UsageBucket, UsageDimension, UsagePage};
+use chrono::{DateTime, Local, TimeZone};
 use log::debug;
 use rusqlite::ToSql;
 
 const MQ_USAGE_TABLE: &str = "mq_data";
 
-pub async fn get_system_name_list(
+/// Columns read by [`search_row`].
+const SEARCH_COLUMNS: &str = "id, date_time, date, minute, system_name, mq_function, work_total, trans_per_sec, \
+    queue_manager, queue_name, channel, cur_depth, max_depth, oldest_msg_age_secs";
+
+pub fn get_system_name_list(
     connection: &rusqlite::Connection,
     mq_function: &str,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
@@ -25,7 +29,20 @@ pub async fn get_system_name_list(
     Ok(system_names)
 }
 
-pub async fn get_mq_function_list(
+pub fn get_all_system_name_list(
+    connection: &rusqlite::Connection,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let sql = format!("SELECT DISTINCT system_name FROM {} ORDER BY system_name", MQ_USAGE_TABLE);
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    let mut system_names = Vec::new();
+    for system_name in rows {
+        system_names.push(system_name?);
+    }
+    Ok(system_names)
+}
+
+pub fn get_mq_function_list(
     connection: &rusqlite::Connection,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let mut stmt = connection.prepare(
@@ -43,7 +60,45 @@ pub async fn get_mq_function_list(
     Ok(mq_functions)
 }
 
-pub async fn get_all_mq_log_tps_summary(
+/// Distinct values of a queue dimension reported for one function, e.g.
+/// its queue managers. Rows without the dimension are left out.
+pub fn get_dimension_values(
+    connection: &rusqlite::Connection,
+    mq_function: &str,
+    dimension: UsageDimension,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let sql = format!(
+        "SELECT DISTINCT {column} FROM {table} WHERE mq_function = ?1 AND {column} IS NOT NULL ORDER BY {column}",
+        column = dimension.column(),
+        table = MQ_USAGE_TABLE
+    );
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map([mq_function], |row| row.get(0))?;
+    let mut values = Vec::new();
+    for value in rows {
+        values.push(value?);
+    }
+    Ok(values)
+}
+
+/// Timestamp of the newest row, `None` when the table is empty.
+pub fn get_latest_date_time(
+    connection: &rusqlite::Connection,
+) -> Result<Option<DateTime<Local>>, Box<dyn std::error::Error>> {
+    let sql = format!("SELECT MAX(date_time) FROM {}", MQ_USAGE_TABLE);
+    let latest = connection.query_row(&sql, [], |row| row.get(0))?;
+    Ok(latest)
+}
+
+/// Highest row id, which grows with every inserted row. Cheap, unlike
+/// `MAX(date_time)`, so it can be read on every conditional request.
+pub fn get_max_usage_id(connection: &rusqlite::Connection) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+    let sql = format!("SELECT MAX(id) FROM {}", MQ_USAGE_TABLE);
+    let max_id = connection.query_row(&sql, [], |row| row.get(0))?;
+    Ok(max_id)
+}
+
+pub fn get_all_mq_log_tps_summary(
     connection: &rusqlite::Connection,
     start_date: &DateTime<Local>,
     end_date: &DateTime<Local>, 
@@ -79,53 +134,97 @@ pub async fn get_all_mq_log_tps_summary(
             mq_function: "".to_string(),
             work_total: 0.0,
             trans_per_sec,
+            queue: QueueDimensions::default(),
+            depth: DepthMetrics::default(),
         });
     }
     
     Ok(mq_log_usage_list)
 }
     
-pub async fn get_mq_log_tps_summary(
+/// Like [`get_all_mq_log_tps_summary`], but summed per function instead of
+/// over all functions. Ordered by function, then time.
+pub fn get_all_mq_log_tps_summary_by_function(
     connection: &rusqlite::Connection,
     start_date: &DateTime<Local>,
     end_date: &DateTime<Local>,
-    mq_function: &str,
-    system_name: Option<&str>,
 ) -> Result<Vec<MQLogUsage>, Box<dyn std::error::Error>> {
     debug!(
-        "get_mq_log_tps_summary : start_date: {}, end_date: {}, mq_function: {}",
-        start_date, end_date, mq_function
+        "get_all_mq_log_tps_summary_by_function: start_date: {}, end_date: {}",
+        start_date, end_date
     );
 
-    let mut params = vec![mq_function];
-    let mut sql = format!("SELECT date_time , SUM(trans_per_sec) AS total_trans_per_sec FROM {} WHERE mq_function = ?1", MQ_USAGE_TABLE);
-
-    sql.push_str(" AND (date_time BETWEEN ?2 AND ?3)");
+    let sql = format!(
+        "SELECT date_time, mq_function, SUM(trans_per_sec) AS total_trans_per_sec FROM {} WHERE (date_time BETWEEN ?1 AND ?2) GROUP BY mq_function, date_time ORDER BY mq_function, date_time",
+        MQ_USAGE_TABLE
+    );
 
     let start_date_str = start_date.to_rfc3339();
     let end_date_str = end_date.to_rfc3339();
+    let params: Vec<&dyn ToSql> = vec![&start_date_str, &end_date_str];
 
-    params.push(&start_date_str);
-    params.push(&end_date_str);
-
-    if let Some(system_name) = system_name {
-        sql.push_str(" AND system_name = ?4");
-        params.push(system_name);
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), |row| {
+        let date_time: DateTime<Local> = row.get(0)?;
+        Ok(MQLogUsage {
+            date_time,
+            date: "".to_string(),
+            minute: "".to_string(),
+            system_name: "".to_string(),
+            mq_function: row.get(1)?,
+            work_total: 0.0,
+            trans_per_sec: row.get(2)?,
+            queue: QueueDimensions::default(),
+            depth: DepthMetrics::default(),
+        })
+    })?;
+    let mut mq_log_usage_list = Vec::new();
+    for usage in rows {
+        mq_log_usage_list.push(usage?);
     }
+    Ok(mq_log_usage_list)
+}
+
+/// TPS of one function per timestamp, summed over systems and queues
+/// unless `group_by` breaks it down; the group is set on each row. Depths
+/// are summed as well and the oldest message age is the highest.
+pub fn get_mq_log_tps_summary(
+    connection: &rusqlite::Connection,
+    start_date: &DateTime<Local>,
+    end_date: &DateTime<Local>,
+    filter: &UsageFilter<'_>,
+    group_by: Option<UsageDimension>,
+) -> Result<Vec<MQLogUsage>, Box<dyn std::error::Error>> {
+    debug!(
+        "get_mq_log_tps_summary : start_date: {}, end_date: {}, filter: {:?}, group_by: {:?}",
+        start_date, end_date, filter, group_by
+    );
 
-    sql.push_str(" GROUP BY date_time");
+    let group = group_by.map_or("NULL", |dimension| dimension.column());
+    let mut sql = format!(
+        "SELECT date_time, SUM(trans_per_sec) AS total_trans_per_sec, SUM(cur_depth), MAX(oldest_msg_age_secs), {} \
+         FROM {} WHERE (date_time BETWEEN ?1 AND ?2)",
+        group, MQ_USAGE_TABLE
+    );
+    let mut params = vec![start_date.to_rfc3339(), end_date.to_rfc3339()];
+    filter.push_to(&mut sql, &mut params);
+    match group_by {
+        Some(dimension) => sql.push_str(&format!(
+            " GROUP BY {column}, date_time ORDER BY {column}, date_time",
+            column = dimension.column()
+        )),
+        None => sql.push_str(" GROUP BY date_time ORDER BY date_time"),
+    }
 
     let params: Vec<&dyn ToSql> = params.iter().map(|s| s as &dyn ToSql).collect();
-
     let mut stmt = connection.prepare(&sql)?;
     let mut rows = stmt.query(params.as_slice())?;
     let mut mq_log_usage_list = Vec::new();
 
     while let Some(row) = rows.next()? {
-        // idx = 0 , id
         let date_time: DateTime<Local> = row.get(0)?;
         let trans_per_sec: f64 = row.get(1)?;
-        mq_log_usage_list.push(MQLogUsage {
+        let mut usage = MQLogUsage {
             date_time,
             date: "".to_string(),
             minute: "".to_string(),
@@ -133,62 +232,287 @@ pub async fn get_mq_log_tps_summary(
             mq_function: "".to_string(),
             work_total: 0.0,
             trans_per_sec,
-        });
+            queue: QueueDimensions::default(),
+            depth: DepthMetrics {
+                cur_depth: row.get(2)?,
+                max_depth: None,
+                oldest_msg_age_secs: row.get(3)?,
+            },
+        };
+        if let Some(dimension) = group_by {
+            dimension.set(&mut usage, row.get(4)?);
+        }
+        mq_log_usage_list.push(usage);
     }
-    //debug!("get_mq_log_tps_summary : mq_log_usage_list: {:?}", mq_log_usage_list);
     Ok(mq_log_usage_list)
 }
-pub async fn get_mq_log_usage(
-    connection: &rusqlite::Connection,
+
+/// Which rows of a time range a query reads. `None` filters match every
+/// function or system; unset queue dimensions match any queue.
+#[derive(Debug, Clone, Copy)]
+pub struct UsageFilter<'a> {
+    pub mq_function: Option<&'a str>,
+    pub system_name: Option<&'a str>,
+    pub queue: &'a QueueDimensions,
+}
+
+impl UsageFilter<'_> {
+    /// Adds the filter to a `WHERE` clause, numbering its parameters after
+    /// those already in `params`.
+    fn push_to(&self, sql: &mut String, params: &mut Vec<String>) {
+        let filters = [("mq_function", self.mq_function), ("system_name", self.system_name)]
+            .into_iter()
+            .filter_map(|(column, value)| value.map(|value| (column, value)))
+            .chain(self.queue.columns().into_iter().map(|(column, value)| (column, value.as_str())));
+        for (column, value) in filters {
+            params.push(value.to_string());
+            sql.push_str(&format!(" AND {} = ?{}", column, params.len()));
+        }
+    }
+}
+
+/// SQL and parameters selecting the rows matching `filter` in a time range.
+fn usage_search_filter(
     start_date: &DateTime<Local>,
     end_date: &DateTime<Local>,
+    filter: &UsageFilter<'_>,
+) -> (String, Vec<String>) {
+    let mut sql = format!(
+        "SELECT {} FROM {} WHERE (date_time BETWEEN ?1 AND ?2)",
+        SEARCH_COLUMNS, MQ_USAGE_TABLE
+    );
+    let mut params = vec![start_date.to_rfc3339(), end_date.to_rfc3339()];
+    filter.push_to(&mut sql, &mut params);
+    (sql, params)
+}
+
+fn search_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<(SearchCursor, MQLogUsage)> {
+    let cursor = SearchCursor {
+        id: row.get(0)?,
+        date_time: row.get(1)?,
+    };
+    let usage = MQLogUsage {
+        date_time: row.get(1)?,
+        date: row.get(2)?,
+        minute: row.get(3)?,
+        system_name: row.get(4)?,
+        mq_function: row.get(5)?,
+        work_total: row.get(6)?,
+        trans_per_sec: row.get(7)?,
+        queue: QueueDimensions {
+            queue_manager: row.get(8)?,
+            queue_name: row.get(9)?,
+            channel: row.get(10)?,
+        },
+        depth: DepthMetrics {
+            cur_depth: row.get(11)?,
+            max_depth: row.get(12)?,
+            oldest_msg_age_secs: row.get(13)?,
+        },
+    };
+    Ok((cursor, usage))
+}
+
+/// Per-minute rows of one function inserted after row `after_id` and up to
+/// row `up_to_id`, in insertion order, with their row ids. Live streams
+/// resume from the id of the last row they sent.
+pub fn get_mq_log_usage_inserted(
+    connection: &rusqlite::Connection,
     mq_function: &str,
     system_name: Option<&str>,
+    after_id: i64,
+    up_to_id: i64,
+    limit: usize,
+) -> Result<Vec<(i64, MQLogUsage)>, Box<dyn std::error::Error>> {
+    let mut sql = format!(
+        "SELECT {} FROM {} WHERE id > ?1 AND id <= ?2 AND mq_function = ?3 AND granularity = 'minute'",
+        SEARCH_COLUMNS, MQ_USAGE_TABLE
+    );
+    let (limit, mq_function) = (limit as i64, mq_function.to_string());
+    let mut params: Vec<&dyn ToSql> = vec![&after_id, &up_to_id, &mq_function];
+    if let Some(system_name) = &system_name {
+        params.push(system_name);
+        sql.push_str(&format!(" AND system_name = ?{}", params.len()));
+    }
+    params.push(&limit);
+    sql.push_str(&format!(" ORDER BY id LIMIT ?{}", params.len()));
+
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), search_row)?;
+    let mut usage = Vec::new();
+    for row in rows {
+        let (cursor, row) = row?;
+        usage.push((cursor.id, row));
+    }
+    Ok(usage)
+}
+
+/// Rows matching `filter` in a time range, ordered by time. At most
+/// `limit` rows are read when a limit is given.
+pub fn get_mq_log_usage(
+    connection: &rusqlite::Connection,
+    start_date: &DateTime<Local>,
+    end_date: &DateTime<Local>,
+    filter: &UsageFilter<'_>,
+    limit: Option<usize>,
 ) -> Result<Vec<MQLogUsage>, Box<dyn std::error::Error>> {
     debug!(
-        "get_mq_log_usage: start_date: {}, end_date: {}, mq_function: {}",
-        start_date, end_date, mq_function
+        "get_mq_log_usage: start_date: {}, end_date: {}, filter: {:?}",
+        start_date, end_date, filter
     );
-    let mut params = vec![mq_function];
-    let mut sql = format!("SELECT * FROM {} WHERE mq_function = ?1", MQ_USAGE_TABLE);
-
-    sql.push_str(" AND (date_time BETWEEN ?2 AND ?3) ");
+    let (mut sql, params) = usage_search_filter(start_date, end_date, filter);
+    sql.push_str(" ORDER BY date_time, id");
+    if let Some(limit) = limit {
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+    let params: Vec<&dyn ToSql> = params.iter().map(|s| s as &dyn ToSql).collect();
 
-    let start_date_str = start_date.to_rfc3339();
-    let end_date_str = end_date.to_rfc3339();
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), search_row)?;
+    let mut mq_log_usage_list = Vec::new();
+    for row in rows {
+        mq_log_usage_list.push(row?.1);
+    }
+    Ok(mq_log_usage_list)
+}
 
-    params.push(&start_date_str);
-    params.push(&end_date_str);
+/// Up to `page_size` rows following `after` (keyset pagination on
+/// `date_time`, `id`), with the cursor of the next page when more rows
+/// follow.
+pub fn get_mq_log_usage_page(
+    connection: &rusqlite::Connection,
+    start_date: &DateTime<Local>,
+    end_date: &DateTime<Local>,
+    filter: &UsageFilter<'_>,
+    after: Option<&SearchCursor>,
+    page_size: usize,
+) -> Result<UsagePage, Box<dyn std::error::Error>> {
+    let (mut sql, mut params) = usage_search_filter(start_date, end_date, filter);
+    if let Some(after) = after {
+        params.push(after.date_time.clone());
+        params.push(after.id.to_string());
+        sql.push_str(&format!(
+            " AND (date_time > ?{dt} OR (date_time = ?{dt} AND id > CAST(?{id} AS INTEGER)))",
+            dt = params.len() - 1,
+            id = params.len()
+        ));
+    }
+    // One extra row tells whether another page follows.
+    sql.push_str(&format!(" ORDER BY date_time, id LIMIT {}", page_size + 1));
+    let params: Vec<&dyn ToSql> = params.iter().map(|s| s as &dyn ToSql).collect();
 
-    if let Some(system_name) = system_name {
-        sql.push_str(" AND system_name = ?4");
-        params.push(system_name);
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), search_row)?;
+    let mut page = UsagePage {
+        rows: Vec::new(),
+        next_cursor: None,
+    };
+    let mut last = None;
+    for row in rows {
+        let (cursor, usage) = row?;
+        if page.rows.len() == page_size {
+            page.next_cursor = last;
+            break;
+        }
+        page.rows.push(usage);
+        last = Some(cursor);
     }
+    Ok(page)
+}
+
+/// Usage in `bucket_secs` wide buckets (aligned to the epoch), summed over
+/// systems and queues unless `group_by` breaks the result down.
+/// `cur_depth` is the highest per-timestamp sum of depths in each bucket.
+pub fn get_bucketed_usage(
+    connection: &rusqlite::Connection,
+    start_date: &DateTime<Local>,
+    end_date: &DateTime<Local>,
+    bucket_secs: i64,
+    filter: &UsageFilter<'_>,
+    group_by: Option<UsageDimension>,
+) -> Result<Vec<UsageBucket>, Box<dyn std::error::Error>> {
+    debug!(
+        "get_bucketed_usage: start_date: {}, end_date: {}, bucket_secs: {}, filter: {:?}, group_by: {:?}",
+        start_date, end_date, bucket_secs, filter, group_by
+    );
 
+    let mut params = vec![start_date.to_rfc3339(), end_date.to_rfc3339()];
+    let mut filters = String::new();
+    filter.push_to(&mut filters, &mut params);
     let params: Vec<&dyn ToSql> = params.iter().map(|s| s as &dyn ToSql).collect();
+    // Sums per timestamp first, so the TPS of a bucket is the average of
+    // the per-timestamp sums.
+    let sql = format!(
+        "SELECT series, (CAST(strftime('%s', date_time) AS INTEGER) / {bucket_secs}) * {bucket_secs} AS bucket,
+                SUM(tps) / COUNT(*), SUM(work), MAX(depth), MAX(age)
+         FROM (SELECT {series} AS series, date_time, SUM(trans_per_sec) AS tps, SUM(work_total) AS work,
+                      SUM(cur_depth) AS depth, MAX(oldest_msg_age_secs) AS age
+               FROM {table} WHERE (date_time BETWEEN ?1 AND ?2){filters}
+               GROUP BY series, date_time)
+         GROUP BY series, bucket ORDER BY series, bucket",
+        series = group_by.map_or("NULL", |dimension| dimension.column()),
+        table = MQ_USAGE_TABLE,
+        filters = filters,
+        bucket_secs = bucket_secs,
+    );
 
     let mut stmt = connection.prepare(&sql)?;
-    let mut rows = stmt.query(params.as_slice())?;
-    let mut mq_log_usage_list = Vec::new();
+    let rows = stmt.query_map(params.as_slice(), |row| {
+        Ok(UsageBucket {
+            group: row.get(0)?,
+            bucket_start: row.get(1)?,
+            trans_per_sec: row.get(2)?,
+            work_total: row.get(3)?,
+            cur_depth: row.get(4)?,
+            oldest_msg_age_secs: row.get(5)?,
+        })
+    })?;
+    let mut buckets = Vec::new();
+    for bucket in rows {
+        buckets.push(bucket?);
+    }
+    Ok(buckets)
+}
 
-    while let Some(row) = rows.next()? {
-        // idx = 0 , id
-        let date_time: DateTime<Local> = row.get(1)?;
-        let date: String = row.get(2)?;
-        let minute: String = row.get(3)?;
-        let system_name: String = row.get(4)?;
-        let mq_function: String = row.get(5)?;
-        let work_total: f64 = row.get(6)?;
-        let trans_per_sec: f64 = row.get(7)?;
-        mq_log_usage_list.push(MQLogUsage {
-            date_time,
-            date,
-            minute,
-            system_name,
-            mq_function,
-            work_total,
-            trans_per_sec,
-        });
+/// Buckets of one series touched by the per-minute rows inserted after row
+/// `after_id` and up to row `up_to_id`. Touched buckets are recomputed in
+/// full, so rows arriving late update the buckets they fall in.
+pub fn get_bucketed_usage_inserted(
+    connection: &rusqlite::Connection,
+    mq_function: &str,
+    system_name: Option<&str>,
+    bucket_secs: i64,
+    after_id: i64,
+    up_to_id: i64,
+) -> Result<Vec<UsageBucket>, Box<dyn std::error::Error>> {
+    let mut sql = format!(
+        "SELECT MIN(date_time), MAX(date_time) FROM {}
+         WHERE id > ?1 AND id <= ?2 AND mq_function = ?3 AND granularity = 'minute'",
+        MQ_USAGE_TABLE
+    );
+    let mq_function_param = mq_function.to_string();
+    let mut params: Vec<&dyn ToSql> = vec![&after_id, &up_to_id, &mq_function_param];
+    if let Some(system_name) = &system_name {
+        params.push(system_name);
+        sql.push_str(&format!(" AND system_name = ?{}", params.len()));
     }
-    Ok(mq_log_usage_list)
+    let span: (Option<DateTime<Local>>, Option<DateTime<Local>>) =
+        connection.query_row(&sql, params.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let (Some(first), Some(last)) = span else {
+        return Ok(Vec::new());
+    };
+    let bucket_start = first.timestamp().div_euclid(bucket_secs) * bucket_secs;
+    let start_date = Local.timestamp_opt(bucket_start, 0).single().unwrap_or(first);
+    get_bucketed_usage(
+        connection,
+        &start_date,
+        &last,
+        bucket_secs,
+        &UsageFilter {
+            mq_function: Some(mq_function),
+            system_name,
+            queue: &QueueDimensions::default(),
+        },
+        None,
+    )
 }
@@ -0,0 +1,128 @@
+use crate::domain::model::{MQLogUsage, SeriesCount};
+use crate::domain::retention::Granularity;
+use chrono::{DateTime, Local};
+use rusqlite::ToSql;
+
+const MQ_USAGE_TABLE: &str = "mq_data";
+
+fn usage_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<MQLogUsage> {
+    Ok(MQLogUsage::new(
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+/// Newest per-minute usage of every (mq_function, system_name) series that
+/// reported since `since`, summed over queues, ordered by function and
+/// system.
+pub fn get_latest_per_series(
+    connection: &rusqlite::Connection,
+    since: &DateTime<Local>,
+    mq_function: Option<&str>,
+) -> Result<Vec<MQLogUsage>, Box<dyn std::error::Error>> {
+    let mut sql = format!(
+        "SELECT m.date_time, m.system_name, m.mq_function, SUM(m.work_total), SUM(m.trans_per_sec)
+         FROM {table} m
+         JOIN (SELECT mq_function, system_name, MAX(date_time) AS date_time FROM {table}
+               WHERE granularity = 'minute' AND date_time >= ?1
+               GROUP BY mq_function, system_name) latest
+           ON m.mq_function = latest.mq_function
+          AND m.system_name = latest.system_name
+          AND m.date_time = latest.date_time
+         WHERE m.granularity = 'minute'",
+        table = MQ_USAGE_TABLE
+    );
+    let since_str = since.to_rfc3339();
+    let mut params: Vec<&dyn ToSql> = vec![&since_str];
+    if let Some(mq_function) = &mq_function {
+        sql.push_str(" AND m.mq_function = ?2");
+        params.push(mq_function);
+    }
+    sql.push_str(" GROUP BY m.mq_function, m.system_name, m.date_time ORDER BY m.mq_function, m.system_name");
+
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), usage_from_row)?;
+    let mut usages = Vec::new();
+    for usage in rows {
+        usages.push(usage?);
+    }
+    Ok(usages)
+}
+
+/// Rows of one granularity in a time range, optionally for one function
+/// and system.
+#[derive(Debug, Clone)]
+pub struct UsageRange<'a> {
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub granularity: Granularity,
+    pub mq_function: Option<&'a str>,
+    pub system_name: Option<&'a str>,
+}
+
+impl UsageRange<'_> {
+    fn where_clause(&self) -> (String, Vec<String>) {
+        let mut sql = "WHERE granularity = ?1 AND (date_time BETWEEN ?2 AND ?3)".to_string();
+        let mut params = vec![
+            self.granularity.as_str().to_string(),
+            self.from.to_rfc3339(),
+            self.to.to_rfc3339(),
+        ];
+        if let Some(mq_function) = self.mq_function {
+            params.push(mq_function.to_string());
+            sql.push_str(&format!(" AND mq_function = ?{}", params.len()));
+        }
+        if let Some(system_name) = self.system_name {
+            params.push(system_name.to_string());
+            sql.push_str(&format!(" AND system_name = ?{}", params.len()));
+        }
+        (sql, params)
+    }
+}
+
+/// Number of distinct series and of rows in the range, checked against the
+/// export limits before anything is loaded.
+pub fn count_series_and_rows(
+    connection: &rusqlite::Connection,
+    range: &UsageRange<'_>,
+) -> Result<SeriesCount, Box<dyn std::error::Error>> {
+    let (where_clause, params) = range.where_clause();
+    let sql = format!(
+        "SELECT COUNT(*), COALESCE(SUM(rows), 0) FROM
+         (SELECT COUNT(DISTINCT date_time) AS rows FROM {} {} GROUP BY mq_function, system_name)",
+        MQ_USAGE_TABLE, where_clause
+    );
+    let params: Vec<&dyn ToSql> = params.iter().map(|s| s as &dyn ToSql).collect();
+    let counts = connection.query_row(&sql, params.as_slice(), |row| {
+        Ok(SeriesCount {
+            series: row.get(0)?,
+            rows: row.get(1)?,
+        })
+    })?;
+    Ok(counts)
+}
+
+/// Usage in the range summed over queues, ordered by function, system and
+/// time, the order the OpenMetrics exposition needs.
+pub fn get_usage_by_series(
+    connection: &rusqlite::Connection,
+    range: &UsageRange<'_>,
+) -> Result<Vec<MQLogUsage>, Box<dyn std::error::Error>> {
+    let (where_clause, params) = range.where_clause();
+    let sql = format!(
+        "SELECT date_time, system_name, mq_function, SUM(work_total), SUM(trans_per_sec) FROM {} {}
+         GROUP BY mq_function, system_name, date_time ORDER BY mq_function, system_name, date_time",
+        MQ_USAGE_TABLE, where_clause
+    );
+    let params: Vec<&dyn ToSql> = params.iter().map(|s| s as &dyn ToSql).collect();
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), usage_from_row)?;
+    let mut usages = Vec::new();
+    for usage in rows {
+        usages.push(usage?);
+    }
+    Ok(usages)
+}
@@ -0,0 +1,154 @@
+use crate::domain::import::{DuplicatePolicy, ImportReport, RejectedRecord};
+use crate::domain::model::QueueDimensions;
+use crate::domain::queue_depth::{ParsedDepth, QueueDepth};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+const QUEUE_DEPTH_TABLE: &str = "mq_queue_depth";
+
+/// Writes depth samples within `tx`, with the same duplicate handling as
+/// [`crate::application::import_service::import_usage`]; the caller
+/// commits. The `depth_rate` of each sample is computed against the latest
+/// earlier sample of its queue.
+pub fn write_depth(
+    tx: &Transaction,
+    samples: Vec<ParsedDepth>,
+    duplicate_policy: DuplicatePolicy,
+) -> rusqlite::Result<ImportReport> {
+    let mut report = ImportReport::default();
+    {
+        let mut find = tx.prepare(&format!(
+            "SELECT id FROM {} WHERE queue_manager = ?1 AND queue_name = ?2 AND date_time = ?3",
+            QUEUE_DEPTH_TABLE
+        ))?;
+        let mut previous = tx.prepare(&format!(
+            "SELECT date_time, cur_depth FROM {} WHERE queue_manager = ?1 AND queue_name = ?2 AND date_time < ?3 \
+             ORDER BY date_time DESC LIMIT 1",
+            QUEUE_DEPTH_TABLE
+        ))?;
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} (date_time, queue_manager, queue_name, system_name, mq_function, cur_depth, max_depth, \
+             enqueue_rate, dequeue_rate, depth_rate, oldest_msg_age_secs) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            QUEUE_DEPTH_TABLE
+        ))?;
+        let mut update = tx.prepare(&format!(
+            "UPDATE {} SET system_name = ?2, mq_function = ?3, cur_depth = ?4, max_depth = ?5, enqueue_rate = ?6, \
+             dequeue_rate = ?7, depth_rate = ?8, oldest_msg_age_secs = ?9 WHERE id = ?1",
+            QUEUE_DEPTH_TABLE
+        ))?;
+
+        for (index, sample) in samples {
+            let mut sample = match sample.and_then(|s| s.validate().map(|_| s)) {
+                Ok(sample) => sample,
+                Err(reason) => {
+                    report.rejected.push(RejectedRecord { index, reason });
+                    continue;
+                }
+            };
+            let date_time = sample.date_time.to_rfc3339();
+            let key = params![sample.queue_manager, sample.queue_name, date_time];
+            let existing: Option<i64> = find.query_row(key, |row| row.get(0)).optional()?;
+            let last: Option<(DateTime<Local>, i64)> =
+                previous.query_row(key, |row| Ok((row.get(0)?, row.get(1)?))).optional()?;
+            sample.depth_rate = last.and_then(|(at, depth)| {
+                let seconds = (sample.date_time - at).num_milliseconds() as f64 / 1000.0;
+                (seconds > 0.0).then(|| (sample.cur_depth - depth) as f64 / seconds)
+            });
+            match (existing, duplicate_policy) {
+                (None, _) => {
+                    insert.execute(params![
+                        date_time,
+                        sample.queue_manager,
+                        sample.queue_name,
+                        sample.system_name,
+                        sample.mq_function,
+                        sample.cur_depth,
+                        sample.max_depth,
+                        sample.enqueue_rate,
+                        sample.dequeue_rate,
+                        sample.depth_rate,
+                        sample.oldest_msg_age_secs,
+                    ])?;
+                    report.inserted += 1;
+                }
+                (Some(_), DuplicatePolicy::Skip) => report.skipped += 1,
+                (Some(id), DuplicatePolicy::Replace) => {
+                    update.execute(params![
+                        id,
+                        sample.system_name,
+                        sample.mq_function,
+                        sample.cur_depth,
+                        sample.max_depth,
+                        sample.enqueue_rate,
+                        sample.dequeue_rate,
+                        sample.depth_rate,
+                        sample.oldest_msg_age_secs,
+                    ])?;
+                    report.replaced += 1;
+                }
+                (Some(_), DuplicatePolicy::Reject) => report.rejected.push(RejectedRecord {
+                    index,
+                    reason: format!(
+                        "duplicate of existing sample for {}/{} at {}",
+                        sample.queue_manager, sample.queue_name, date_time
+                    ),
+                }),
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Depth samples of the queues of one function (and system, queue manager
+/// or queue) between two instants, inclusive, ordered by time and queue.
+/// Samples have no channel, so `queue.channel` is not used.
+pub fn get_queue_depth(
+    connection: &Connection,
+    mq_function: &str,
+    system_name: Option<&str>,
+    queue: &QueueDimensions,
+    start_date: &DateTime<Local>,
+    end_date: &DateTime<Local>,
+) -> Result<Vec<QueueDepth>, Box<dyn std::error::Error>> {
+    let mut sql = format!(
+        "SELECT date_time, queue_manager, queue_name, system_name, mq_function, cur_depth, max_depth, \
+         enqueue_rate, dequeue_rate, depth_rate, oldest_msg_age_secs FROM {} \
+         WHERE mq_function = ?1 AND date_time BETWEEN ?2 AND ?3",
+        QUEUE_DEPTH_TABLE
+    );
+    let mut params = vec![mq_function.to_string(), start_date.to_rfc3339(), end_date.to_rfc3339()];
+    let filters = [
+        ("system_name", system_name),
+        ("queue_manager", queue.queue_manager.as_deref()),
+        ("queue_name", queue.queue_name.as_deref()),
+    ];
+    for (column, value) in filters {
+        if let Some(value) = value {
+            params.push(value.to_string());
+            sql.push_str(&format!(" AND {} = ?{}", column, params.len()));
+        }
+    }
+    sql.push_str(" ORDER BY date_time, queue_manager, queue_name");
+
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(QueueDepth {
+            date_time: row.get(0)?,
+            queue_manager: row.get(1)?,
+            queue_name: row.get(2)?,
+            system_name: row.get(3)?,
+            mq_function: row.get(4)?,
+            cur_depth: row.get(5)?,
+            max_depth: row.get(6)?,
+            enqueue_rate: row.get(7)?,
+            dequeue_rate: row.get(8)?,
+            depth_rate: row.get(9)?,
+            oldest_msg_age_secs: row.get(10)?,
+        })
+    })?;
+    let mut samples = Vec::new();
+    for sample in rows {
+        samples.push(sample?);
+    }
+    Ok(samples)
+}
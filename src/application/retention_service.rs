@@ -0,0 +1,421 @@
+use crate::domain::retention::{Granularity, RetentionPolicy, RetentionReport};
+use chrono::{DateTime, Duration, Local};
+use log::{debug, info};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+const MQ_USAGE_TABLE: &str = "mq_data";
+const DELETE_BATCH_SIZE: usize = 10_000;
+
+/// Applies `policy` to `mq_data`: minute rows older than `minute_days` are
+/// rolled up into hourly rows, hourly rows older than `hourly_days` into
+/// daily rows, and daily rows older than `daily_days` are deleted.
+///
+/// Each batch runs in its own transaction and the connection lock is
+/// released between batches so API requests are not starved. Progress is
+/// accumulated in `report` even when a later batch fails.
+pub fn apply_retention(
+    db: &Mutex<Connection>,
+    policy: &RetentionPolicy,
+    now: DateTime<Local>,
+    report: &mut RetentionReport,
+) -> rusqlite::Result<()> {
+    let batch_buckets = policy.batch_buckets.max(1);
+
+    if let Some(days) = policy.minute_days {
+        let cutoff = Granularity::Hour.floor(&(now - Duration::days(days as i64)));
+        loop {
+            let mut connection = db.lock().unwrap();
+            let (read, written) = downsample_batch(
+                &mut connection,
+                Granularity::Minute,
+                Granularity::Hour,
+                &cutoff,
+                batch_buckets,
+            )?;
+            if read == 0 {
+                break;
+            }
+            report.transactions += 1;
+            report.minute_rows_downsampled += read;
+            report.hourly_rows_written += written;
+        }
+    }
+
+    if let Some(days) = policy.hourly_days {
+        let cutoff = Granularity::Day.floor(&(now - Duration::days(days as i64)));
+        loop {
+            let mut connection = db.lock().unwrap();
+            let (read, written) = downsample_batch(
+                &mut connection,
+                Granularity::Hour,
+                Granularity::Day,
+                &cutoff,
+                batch_buckets,
+            )?;
+            if read == 0 {
+                break;
+            }
+            report.transactions += 1;
+            report.hourly_rows_downsampled += read;
+            report.daily_rows_written += written;
+        }
+    }
+
+    if let Some(days) = policy.daily_days {
+        let cutoff = Granularity::Day.floor(&(now - Duration::days(days as i64)));
+        loop {
+            let connection = db.lock().unwrap();
+            let deleted = delete_batch(&connection, Granularity::Day, &cutoff)?;
+            if deleted == 0 {
+                break;
+            }
+            report.transactions += 1;
+            report.daily_rows_deleted += deleted;
+        }
+    }
+
+    let rows_removed = report.minute_rows_downsampled + report.hourly_rows_downsampled
+        + report.daily_rows_deleted;
+    if policy.vacuum && rows_removed > 0 {
+        info!("Retention removed {} rows, running VACUUM", rows_removed);
+        db.lock().unwrap().execute_batch("VACUUM")?;
+        report.vacuumed = true;
+    }
+    Ok(())
+}
+
+/// (bucket, system_name, mq_function, queue_manager, queue_name, channel)
+type RollupKey = (DateTime<Local>, String, String, Option<String>, Option<String>, Option<String>);
+
+#[derive(Default)]
+struct Rollup {
+    work_total: f64,
+    /// Sum of `trans_per_sec` times the length of the source rows.
+    tps_seconds: f64,
+    cur_depth: Option<i64>,
+    max_depth: Option<i64>,
+    oldest_msg_age_secs: Option<i64>,
+}
+
+/// Columns read into a [`Rollup`], in the order [`Rollup::add`] expects.
+const ROLLUP_COLUMNS: &str = "date_time, system_name, mq_function, work_total, trans_per_sec, queue_manager, \
+     queue_name, channel, cur_depth, max_depth, oldest_msg_age_secs";
+
+impl Rollup {
+    /// The bucket of `row` in `to`.
+    fn key(row: &rusqlite::Row<'_>, to: Granularity) -> rusqlite::Result<RollupKey> {
+        let date_time: DateTime<Local> = row.get(0)?;
+        Ok((to.floor(&date_time), row.get(1)?, row.get(2)?, row.get(5)?, row.get(6)?, row.get(7)?))
+    }
+
+    /// Adds `row`, whose TPS is averaged over `row_secs` seconds.
+    fn add(&mut self, row: &rusqlite::Row<'_>, row_secs: f64) -> rusqlite::Result<()> {
+        self.work_total += row.get::<_, f64>(3)?;
+        self.tps_seconds += row.get::<_, f64>(4)? * row_secs;
+        self.cur_depth = self.cur_depth.max(row.get(8)?);
+        self.max_depth = self.max_depth.max(row.get(9)?);
+        self.oldest_msg_age_secs = self.oldest_msg_age_secs.max(row.get(10)?);
+        Ok(())
+    }
+}
+
+/// Rolls up the oldest `max_buckets` target buckets of `from` rows older
+/// than `cutoff` into `to` rows. Rows arriving after their bucket was
+/// rolled up are merged into the existing `to` row. Returns (source rows,
+/// written rows).
+fn downsample_batch(
+    connection: &mut Connection,
+    from: Granularity,
+    to: Granularity,
+    cutoff: &DateTime<Local>,
+    max_buckets: u32,
+) -> rusqlite::Result<(usize, usize)> {
+    let tx = connection.transaction()?;
+    let cutoff_str = cutoff.to_rfc3339();
+
+    let oldest: Option<DateTime<Local>> = tx
+        .query_row(
+            &format!(
+                "SELECT MIN(date_time) FROM {} WHERE granularity = ?1 AND date_time < ?2",
+                MQ_USAGE_TABLE
+            ),
+            params![from.as_str(), cutoff_str],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let Some(oldest) = oldest else {
+        return Ok((0, 0));
+    };
+
+    let window_start = to.floor(&oldest);
+    let window_end = to.advance(&window_start, max_buckets as i32).min(*cutoff);
+    let window_start_str = window_start.to_rfc3339();
+    let window_end_str = window_end.to_rfc3339();
+    debug!(
+        "Downsampling {} -> {} for [{}, {})",
+        from.as_str(),
+        to.as_str(),
+        window_start_str,
+        window_end_str
+    );
+
+    // Rows keep their queue dimensions; the depth of a bucket is the
+    // highest seen in it.
+    let mut buckets: BTreeMap<RollupKey, Rollup> = BTreeMap::new();
+    let mut read = 0;
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {} FROM {} WHERE granularity = ?1 AND date_time >= ?2 AND date_time < ?3",
+            ROLLUP_COLUMNS, MQ_USAGE_TABLE
+        ))?;
+        let mut rows = stmt.query(params![from.as_str(), window_start_str, window_end_str])?;
+        let from_secs = from.span().num_seconds() as f64;
+        while let Some(row) = rows.next()? {
+            buckets.entry(Rollup::key(row, to)?).or_default().add(row, from_secs)?;
+            read += 1;
+        }
+    }
+
+    // Late rows, e.g. from a backfill, land in buckets rolled up before:
+    // fold the existing rollup in and replace it.
+    let mut replaced = Vec::new();
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {}, id FROM {} WHERE granularity = ?1 AND date_time >= ?2 AND date_time < ?3",
+            ROLLUP_COLUMNS, MQ_USAGE_TABLE
+        ))?;
+        let mut rows = stmt.query(params![to.as_str(), window_start_str, window_end_str])?;
+        while let Some(row) = rows.next()? {
+            let key = Rollup::key(row, to)?;
+            if let Some(entry) = buckets.get_mut(&key) {
+                let bucket_secs = (to.advance(&key.0, 1) - key.0).num_seconds() as f64;
+                entry.add(row, bucket_secs)?;
+                replaced.push(row.get::<_, i64>(11)?);
+            }
+        }
+    }
+    {
+        let mut delete = tx.prepare(&format!("DELETE FROM {} WHERE id = ?1", MQ_USAGE_TABLE))?;
+        for id in replaced {
+            delete.execute([id])?;
+        }
+    }
+
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} (date_time, date, minute, system_name, mq_function, work_total, trans_per_sec, granularity, \
+             queue_manager, queue_name, channel, cur_depth, max_depth, oldest_msg_age_secs) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            MQ_USAGE_TABLE
+        ))?;
+        for ((bucket, system_name, mq_function, queue_manager, queue_name, channel), rollup) in &buckets {
+            // Average over the whole bucket, so missing samples count as idle time.
+            let bucket_secs = (to.advance(bucket, 1) - *bucket).num_seconds() as f64;
+            insert.execute(params![
+                bucket.to_rfc3339(),
+                bucket.format("%Y-%m-%d").to_string(),
+                bucket.format("%H:%M").to_string(),
+                system_name,
+                mq_function,
+                rollup.work_total,
+                rollup.tps_seconds / bucket_secs,
+                to.as_str(),
+                queue_manager,
+                queue_name,
+                channel,
+                rollup.cur_depth,
+                rollup.max_depth,
+                rollup.oldest_msg_age_secs,
+            ])?;
+        }
+    }
+
+    tx.execute(
+        &format!(
+            "DELETE FROM {} WHERE granularity = ?1 AND date_time >= ?2 AND date_time < ?3",
+            MQ_USAGE_TABLE
+        ),
+        params![from.as_str(), window_start_str, window_end_str],
+    )?;
+    tx.commit()?;
+    Ok((read, buckets.len()))
+}
+
+fn delete_batch(
+    connection: &Connection,
+    granularity: Granularity,
+    cutoff: &DateTime<Local>,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        &format!(
+            "DELETE FROM {table} WHERE id IN (SELECT id FROM {table} WHERE granularity = ?1 AND date_time < ?2 LIMIT ?3)",
+            table = MQ_USAGE_TABLE
+        ),
+        params![granularity.as_str(), cutoff.to_rfc3339(), DELETE_BATCH_SIZE],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::schema;
+    use chrono::TimeZone;
+
+    fn database() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        schema::migrate(&mut connection).unwrap();
+        connection
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 1, 10, hour, minute, 0).unwrap()
+    }
+
+    fn insert(connection: &Connection, date_time: DateTime<Local>, work_total: f64, tps: f64, queue: Option<&str>, depth: Option<i64>) {
+        connection
+            .execute(
+                "INSERT INTO mq_data (date_time, date, minute, system_name, mq_function, work_total, trans_per_sec, \
+                 granularity, queue_name, cur_depth) VALUES (?1, '', '', 'SYS', 'PAY', ?2, ?3, 'minute', ?4, ?5)",
+                params![date_time.to_rfc3339(), work_total, tps, queue, depth],
+            )
+            .unwrap();
+    }
+
+    /// (date_time, queue_name, work_total, trans_per_sec, cur_depth).
+    type Row = (DateTime<Local>, Option<String>, f64, f64, Option<i64>);
+
+    fn rows(connection: &Connection, granularity: Granularity) -> Vec<Row> {
+        let mut stmt = connection
+            .prepare(
+                "SELECT date_time, queue_name, work_total, trans_per_sec, cur_depth FROM mq_data \
+                 WHERE granularity = ?1 ORDER BY date_time, queue_name",
+            )
+            .unwrap();
+        stmt.query_map([granularity.as_str()], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn minute_rows_roll_up_into_hours_averaging_tps_over_the_bucket() {
+        let mut connection = database();
+        for minute in 0..30 {
+            insert(&connection, at(10, minute), 10.0, 2.0, None, None);
+        }
+        insert(&connection, at(11, 5), 5.0, 6.0, None, None);
+        insert(&connection, at(12, 0), 1.0, 1.0, None, None);
+
+        let (read, written) =
+            downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 24).unwrap();
+        assert_eq!((read, written), (31, 2));
+
+        let hourly = rows(&connection, Granularity::Hour);
+        assert_eq!(hourly.len(), 2);
+        assert_eq!((hourly[0].0, hourly[0].2), (at(10, 0), 300.0));
+        // 30 busy minutes at 2 TPS over a 60 minute bucket.
+        assert!((hourly[0].3 - 1.0).abs() < 1e-9);
+        assert_eq!((hourly[1].0, hourly[1].2), (at(11, 0), 5.0));
+        assert!((hourly[1].3 - 0.1).abs() < 1e-9);
+        // The row at the cutoff stays a minute row.
+        assert_eq!(rows(&connection, Granularity::Minute).len(), 1);
+    }
+
+    #[test]
+    fn late_rows_are_merged_into_the_existing_rollup() {
+        let mut connection = database();
+        for minute in 0..30 {
+            insert(&connection, at(10, minute), 10.0, 2.0, Some("Q1"), Some(4));
+        }
+        downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 24).unwrap();
+
+        // A backfill of the other half hour, and a queue not seen before.
+        for minute in 30..60 {
+            insert(&connection, at(10, minute), 10.0, 4.0, Some("Q1"), Some(7));
+        }
+        insert(&connection, at(10, 45), 1.0, 1.0, Some("Q2"), None);
+        let (read, written) =
+            downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 24).unwrap();
+        assert_eq!((read, written), (31, 2));
+
+        let hourly = rows(&connection, Granularity::Hour);
+        assert_eq!(hourly.len(), 2);
+        assert_eq!((hourly[0].0, hourly[0].1.as_deref(), hourly[0].2, hourly[0].4), (at(10, 0), Some("Q1"), 600.0, Some(7)));
+        // 30 minutes at 2 TPS and 30 at 4 TPS over the hour.
+        assert!((hourly[0].3 - 3.0).abs() < 1e-9);
+        assert_eq!((hourly[1].1.as_deref(), hourly[1].2), (Some("Q2"), 1.0));
+
+        // The same holds a level up, for hours arriving after their day.
+        let next_day = Local.with_ymd_and_hms(2026, 1, 11, 0, 0, 0).unwrap();
+        downsample_batch(&mut connection, Granularity::Hour, Granularity::Day, &next_day, 24).unwrap();
+        insert(&connection, at(11, 0), 3.0, 1.0, Some("Q1"), None);
+        downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 24).unwrap();
+        downsample_batch(&mut connection, Granularity::Hour, Granularity::Day, &next_day, 24).unwrap();
+        let daily = rows(&connection, Granularity::Day);
+        assert_eq!(daily.iter().map(|row| (row.1.as_deref(), row.2)).collect::<Vec<_>>(), [(Some("Q1"), 603.0), (Some("Q2"), 1.0)]);
+        assert!(rows(&connection, Granularity::Hour).is_empty());
+    }
+
+    #[test]
+    fn a_batch_covers_at_most_max_buckets() {
+        let mut connection = database();
+        for hour in 8..11 {
+            insert(&connection, at(hour, 15), 1.0, 1.0, None, None);
+        }
+        let (read, written) =
+            downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 2).unwrap();
+        assert_eq!((read, written), (2, 2));
+        assert_eq!(rows(&connection, Granularity::Minute).len(), 1);
+        let (read, _) =
+            downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 2).unwrap();
+        assert_eq!(read, 1);
+        let (read, _) =
+            downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 2).unwrap();
+        assert_eq!(read, 0);
+    }
+
+    #[test]
+    fn queues_stay_apart_and_keep_their_highest_depth() {
+        let mut connection = database();
+        insert(&connection, at(10, 0), 1.0, 1.0, Some("Q1"), Some(5));
+        insert(&connection, at(10, 1), 1.0, 1.0, Some("Q1"), Some(9));
+        insert(&connection, at(10, 2), 1.0, 1.0, Some("Q2"), None);
+        downsample_batch(&mut connection, Granularity::Minute, Granularity::Hour, &at(12, 0), 24).unwrap();
+
+        let hourly = rows(&connection, Granularity::Hour);
+        assert_eq!(hourly.len(), 2);
+        assert_eq!((hourly[0].1.as_deref(), hourly[0].2, hourly[0].4), (Some("Q1"), 2.0, Some(9)));
+        assert_eq!((hourly[1].1.as_deref(), hourly[1].2, hourly[1].4), (Some("Q2"), 1.0, None));
+    }
+
+    #[test]
+    fn policy_downsamples_then_deletes_old_days() {
+        let connection = database();
+        let now = Local.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        insert(&connection, now - Duration::days(40), 1.0, 1.0, None, None);
+        insert(&connection, now - Duration::days(5), 2.0, 1.0, None, None);
+        insert(&connection, now - Duration::hours(1), 3.0, 1.0, None, None);
+        let db = Mutex::new(connection);
+        let policy = RetentionPolicy {
+            minute_days: Some(1),
+            hourly_days: Some(2),
+            daily_days: Some(30),
+            batch_buckets: 24,
+            vacuum: false,
+        };
+        let mut report = RetentionReport::default();
+        apply_retention(&db, &policy, now, &mut report).unwrap();
+
+        assert_eq!(report.minute_rows_downsampled, 2);
+        assert_eq!(report.hourly_rows_downsampled, 2);
+        assert_eq!(report.daily_rows_deleted, 1);
+        let connection = db.lock().unwrap();
+        let daily = rows(&connection, Granularity::Day);
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].2, 2.0);
+        assert_eq!(rows(&connection, Granularity::Minute).len(), 1);
+    }
+}
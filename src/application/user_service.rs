@@ -0,0 +1,101 @@
+use crate::domain::user::User;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension};
+
+const USERS_TABLE: &str = "users";
+
+/// Argon2id keyed with `salt_key` as a server-side secret, so leaked hashes
+/// cannot be verified without the configuration.
+fn hasher(salt_key: &str) -> Result<Argon2<'_>, Box<dyn std::error::Error>> {
+    if salt_key.is_empty() {
+        return Ok(Argon2::default());
+    }
+    Argon2::new_with_secret(salt_key.as_bytes(), Algorithm::Argon2id, Version::V0x13, Params::default())
+        .map_err(|e| e.to_string().into())
+}
+
+pub fn hash_password(password: &str, salt_key: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = hasher(salt_key)?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| e.to_string())?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str, salt_key: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(password_hash) else {
+        return false;
+    };
+    hasher(salt_key)
+        .map(|argon2| argon2.verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+
+pub fn find_user(
+    connection: &Connection,
+    username: &str,
+) -> Result<Option<User>, Box<dyn std::error::Error>> {
+    let sql = format!(
+        "SELECT username, password_hash, created_at FROM {} WHERE username = ?1",
+        USERS_TABLE
+    );
+    let user = connection
+        .query_row(&sql, [username], |row| {
+            Ok(User {
+                username: row.get(0)?,
+                password_hash: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })
+        .optional()?;
+    Ok(user)
+}
+
+pub fn list_users(connection: &Connection) -> Result<Vec<User>, Box<dyn std::error::Error>> {
+    let sql = format!(
+        "SELECT username, password_hash, created_at FROM {} ORDER BY username",
+        USERS_TABLE
+    );
+    let mut stmt = connection.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| {
+        let created_at: DateTime<Local> = row.get(2)?;
+        Ok(User {
+            username: row.get(0)?,
+            password_hash: row.get(1)?,
+            created_at,
+        })
+    })?;
+    let mut users = Vec::new();
+    for user in rows {
+        users.push(user?);
+    }
+    Ok(users)
+}
+
+/// Creates the user, or replaces the password of an existing one.
+pub fn upsert_user(
+    connection: &Connection,
+    username: &str,
+    password: &str,
+    salt_key: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if username.trim().is_empty() {
+        return Err("username must not be empty".into());
+    }
+    let password_hash = hash_password(password, salt_key)?;
+    let sql = format!(
+        "INSERT INTO {} (username, password_hash, created_at) VALUES (?1, ?2, ?3) \
+         ON CONFLICT(username) DO UPDATE SET password_hash = excluded.password_hash",
+        USERS_TABLE
+    );
+    connection.execute(&sql, params![username.trim(), password_hash, Local::now().to_rfc3339()])?;
+    Ok(())
+}
+
+pub fn delete_user(connection: &Connection, username: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let sql = format!("DELETE FROM {} WHERE username = ?1", USERS_TABLE);
+    Ok(connection.execute(&sql, [username])? > 0)
+}
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// What a catalog entry describes. Paths accept the plural too
+/// (`/catalog/systems/...`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogKind {
+    #[serde(alias = "systems")]
+    System,
+    #[serde(alias = "functions")]
+    Function,
+}
+
+impl CatalogKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatalogKind::System => "system",
+            CatalogKind::Function => "function",
+        }
+    }
+}
+
+impl FromStr for CatalogKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "system" | "systems" => Ok(CatalogKind::System),
+            "function" | "functions" => Ok(CatalogKind::Function),
+            other => Err(format!("unknown kind '{}' (system, function)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Criticality {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Criticality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Criticality::Low => "low",
+            Criticality::Medium => "medium",
+            Criticality::High => "high",
+            Criticality::Critical => "critical",
+        }
+    }
+}
+
+impl FromStr for Criticality {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "low" => Ok(Criticality::Low),
+            "medium" => Ok(Criticality::Medium),
+            "high" => Ok(Criticality::High),
+            "critical" => Ok(Criticality::Critical),
+            other => Err(format!("unknown criticality '{}' (low, medium, high, critical)", other)),
+        }
+    }
+}
+
+/// What is known about a system or function beyond its name. Every field
+/// is optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_team: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub criticality: Option<Criticality>,
+    /// Lower-case labels such as `payments` or `tier-1`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// TPS the function or system normally runs at, for spotting outliers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_tps_min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_tps_max: Option<f64>,
+}
+
+impl CatalogMetadata {
+    /// Text fields trimmed with blanks dropped, and tags trimmed,
+    /// lower-cased, sorted and deduplicated.
+    pub fn normalized(self) -> Self {
+        let text = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut tags: Vec<String> = self
+            .tags
+            .into_iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        Self {
+            display_name: text(self.display_name),
+            description: text(self.description),
+            owner_team: text(self.owner_team),
+            contact: text(self.contact),
+            tags,
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("expected_tps_min", self.expected_tps_min), ("expected_tps_max", self.expected_tps_max)] {
+            if let Some(value) = value
+                && (!value.is_finite() || value < 0.0)
+            {
+                return Err(format!("{} must be a non-negative number, got {}", name, value));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.expected_tps_min, self.expected_tps_max)
+            && min > max
+        {
+            return Err("expected_tps_min must not exceed expected_tps_max".to_string());
+        }
+        if let Some(tag) = self.tags.iter().find(|tag| tag.contains([',', ';'])) {
+            return Err(format!("tag '{}' must not contain ',' or ';'", tag));
+        }
+        Ok(())
+    }
+
+    /// Whether every one of `tags` is among the entry's tags.
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+}
+
+/// Metadata of one system or function.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CatalogEntry {
+    pub kind: CatalogKind,
+    pub name: String,
+    #[serde(flatten)]
+    pub metadata: CatalogMetadata,
+    pub updated_at: DateTime<Local>,
+}
+
+/// An entry read from a catalog import file.
+#[derive(Debug, Clone)]
+pub struct CatalogRecord {
+    pub kind: CatalogKind,
+    pub name: String,
+    pub metadata: CatalogMetadata,
+}
+
+/// A catalog record, or the reason it could not be read, paired with its
+/// position in the file (line number for CSV, entry number for YAML).
+pub type ParsedCatalogRecord = (usize, Result<CatalogRecord, String>);
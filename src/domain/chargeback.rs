@@ -0,0 +1,252 @@
+use chrono::{DateTime, Datelike, Local, Months, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// What costs are charged to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AllocationUnit {
+    /// The `owner_team` of the system in the catalog.
+    #[default]
+    Team,
+    System,
+}
+
+impl AllocationUnit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AllocationUnit::Team => "team",
+            AllocationUnit::System => "system",
+        }
+    }
+}
+
+/// How a shared-cost pool is split among its consumers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolSplit {
+    /// By work_total, like the platform cost.
+    #[default]
+    Proportional,
+    Equal,
+}
+
+/// A cost outside the platform cost, e.g. licences or a support team.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SharedPool {
+    pub name: String,
+    /// Monthly amount.
+    pub cost: f64,
+    #[serde(default)]
+    pub split: PoolSplit,
+    /// Teams or systems sharing the pool; every consumer when absent.
+    #[serde(default)]
+    pub consumers: Option<Vec<String>>,
+}
+
+/// How the monthly cost is set and allocated. The cost of a month is, in
+/// order, its `monthly_costs` entry, `monthly_cost`, or `rate_per_work`
+/// times the month's work_total.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChargebackPolicy {
+    /// Shown in reports; amounts are plain numbers.
+    pub currency: Option<String>,
+    pub monthly_cost: Option<f64>,
+    /// Cost of particular months, keyed `YYYY-MM`.
+    pub monthly_costs: BTreeMap<String, f64>,
+    /// Cost per unit of work_total.
+    pub rate_per_work: Option<f64>,
+    pub allocate_by: AllocationUnit,
+    /// Least amount charged to a consumer with usage in the month.
+    pub minimum_charge: f64,
+    /// Team of systems without an `owner_team` in the catalog.
+    pub unassigned_team: String,
+    pub shared_pools: Vec<SharedPool>,
+}
+
+impl Default for ChargebackPolicy {
+    fn default() -> Self {
+        Self {
+            currency: None,
+            monthly_cost: None,
+            monthly_costs: BTreeMap::new(),
+            rate_per_work: None,
+            allocate_by: AllocationUnit::Team,
+            minimum_charge: 0.0,
+            unassigned_team: "unassigned".to_string(),
+            shared_pools: Vec::new(),
+        }
+    }
+}
+
+fn non_negative(name: &str, value: f64) -> Result<(), String> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("{} must be a non-negative number, got {}", name, value));
+    }
+    Ok(())
+}
+
+impl ChargebackPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.monthly_cost.is_some() && self.rate_per_work.is_some() {
+            return Err("set monthly_cost or rate_per_work, not both".to_string());
+        }
+        if let Some(cost) = self.monthly_cost {
+            non_negative("monthly_cost", cost)?;
+        }
+        if let Some(rate) = self.rate_per_work {
+            non_negative("rate_per_work", rate)?;
+        }
+        for (month, cost) in &self.monthly_costs {
+            ChargebackMonth::parse(month).map_err(|e| format!("monthly_costs: {}", e))?;
+            non_negative(&format!("monthly_costs.{}", month), *cost)?;
+        }
+        non_negative("minimum_charge", self.minimum_charge)?;
+        if self.unassigned_team.trim().is_empty() {
+            return Err("unassigned_team must not be empty".to_string());
+        }
+        for pool in &self.shared_pools {
+            if pool.name.trim().is_empty() {
+                return Err("shared_pools: name must not be empty".to_string());
+            }
+            non_negative(&format!("shared_pools.{}.cost", pool.name), pool.cost)?;
+            if pool.consumers.as_ref().is_some_and(|consumers| consumers.is_empty()) {
+                return Err(format!("shared_pools.{}: consumers must not be empty when set", pool.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// The cost of `month`, unless overridden by the caller.
+    pub fn basis(&self, month: &ChargebackMonth) -> Option<CostBasis> {
+        self.monthly_costs
+            .get(&month.to_string())
+            .or(self.monthly_cost.as_ref())
+            .map(|cost| CostBasis::Fixed(*cost))
+            .or(self.rate_per_work.map(CostBasis::Rate))
+    }
+}
+
+/// Where the platform cost of a month comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CostBasis {
+    /// An amount split by work_total.
+    Fixed(f64),
+    /// An amount per unit of work_total.
+    Rate(f64),
+}
+
+/// A calendar month in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChargebackMonth(NaiveDate);
+
+impl ChargebackMonth {
+    /// Parses `YYYY-MM`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        NaiveDate::parse_from_str(&format!("{}-01", value.trim()), "%Y-%m-%d")
+            .map(Self)
+            .map_err(|_| format!("invalid month '{}', expected YYYY-MM", value))
+    }
+
+    /// The month before the one containing `now`.
+    pub fn previous(now: DateTime<Local>) -> Self {
+        let first = now.date_naive().with_day(1).unwrap_or(now.date_naive());
+        Self(first - Months::new(1))
+    }
+
+    fn local_midnight(date: NaiveDate) -> DateTime<Local> {
+        let naive = date.and_time(NaiveTime::MIN);
+        Local.from_local_datetime(&naive).earliest().unwrap_or_else(|| Local.from_utc_datetime(&naive))
+    }
+
+    /// First instant of the month.
+    pub fn start(&self) -> DateTime<Local> {
+        Self::local_midnight(self.0)
+    }
+
+    /// First instant of the next month.
+    pub fn end(&self) -> DateTime<Local> {
+        Self::local_midnight(self.0 + Months::new(1))
+    }
+}
+
+impl std::fmt::Display for ChargebackMonth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%Y-%m"))
+    }
+}
+
+/// work_total of one system and function over a month.
+#[derive(Debug, Clone)]
+pub struct FunctionUsage {
+    pub system_name: String,
+    pub mq_function: String,
+    pub work_total: f64,
+}
+
+/// Amounts of a consumer or of one of its functions, in cents that add
+/// up: `total_cost` is `usage_cost + shared_cost + minimum_adjustment`,
+/// and functions sum to their consumer.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ChargeAmounts {
+    pub work_total: f64,
+    /// Fraction of the month's work_total.
+    pub share: f64,
+    pub usage_cost: f64,
+    pub shared_cost: f64,
+    /// Raise to the minimum charge; with a fixed cost, negative for the
+    /// consumers funding other consumers' raises.
+    pub minimum_adjustment: f64,
+    pub total_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FunctionCharge {
+    pub system_name: String,
+    pub mq_function: String,
+    #[serde(flatten)]
+    pub amounts: ChargeAmounts,
+}
+
+/// A team or system and what it is charged, with the split per system and
+/// function pro rata to work_total.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConsumerCharge {
+    pub consumer: String,
+    /// Team of the system, when allocating to systems.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    #[serde(flatten)]
+    pub amounts: ChargeAmounts,
+    pub functions: Vec<FunctionCharge>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ChargebackReport {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    pub allocate_by: AllocationUnit,
+    /// Platform cost split by work_total, when fixed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform_cost: Option<f64>,
+    /// Cost per unit of work_total, when charged by rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_per_work: Option<f64>,
+    pub minimum_charge: f64,
+    #[serde(flatten)]
+    pub amounts: ChargeAmounts,
+    /// Cost no consumer took: the platform cost of a month without usage,
+    /// or pools none of whose consumers had usage.
+    pub unallocated_cost: f64,
+    /// What the minimum charges cost beyond a fixed platform cost too
+    /// small to fund them; 0 otherwise.
+    pub minimum_overrun: f64,
+    pub consumers: Vec<ConsumerCharge>,
+}
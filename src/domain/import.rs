@@ -0,0 +1,130 @@
+use crate::domain::model::MQLogUsage;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// What to do with a record that matches a per-minute row of `mq_data` on
+/// mq_function, date_time, system_name and queue dimensions (queue
+/// manager, queue, channel; absent ones only match absent ones), or for
+/// depth samples a sample of the same queue at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// Keep the stored row and drop the new one.
+    #[default]
+    Skip,
+    /// Overwrite the stored values with the new ones.
+    Replace,
+    /// Refuse the new record and report it as rejected.
+    Reject,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "skip" => Ok(DuplicatePolicy::Skip),
+            "replace" => Ok(DuplicatePolicy::Replace),
+            "reject" => Ok(DuplicatePolicy::Reject),
+            other => Err(format!("unknown duplicate policy '{}' (skip, replace, reject)", other)),
+        }
+    }
+}
+
+/// Format of a usage file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    /// CSV with a header row.
+    #[default]
+    Csv,
+    /// IBM MQ statistics and accounting messages written by `amqsevt -o json`.
+    Amqsevt,
+    /// runmqsc output of queue depth commands, loaded as depth samples.
+    Runmqsc,
+}
+
+impl FromStr for SourceFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "csv" => Ok(SourceFormat::Csv),
+            "amqsevt" => Ok(SourceFormat::Amqsevt),
+            "runmqsc" => Ok(SourceFormat::Runmqsc),
+            other => Err(format!("unknown format '{}' (csv, amqsevt, runmqsc)", other)),
+        }
+    }
+}
+
+impl SourceFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceFormat::Csv => "csv",
+            SourceFormat::Amqsevt => "amqsevt",
+            SourceFormat::Runmqsc => "runmqsc",
+        }
+    }
+
+    /// What the index of a record counts in files of this format.
+    pub fn record_unit(&self) -> &'static str {
+        match self {
+            SourceFormat::Csv | SourceFormat::Runmqsc => "line",
+            SourceFormat::Amqsevt => "event",
+        }
+    }
+
+    /// Whether files of this format name queues that must be mapped to
+    /// functions and systems.
+    pub fn needs_queue_mapping(&self) -> bool {
+        matches!(self, SourceFormat::Amqsevt | SourceFormat::Runmqsc)
+    }
+}
+
+/// A record read from an input source, or the reason it could not be read,
+/// paired with its position in the source (line number for files).
+pub type ParsedRecord = (usize, Result<MQLogUsage, String>);
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RejectedRecord {
+    /// Position of the record in its source (line number for files).
+    pub index: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    pub rejected: Vec<RejectedRecord>,
+}
+
+impl ImportReport {
+    pub fn changed_rows(&self) -> usize {
+        self.inserted + self.replaced
+    }
+
+    pub fn merge(&mut self, other: ImportReport) {
+        self.inserted += other.inserted;
+        self.replaced += other.replaced;
+        self.skipped += other.skipped;
+        self.rejected.extend(other.rejected);
+    }
+}
+
+/// A file loaded by the inbox watcher. The SHA-256 of its content keeps
+/// a copy of it from being loaded again.
+#[derive(Debug, Clone)]
+pub struct IngestedFile {
+    pub checksum: String,
+    pub file_name: String,
+    pub format: SourceFormat,
+    pub loaded_at: DateTime<Local>,
+    pub inserted: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+}
@@ -1,2 +1,9 @@
 pub mod auth;
+pub mod catalog;
+pub mod chargeback;
+pub mod import;
 pub mod model;
+pub mod queue_depth;
+pub mod queue_mapping;
+pub mod retention;
+pub mod user;
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MQLogUsage {
@@ -10,4 +11,224 @@ pub struct MQLogUsage {
     pub mq_function: String,
     pub work_total: f64,
     pub trans_per_sec: f64,
+    #[serde(flatten)]
+    pub queue: QueueDimensions,
+    #[serde(flatten)]
+    pub depth: DepthMetrics,
+}
+
+/// Queue manager, queue and channel a row is about. Rows loaded before
+/// these existed, or that cover a whole function, have none. As a search
+/// filter, each field that is set must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct QueueDimensions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_manager: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+}
+
+impl QueueDimensions {
+    /// Column and value of every field that is set.
+    pub fn columns(&self) -> Vec<(&'static str, &String)> {
+        [
+            ("queue_manager", &self.queue_manager),
+            ("queue_name", &self.queue_name),
+            ("channel", &self.channel),
+        ]
+        .into_iter()
+        .filter_map(|(column, value)| value.as_ref().map(|value| (column, value)))
+        .collect()
+    }
+
+    /// Trimmed copy without blank fields.
+    pub fn normalized(&self) -> Self {
+        let clean = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+        Self {
+            queue_manager: clean(&self.queue_manager),
+            queue_name: clean(&self.queue_name),
+            channel: clean(&self.channel),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns().is_empty()
+    }
+}
+
+/// Depth of the queue a row is about, when the source reports it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DepthMetrics {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cur_depth: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<i64>,
+    /// Age of the oldest message on the queue, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oldest_msg_age_secs: Option<i64>,
+}
+
+/// Column summaries can be broken down by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageDimension {
+    System,
+    QueueManager,
+    Queue,
+    Channel,
+}
+
+impl UsageDimension {
+    pub fn column(&self) -> &'static str {
+        match self {
+            UsageDimension::System => "system_name",
+            UsageDimension::QueueManager => "queue_manager",
+            UsageDimension::Queue => "queue_name",
+            UsageDimension::Channel => "channel",
+        }
+    }
+
+    /// Value of this dimension in `usage`.
+    pub fn value<'a>(&self, usage: &'a MQLogUsage) -> Option<&'a str> {
+        match self {
+            UsageDimension::System => Some(usage.system_name.as_str()),
+            UsageDimension::QueueManager => usage.queue.queue_manager.as_deref(),
+            UsageDimension::Queue => usage.queue.queue_name.as_deref(),
+            UsageDimension::Channel => usage.queue.channel.as_deref(),
+        }
+    }
+
+    /// Sets this dimension of `usage`, e.g. to label a grouped summary row.
+    pub fn set(&self, usage: &mut MQLogUsage, value: Option<String>) {
+        match self {
+            UsageDimension::System => usage.system_name = value.unwrap_or_default(),
+            UsageDimension::QueueManager => usage.queue.queue_manager = value,
+            UsageDimension::Queue => usage.queue.queue_name = value,
+            UsageDimension::Channel => usage.queue.channel = value,
+        }
+    }
+}
+
+impl MQLogUsage {
+    /// Builds a per-minute usage row, deriving the `date` and `minute` columns.
+    pub fn new(
+        date_time: DateTime<Local>,
+        system_name: String,
+        mq_function: String,
+        work_total: f64,
+        trans_per_sec: f64,
+    ) -> Self {
+        Self {
+            date: date_time.format("%Y-%m-%d").to_string(),
+            minute: date_time.format("%H:%M").to_string(),
+            date_time,
+            system_name,
+            mq_function,
+            work_total,
+            trans_per_sec,
+            queue: QueueDimensions::default(),
+            depth: DepthMetrics::default(),
+        }
+    }
+
+    pub fn with_queue(mut self, queue: QueueDimensions) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: DepthMetrics) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Reason this row cannot be stored, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.system_name.trim().is_empty() {
+            return Err("system_name must not be empty".to_string());
+        }
+        if self.mq_function.trim().is_empty() {
+            return Err("mq_function must not be empty".to_string());
+        }
+        if !self.work_total.is_finite() || self.work_total < 0.0 {
+            return Err(format!("work_total must be a non-negative number, got {}", self.work_total));
+        }
+        if !self.trans_per_sec.is_finite() || self.trans_per_sec < 0.0 {
+            return Err(format!(
+                "trans_per_sec must be a non-negative number, got {}",
+                self.trans_per_sec
+            ));
+        }
+        for (column, value) in self.queue.columns() {
+            if value.trim().is_empty() {
+                return Err(format!("{} must not be empty when given", column));
+            }
+        }
+        let depth = &self.depth;
+        for (name, value) in [
+            ("cur_depth", depth.cur_depth),
+            ("max_depth", depth.max_depth),
+            ("oldest_msg_age_secs", depth.oldest_msg_age_secs),
+        ] {
+            if value.is_some_and(|v| v < 0) {
+                return Err(format!("{} must not be negative", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Usage aggregated over one time bucket: the average of the per-timestamp
+/// TPS sums and the total work.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageBucket {
+    /// Value of the dimension the series is broken down by, if any.
+    pub group: Option<String>,
+    /// Start of the bucket, in seconds since the epoch.
+    pub bucket_start: i64,
+    pub trans_per_sec: f64,
+    pub work_total: f64,
+    /// Highest per-timestamp sum of `cur_depth` in the bucket.
+    pub cur_depth: Option<i64>,
+    /// Age of the oldest message seen in the bucket.
+    pub oldest_msg_age_secs: Option<i64>,
+}
+
+/// Keyset position in a search: the `date_time` (as stored) and `id` of the
+/// last row returned. Searches are ordered by `date_time`, then `id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchCursor {
+    pub date_time: String,
+    pub id: i64,
+}
+
+impl SearchCursor {
+    /// Opaque form handed to clients.
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}|{}", self.id, self.date_time))
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let decoded = String::from_utf8(hex::decode(value).ok()?).ok()?;
+        let (id, date_time) = decoded.split_once('|')?;
+        Some(Self {
+            date_time: date_time.to_string(),
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+/// One page of a search and the cursor of the next page, if there is one.
+#[derive(Debug, Clone)]
+pub struct UsagePage {
+    pub rows: Vec<MQLogUsage>,
+    pub next_cursor: Option<SearchCursor>,
+}
+
+/// Distinct series and rows of a usage range.
+#[derive(Debug, Clone, Copy)]
+pub struct SeriesCount {
+    pub series: usize,
+    pub rows: usize,
 }
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Depth of one local queue at one instant, attributed to the function and
+/// system its queue rule names.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QueueDepth {
+    pub date_time: DateTime<Local>,
+    pub queue_manager: String,
+    pub queue_name: String,
+    pub system_name: String,
+    pub mq_function: String,
+    pub cur_depth: i64,
+    pub max_depth: Option<i64>,
+    /// Age of the oldest message in seconds (`MSGAGE`), when queue
+    /// monitoring is on.
+    pub oldest_msg_age_secs: Option<i64>,
+    /// Messages put per second since the previous statistics reset, when
+    /// the snapshot holds `RESET QSTATS` output.
+    pub enqueue_rate: Option<f64>,
+    /// Messages got per second, like `enqueue_rate`.
+    pub dequeue_rate: Option<f64>,
+    /// Change of `cur_depth` per second since the previous snapshot of the
+    /// queue; its sign tells whether puts or gets were ahead.
+    pub depth_rate: Option<f64>,
+}
+
+impl QueueDepth {
+    /// Reason this sample cannot be stored, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.queue_manager.trim().is_empty() || self.queue_name.trim().is_empty() {
+            return Err("queue_manager and queue_name must not be empty".to_string());
+        }
+        if self.cur_depth < 0 {
+            return Err(format!("cur_depth must not be negative, got {}", self.cur_depth));
+        }
+        Ok(())
+    }
+}
+
+/// A depth sample read from a snapshot file, or the reason it could not be
+/// read, paired with its line number.
+pub type ParsedDepth = (usize, Result<QueueDepth, String>);
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+/// Which message counts of a queue make up its work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountedOps {
+    /// Messages put, including MQPUT1.
+    #[default]
+    Puts,
+    Gets,
+    /// Puts and gets.
+    All,
+}
+
+/// Maps the queues of a queue manager to an MQ function and system.
+/// `queue_manager` and `queue` are exact names or generic names ending in
+/// `*`, as in MQSC.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueRule {
+    /// Any queue manager when omitted.
+    pub queue_manager: Option<String>,
+    pub queue: String,
+    pub mq_function: String,
+    /// Defaults to the queue manager name.
+    pub system_name: Option<String>,
+    #[serde(default)]
+    pub count: CountedOps,
+}
+
+/// Ordered queue rules; the first matching rule wins.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueMapping {
+    #[serde(default, rename = "queue")]
+    pub rules: Vec<QueueRule>,
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+impl QueueRule {
+    pub fn system_name<'a>(&'a self, queue_manager: &'a str) -> &'a str {
+        self.system_name.as_deref().unwrap_or(queue_manager)
+    }
+}
+
+impl QueueMapping {
+    pub fn resolve(&self, queue_manager: &str, queue: &str) -> Option<&QueueRule> {
+        self.rules.iter().find(|rule| {
+            rule.queue_manager.as_deref().is_none_or(|pattern| matches(pattern, queue_manager))
+                && matches(&rule.queue, queue)
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Resolution of a row in `mq_data`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Minute => "minute",
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+        }
+    }
+
+    pub fn span(&self) -> Duration {
+        match self {
+            Granularity::Minute => Duration::minutes(1),
+            Granularity::Hour => Duration::hours(1),
+            Granularity::Day => Duration::days(1),
+        }
+    }
+
+    /// Start of the bucket of this granularity containing `date_time`.
+    pub fn floor(&self, date_time: &DateTime<Local>) -> DateTime<Local> {
+        let floored = date_time.with_second(0).and_then(|d| d.with_nanosecond(0));
+        let floored = match self {
+            Granularity::Minute => floored,
+            Granularity::Hour => floored.and_then(|d| d.with_minute(0)),
+            Granularity::Day => Local
+                .from_local_datetime(&date_time.date_naive().and_time(NaiveTime::MIN))
+                .earliest(),
+        };
+        floored.unwrap_or(*date_time)
+    }
+
+    /// Start of the `count`-th bucket after the one starting at `bucket`,
+    /// robust to days that are not 24 hours long.
+    pub fn advance(&self, bucket: &DateTime<Local>, count: i32) -> DateTime<Local> {
+        self.floor(&(*bucket + self.span() * count + self.span() / 2))
+    }
+}
+
+/// How long rows of each granularity are kept. `None` keeps them forever.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionPolicy {
+    pub minute_days: Option<u32>,
+    pub hourly_days: Option<u32>,
+    pub daily_days: Option<u32>,
+    /// Number of target buckets (hours or days) downsampled per transaction.
+    pub batch_buckets: u32,
+    pub vacuum: bool,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            minute_days: Some(90),
+            hourly_days: Some(730),
+            daily_days: None,
+            batch_buckets: 24,
+            vacuum: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct RetentionReport {
+    pub started_at: Option<DateTime<Local>>,
+    pub finished_at: Option<DateTime<Local>>,
+    pub minute_rows_downsampled: usize,
+    pub hourly_rows_written: usize,
+    pub hourly_rows_downsampled: usize,
+    pub daily_rows_written: usize,
+    pub daily_rows_deleted: usize,
+    pub transactions: usize,
+    pub vacuumed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+/// Account stored in the `users` table, in addition to the built-in
+/// account from the configuration.
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub created_at: DateTime<Local>,
+}
@@ -1,13 +1,76 @@
+use crate::domain::chargeback::ChargebackPolicy;
+use crate::infrastructure::cache::ResponseCache;
+use crate::infrastructure::config::{AuthConfig, IngestConfig, LiveConfig, MqMetricsConfig, SearchConfig};
+use crate::infrastructure::data_watch::DataWatch;
+use crate::infrastructure::datasets::{Dataset, Datasets};
+use crate::infrastructure::metrics::Metrics;
+use crate::infrastructure::retention_job::RetentionJob;
 use rusqlite::Connection;
 use std::sync::{Arc, Mutex};
-use redis::Client;
 
+/// Shared state of the server. `db`, `retention`, `data_watch` and
+/// `dataset` belong to the dataset of the request; outside `/api/v1`
+/// they are those of the default dataset.
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<Mutex<Connection>>,
-    pub user_name: String,
-    pub password: String,
-    pub secret_value: String,
-    pub salt_key: String,
-    pub redis_client: Option<redis::Client>,
+    /// Database of the login accounts (`database.path`).
+    pub accounts: Arc<Mutex<Connection>>,
+    pub auth: AuthConfig,
+    pub cache: Arc<ResponseCache>,
+    pub retention: Arc<RetentionJob>,
+    pub metrics: Arc<Metrics>,
+    /// Bearer token protecting `/metrics`, if any.
+    pub metrics_token: Option<String>,
+    pub mq_metrics: MqMetricsConfig,
+    pub search: SearchConfig,
+    pub live: LiveConfig,
+    pub data_watch: Arc<DataWatch>,
+    pub ingest: IngestConfig,
+    pub chargeback: ChargebackPolicy,
+    pub datasets: Arc<Datasets>,
+    pub dataset: String,
+}
+
+impl AppState {
+    /// This state reading and writing `dataset`.
+    pub fn for_dataset(&self, dataset: &Dataset) -> Self {
+        Self {
+            db: dataset.db.clone(),
+            retention: dataset.retention.clone(),
+            data_watch: dataset.data_watch.clone(),
+            dataset: dataset.name.clone(),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// The state `serve` builds from `config`, without its background jobs.
+    pub fn for_tests(config: &crate::infrastructure::config::AppConfig) -> Self {
+        let accounts = crate::infrastructure::schema::open_database(&config.database.path).unwrap();
+        let accounts = Arc::new(Mutex::new(accounts));
+        let cache = ResponseCache::new(None, config.cache.ttl.clone(), config.redis.settings(), config.cache.memory.clone());
+        let metrics = Arc::new(Metrics::new());
+        let datasets = Arc::new(Datasets::open(config, &accounts, &metrics).unwrap());
+        let default_dataset = datasets.default_dataset().clone();
+        Self {
+            db: default_dataset.db.clone(),
+            accounts,
+            auth: config.auth.clone(),
+            cache: Arc::new(cache),
+            retention: default_dataset.retention.clone(),
+            metrics,
+            metrics_token: config.metrics.token.clone(),
+            mq_metrics: config.mq_metrics.clone(),
+            search: config.search.clone(),
+            live: config.live.clone(),
+            data_watch: default_dataset.data_watch.clone(),
+            ingest: config.ingest.clone(),
+            chargeback: config.chargeback.clone(),
+            datasets,
+            dataset: default_dataset.name.clone(),
+        }
+    }
 }
@@ -0,0 +1,186 @@
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Limits of the in-process cache tier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemorySettings {
+    pub enabled: bool,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    /// Upper bound on how long an entry lives in memory when Redis is also
+    /// configured, so instances do not serve each other's stale data for
+    /// longer than this.
+    pub l1_ttl_secs: u64,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1000,
+            max_bytes: 64 * 1024 * 1024,
+            l1_ttl_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MemoryStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub evictions: u64,
+}
+
+struct Entry {
+    value: String,
+    expires_at: Instant,
+}
+
+struct Inner {
+    entries: LruCache<String, Entry>,
+    bytes: usize,
+    evictions: u64,
+}
+
+/// Size-bounded LRU of serialized responses with per-entry expiry. Bounded
+/// both by entry count and by the total size of keys and values.
+pub struct MemoryStore {
+    max_entries: usize,
+    max_bytes: usize,
+    inner: Mutex<Inner>,
+}
+
+impl MemoryStore {
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            max_bytes,
+            inner: Mutex::new(Inner {
+                entries: LruCache::unbounded(),
+                bytes: 0,
+                evictions: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired = match inner.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => return Some(entry.value.clone()),
+            Some(_) => true,
+            None => false,
+        };
+        if expired && let Some(entry) = inner.entries.pop(key) {
+            inner.bytes -= key.len() + entry.value.len();
+        }
+        None
+    }
+
+    pub fn set(&self, key: &str, value: String, ttl: Duration) {
+        let size = key.len() + value.len();
+        if size > self.max_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let entry = Entry {
+            value,
+            expires_at: Instant::now() + ttl,
+        };
+        if let Some(old) = inner.entries.put(key.to_string(), entry) {
+            inner.bytes -= key.len() + old.value.len();
+        }
+        inner.bytes += size;
+        while inner.entries.len() > self.max_entries || inner.bytes > self.max_bytes {
+            match inner.entries.pop_lru() {
+                Some((old_key, old)) => {
+                    inner.bytes -= old_key.len() + old.value.len();
+                    inner.evictions += 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.bytes = 0;
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let inner = self.inner.lock().unwrap();
+        MemoryStats {
+            entries: inner.entries.len(),
+            bytes: inner.bytes,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            evictions: inner.evictions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn evicts_the_least_recently_used_entry_over_max_entries() {
+        let store = MemoryStore::new(2, 1024);
+        store.set("a", "1".to_string(), TTL);
+        store.set("b", "2".to_string(), TTL);
+        assert_eq!(store.get("a").as_deref(), Some("1"));
+        store.set("c", "3".to_string(), TTL);
+        assert_eq!(store.get("b"), None);
+        assert_eq!(store.get("a").as_deref(), Some("1"));
+        assert_eq!(store.get("c").as_deref(), Some("3"));
+        let stats = store.stats();
+        assert_eq!((stats.entries, stats.evictions), (2, 1));
+    }
+
+    #[test]
+    fn evicts_until_under_max_bytes() {
+        let store = MemoryStore::new(10, 12);
+        store.set("a", "xxxxx".to_string(), TTL);
+        store.set("b", "yyyyy".to_string(), TTL);
+        assert_eq!(store.stats().bytes, 12);
+        store.set("c", "zz".to_string(), TTL);
+        assert_eq!(store.get("a"), None);
+        let stats = store.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, 9, 1));
+    }
+
+    #[test]
+    fn replacing_an_entry_keeps_the_byte_count() {
+        let store = MemoryStore::new(10, 100);
+        store.set("a", "xxxxx".to_string(), TTL);
+        store.set("a", "yy".to_string(), TTL);
+        assert_eq!(store.get("a").as_deref(), Some("yy"));
+        let stats = store.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (1, 3, 0));
+    }
+
+    #[test]
+    fn skips_values_larger_than_the_store() {
+        let store = MemoryStore::new(10, 4);
+        store.set("a", "1".to_string(), TTL);
+        store.set("b", "xxxxxx".to_string(), TTL);
+        assert_eq!(store.get("b"), None);
+        assert_eq!(store.get("a").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_on_read() {
+        let store = MemoryStore::new(10, 100);
+        store.set("a", "1".to_string(), Duration::ZERO);
+        assert_eq!(store.get("a"), None);
+        assert_eq!(store.stats().bytes, 0);
+    }
+}
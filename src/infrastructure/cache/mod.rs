@@ -0,0 +1,356 @@
+pub mod memory_store;
+pub mod redis_store;
+
+use crate::infrastructure::cache::memory_store::{MemorySettings, MemoryStats, MemoryStore};
+use crate::infrastructure::cache::redis_store::{RedisHealth, RedisSettings, RedisStore};
+use crate::infrastructure::circuit_breaker::BreakerStatus;
+use log::{debug, error};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+const KEY_PREFIX: &str = "mqusageviewer:cache";
+const GENERATION_KEY: &str = "mqusageviewer:cache:generation";
+
+/// Read endpoints whose responses are cached, each with its own TTL.
+#[derive(Debug, Clone, Copy)]
+pub enum CacheRoute {
+    Functions,
+    Systems,
+    /// Queue managers, queues or channels of a function.
+    DimensionValues,
+    Search,
+    TpsSummary,
+    AllTpsSummary,
+    /// Bucketed usage of the `GET` query-string routes.
+    Buckets,
+}
+
+impl CacheRoute {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheRoute::Functions => "mq_functions",
+            CacheRoute::Systems => "mq_function_systems",
+            CacheRoute::DimensionValues => "mq_function_dimension_values",
+            CacheRoute::Search => "mq_search",
+            CacheRoute::TpsSummary => "mq_tps_summary",
+            CacheRoute::AllTpsSummary => "all_mq_tps_summary",
+            CacheRoute::Buckets => "mq_usage_buckets",
+        }
+    }
+}
+
+impl Serialize for CacheRoute {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheTtls {
+    pub functions_secs: u64,
+    pub systems_secs: u64,
+    pub search_secs: u64,
+    pub summary_secs: u64,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            functions_secs: 300,
+            systems_secs: 300,
+            search_secs: 60,
+            summary_secs: 120,
+        }
+    }
+}
+
+impl CacheTtls {
+    pub fn for_route(&self, route: CacheRoute) -> u64 {
+        match route {
+            CacheRoute::Functions => self.functions_secs,
+            CacheRoute::Systems | CacheRoute::DimensionValues => self.systems_secs,
+            CacheRoute::Search => self.search_secs,
+            CacheRoute::TpsSummary | CacheRoute::AllTpsSummary | CacheRoute::Buckets => self.summary_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CacheStats {
+    pub backend: &'static str,
+    pub generation: u64,
+    pub hits: u64,
+    pub memory_hits: u64,
+    pub redis_hits: u64,
+    pub misses: u64,
+    pub errors: u64,
+    pub ttls: CacheTtls,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breaker: Option<BreakerStatus>,
+}
+
+#[derive(Serialize)]
+struct KeyMaterial<'a, P: Serialize> {
+    route: CacheRoute,
+    scope: &'a str,
+    params: &'a P,
+}
+
+/// Two-tier response cache for read endpoints: an in-process LRU (L1) in
+/// front of Redis (L2). Either tier may be absent; with neither, every
+/// lookup is a miss.
+///
+/// Keys embed a generation counter. With Redis it is shared through Redis so
+/// a bump on one instance invalidates every instance; without Redis it is
+/// local. Bumping it after new data is written makes every previously cached
+/// response unreachable, and TTLs let both tiers evict them.
+pub struct ResponseCache {
+    memory: Option<MemoryStore>,
+    redis: Option<RedisStore>,
+    ttls: CacheTtls,
+    l1_ttl: Duration,
+    /// Last generation seen in Redis, or the local generation without Redis.
+    generation: AtomicU64,
+    /// When `generation` was last read from Redis.
+    generation_read_at: Mutex<Option<Instant>>,
+    generation_refresh: Duration,
+    memory_hits: AtomicU64,
+    redis_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(
+        redis_client: Option<redis::Client>,
+        ttls: CacheTtls,
+        redis_settings: RedisSettings,
+        memory_settings: MemorySettings,
+    ) -> Self {
+        let memory = memory_settings
+            .enabled
+            .then(|| MemoryStore::new(memory_settings.max_entries, memory_settings.max_bytes));
+        Self {
+            memory,
+            redis: redis_client.map(|client| RedisStore::new(client, redis_settings.clone())),
+            ttls,
+            l1_ttl: Duration::from_secs(memory_settings.l1_ttl_secs),
+            generation: AtomicU64::new(0),
+            generation_read_at: Mutex::new(None),
+            generation_refresh: redis_settings.generation_refresh,
+            memory_hits: AtomicU64::new(0),
+            redis_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn backend(&self) -> &'static str {
+        match (&self.memory, &self.redis) {
+            (Some(_), Some(_)) => "memory+redis",
+            (Some(_), None) => "memory",
+            (None, Some(_)) => "redis",
+            (None, None) => "disabled",
+        }
+    }
+
+    /// Current generation. With Redis, the shared value is read at most
+    /// every `generation_refresh`, by one caller at a time; everyone else
+    /// gets the last known value, as they do while Redis is unreachable.
+    pub async fn generation(&self) -> u64 {
+        let known = self.generation.load(Ordering::SeqCst);
+        let Some(redis) = &self.redis else {
+            return known;
+        };
+        if !self.claim_generation_read() {
+            return known;
+        }
+        match redis.get_counter(GENERATION_KEY).await {
+            Some(generation) => {
+                if generation != known {
+                    self.generation.store(generation, Ordering::SeqCst);
+                    if let Some(memory) = &self.memory {
+                        memory.clear();
+                    }
+                }
+                generation
+            }
+            None => known,
+        }
+    }
+
+    /// Whether the generation is due to be read from Redis; marks it read
+    /// so concurrent callers keep the known value meanwhile.
+    fn claim_generation_read(&self) -> bool {
+        let mut read_at = self.generation_read_at.lock().unwrap();
+        if read_at.is_some_and(|at| at.elapsed() < self.generation_refresh) {
+            return false;
+        }
+        *read_at = Some(Instant::now());
+        true
+    }
+
+    /// TTL of an L1 entry: the route TTL, capped when Redis is the shared tier.
+    fn memory_ttl(&self, route: CacheRoute) -> Duration {
+        let route_ttl = Duration::from_secs(self.ttls.for_route(route).max(1));
+        if self.redis.is_some() {
+            route_ttl.min(self.l1_ttl)
+        } else {
+            route_ttl
+        }
+    }
+
+    /// Builds the cache key for a request: a SHA-256 over the route, the
+    /// user scope and the normalized request parameters.
+    fn key<P: Serialize>(generation: u64, route: CacheRoute, scope: &str, params: &P) -> String {
+        let material = serde_json::to_vec(&KeyMaterial { route, scope, params }).unwrap_or_default();
+        let digest = hex::encode(Sha256::digest(&material));
+        format!("{}:{}:{}:{}", KEY_PREFIX, generation, route.as_str(), digest)
+    }
+
+    pub async fn get<T: DeserializeOwned, P: Serialize>(
+        &self,
+        route: CacheRoute,
+        scope: &str,
+        params: &P,
+    ) -> Option<T> {
+        if self.memory.is_none() && self.redis.is_none() {
+            return None;
+        }
+        let key = Self::key(self.generation().await, route, scope, params);
+
+        if let Some(memory) = &self.memory
+            && let Some(json_str) = memory.get(&key)
+            && let Ok(result) = serde_json::from_str::<T>(&json_str)
+        {
+            self.memory_hits.fetch_add(1, Ordering::Relaxed);
+            debug!("Memory cache hit: {}", key);
+            return Some(result);
+        }
+
+        if let Some(redis) = &self.redis
+            && let Some(Some(json_str)) = redis.get(&key).await
+        {
+            match serde_json::from_str::<T>(&json_str) {
+                Ok(result) => {
+                    self.redis_hits.fetch_add(1, Ordering::Relaxed);
+                    debug!("Redis cache hit: {}", key);
+                    if let Some(memory) = &self.memory {
+                        memory.set(&key, json_str, self.memory_ttl(route));
+                    }
+                    return Some(result);
+                }
+                Err(_) => error!("Failed to deserialize cached data for key: {}", key),
+            }
+        }
+
+        debug!("Cache miss for key: {}", key);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    pub async fn set<T: Serialize, P: Serialize>(
+        &self,
+        route: CacheRoute,
+        scope: &str,
+        params: &P,
+        data: &T,
+    ) {
+        if self.memory.is_none() && self.redis.is_none() {
+            return;
+        }
+        let key = Self::key(self.generation().await, route, scope, params);
+        let json_data = serde_json::to_string(data).unwrap_or_default();
+        if let Some(redis) = &self.redis {
+            redis.set(&key, &json_data, self.ttls.for_route(route)).await;
+        }
+        if let Some(memory) = &self.memory {
+            memory.set(&key, json_data, self.memory_ttl(route));
+        }
+    }
+
+    /// Invalidates every cached response. Call after data is imported or
+    /// otherwise modified. The local tier is always cleared, even when the
+    /// shared generation in Redis cannot be bumped.
+    pub async fn bump_generation(&self) -> u64 {
+        let local = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let generation = match &self.redis {
+            Some(redis) => match redis.incr(GENERATION_KEY).await {
+                Some(generation) => {
+                    self.generation.store(generation, Ordering::SeqCst);
+                    *self.generation_read_at.lock().unwrap() = Some(Instant::now());
+                    generation
+                }
+                None => local,
+            },
+            None => local,
+        };
+        if let Some(memory) = &self.memory {
+            memory.clear();
+        }
+        debug!("Cache generation bumped to {}", generation);
+        generation
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let memory_hits = self.memory_hits.load(Ordering::Relaxed);
+        let redis_hits = self.redis_hits.load(Ordering::Relaxed);
+        CacheStats {
+            backend: self.backend(),
+            generation: self.generation().await,
+            hits: memory_hits + redis_hits,
+            memory_hits,
+            redis_hits,
+            misses: self.misses.load(Ordering::Relaxed),
+            errors: self.redis.as_ref().map(RedisStore::errors).unwrap_or(0),
+            ttls: self.ttls.clone(),
+            memory: self.memory.as_ref().map(MemoryStore::stats),
+            breaker: self.redis.as_ref().map(RedisStore::breaker_status),
+        }
+    }
+
+    pub async fn redis_health(&self) -> Option<RedisHealth> {
+        match &self.redis {
+            Some(redis) => Some(redis.health().await),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_cache() -> ResponseCache {
+        ResponseCache::new(None, CacheTtls::default(), RedisSettings::default(), MemorySettings::default())
+    }
+
+    #[actix_web::test]
+    async fn bumping_the_generation_hides_earlier_entries() {
+        let cache = memory_cache();
+        cache.set(CacheRoute::Functions, "prod/alice", &(), &vec!["PAY"]).await;
+        let cached: Option<Vec<String>> = cache.get(CacheRoute::Functions, "prod/alice", &()).await;
+        assert_eq!(cached, Some(vec!["PAY".to_string()]));
+        let other: Option<Vec<String>> = cache.get(CacheRoute::Functions, "prod/bob", &()).await;
+        assert_eq!(other, None);
+
+        assert_eq!(cache.bump_generation().await, 1);
+        let cached: Option<Vec<String>> = cache.get(CacheRoute::Functions, "prod/alice", &()).await;
+        assert_eq!(cached, None);
+        let stats = cache.stats().await;
+        assert_eq!((stats.generation, stats.memory_hits, stats.misses), (1, 1, 2));
+    }
+
+    #[test]
+    fn generation_is_read_from_redis_at_most_once_per_refresh() {
+        let cache = memory_cache();
+        assert!(cache.claim_generation_read());
+        assert!(!cache.claim_generation_read());
+    }
+}
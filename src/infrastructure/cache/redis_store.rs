@@ -0,0 +1,168 @@
+use crate::infrastructure::circuit_breaker::{BreakerStatus, CircuitBreaker};
+use actix_web::rt::time::timeout;
+use log::{error, info};
+use redis::FromRedisValue;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Connection settings for the Redis backend.
+#[derive(Debug, Clone)]
+pub struct RedisSettings {
+    pub connect_timeout: Duration,
+    pub command_timeout: Duration,
+    pub breaker_failures: u32,
+    pub breaker_cool_down: Duration,
+    /// How long the generation read from Redis is trusted before it is
+    /// read again.
+    pub generation_refresh: Duration,
+}
+
+impl Default for RedisSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_millis(1000),
+            command_timeout: Duration::from_millis(250),
+            breaker_failures: 3,
+            breaker_cool_down: Duration::from_secs(30),
+            generation_refresh: Duration::from_millis(1000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RedisHealth {
+    pub configured: bool,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breaker: Option<BreakerStatus>,
+}
+
+/// Redis access for the response cache.
+///
+/// All commands go through a shared multiplexed connection with a timeout.
+/// After repeated failures the circuit breaker opens and callers skip
+/// Redis entirely until the cool-down has passed.
+pub struct RedisStore {
+    client: redis::Client,
+    connection: Mutex<Option<ConnectionManager>>,
+    settings: RedisSettings,
+    breaker: CircuitBreaker,
+    errors: AtomicU64,
+}
+
+impl RedisStore {
+    pub fn new(client: redis::Client, settings: RedisSettings) -> Self {
+        Self {
+            client,
+            connection: Mutex::new(None),
+            breaker: CircuitBreaker::new(settings.breaker_failures, settings.breaker_cool_down),
+            settings,
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn breaker_status(&self) -> BreakerStatus {
+        self.breaker.status()
+    }
+
+    fn record_failure(&self, what: &str, e: &dyn std::fmt::Display) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.breaker.record_failure();
+        error!("Redis {} failed: {}", what, e);
+    }
+
+    /// Returns the shared connection, establishing it on first use.
+    async fn connection(&self) -> Option<ConnectionManager> {
+        if let Some(con) = self.connection.lock().unwrap().clone() {
+            return Some(con);
+        }
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(self.settings.connect_timeout)
+            .set_response_timeout(self.settings.command_timeout)
+            .set_number_of_retries(1);
+        let connect = ConnectionManager::new_with_config(self.client.clone(), config);
+        match timeout(self.settings.connect_timeout, connect).await {
+            Ok(Ok(con)) => {
+                info!("Connected to Redis cache");
+                *self.connection.lock().unwrap() = Some(con.clone());
+                Some(con)
+            }
+            Ok(Err(e)) => {
+                self.record_failure("connect", &e);
+                None
+            }
+            Err(_) => {
+                self.record_failure("connect", &"timed out");
+                None
+            }
+        }
+    }
+
+    /// Runs one command with the configured timeout, unless the breaker is
+    /// open. `None` means Redis was skipped or the command failed.
+    async fn query<T: FromRedisValue>(&self, what: &str, cmd: redis::Cmd) -> Option<T> {
+        if !self.breaker.allow() {
+            return None;
+        }
+        let mut con = self.connection().await?;
+        match timeout(self.settings.command_timeout, cmd.query_async::<T>(&mut con)).await {
+            Ok(Ok(value)) => {
+                self.breaker.record_success();
+                Some(value)
+            }
+            Ok(Err(e)) => {
+                self.record_failure(what, &e);
+                None
+            }
+            Err(_) => {
+                self.record_failure(what, &"timed out");
+                None
+            }
+        }
+    }
+
+    /// `Some(None)` is a miss, `None` means Redis could not be asked.
+    pub async fn get(&self, key: &str) -> Option<Option<String>> {
+        let mut cmd = redis::cmd("GET");
+        cmd.arg(key);
+        self.query("GET", cmd).await
+    }
+
+    pub async fn set(&self, key: &str, value: &str, ttl_secs: u64) {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(key).arg(value).arg("EX").arg(ttl_secs.max(1));
+        self.query::<()>("SET", cmd).await;
+    }
+
+    pub async fn get_counter(&self, key: &str) -> Option<u64> {
+        let mut cmd = redis::cmd("GET");
+        cmd.arg(key);
+        self.query::<Option<u64>>("GET", cmd)
+            .await
+            .map(|value| value.unwrap_or(0))
+    }
+
+    pub async fn incr(&self, key: &str) -> Option<u64> {
+        let mut cmd = redis::cmd("INCR");
+        cmd.arg(key);
+        self.query("INCR", cmd).await
+    }
+
+    /// Pings Redis (through the breaker) and reports the breaker state.
+    pub async fn health(&self) -> RedisHealth {
+        let reachable = self.query::<String>("PING", redis::cmd("PING")).await.is_some();
+        RedisHealth {
+            configured: true,
+            reachable,
+            breaker: Some(self.breaker.status()),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls go through.
+    Closed,
+    /// Calls are rejected until the cool-down has elapsed.
+    Open,
+    /// Cool-down elapsed; a single probe call is let through, and another
+    /// when the last probe has not reported back within the cool-down.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub failure_threshold: u32,
+    pub cool_down_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+    pub times_opened: u64,
+}
+
+struct Inner {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// Start of the half-open probe that has not reported back yet.
+    probe_started: Option<Instant>,
+    times_opened: u64,
+}
+
+/// Stops calling a failing dependency for `cool_down` after
+/// `failure_threshold` consecutive failures.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+            inner: Mutex::new(Inner {
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_started: None,
+                times_opened: 0,
+            }),
+        }
+    }
+
+    fn state_of(&self, inner: &Inner) -> BreakerState {
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.cool_down => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Returns whether a call may be attempted now. In the half-open state
+    /// only one caller at a time is allowed through as a probe; a probe
+    /// that never reports back is given up after the cool-down.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match self.state_of(&inner) {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen => {
+                if inner.probe_started.is_some_and(|started| started.elapsed() < self.cool_down) {
+                    return false;
+                }
+                inner.probe_started = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_started = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let probe_failed = inner.probe_started.take().is_some();
+        if probe_failed || (inner.opened_at.is_none() && inner.consecutive_failures >= self.failure_threshold) {
+            inner.opened_at = Some(Instant::now());
+            inner.times_opened += 1;
+        }
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let inner = self.inner.lock().unwrap();
+        let state = self.state_of(&inner);
+        BreakerStatus {
+            state,
+            consecutive_failures: inner.consecutive_failures,
+            failure_threshold: self.failure_threshold,
+            cool_down_secs: self.cool_down.as_secs(),
+            retry_in_secs: match (state, inner.opened_at) {
+                (BreakerState::Open, Some(opened_at)) => {
+                    Some(self.cool_down.saturating_sub(opened_at.elapsed()).as_secs())
+                }
+                _ => None,
+            },
+            times_opened: inner.times_opened,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const COOL_DOWN: Duration = Duration::from_millis(30);
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(2, COOL_DOWN);
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, COOL_DOWN);
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.allow());
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+        breaker.record_failure();
+        assert!(!breaker.allow());
+        let status = breaker.status();
+        assert_eq!((status.state, status.times_opened), (BreakerState::Open, 1));
+        assert!(status.retry_in_secs.is_some());
+    }
+
+    #[test]
+    fn half_open_lets_one_probe_through_and_closes_on_success() {
+        let breaker = open_breaker();
+        sleep(COOL_DOWN);
+        assert_eq!(breaker.status().state, BreakerState::HalfOpen);
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        breaker.record_success();
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+        assert!(breaker.allow());
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let breaker = open_breaker();
+        sleep(COOL_DOWN);
+        assert!(breaker.allow());
+        breaker.record_failure();
+        let status = breaker.status();
+        assert_eq!((status.state, status.times_opened), (BreakerState::Open, 2));
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn abandoned_probe_is_replaced_after_the_cool_down() {
+        let breaker = open_breaker();
+        sleep(COOL_DOWN);
+        assert!(breaker.allow());
+        // The probe's caller goes away without recording anything.
+        assert!(!breaker.allow());
+        sleep(COOL_DOWN);
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+    }
+}
@@ -0,0 +1,953 @@
+use crate::domain::chargeback::ChargebackPolicy;
+use crate::domain::import::{DuplicatePolicy, SourceFormat};
+use crate::domain::retention::RetentionPolicy;
+use crate::infrastructure::cache::CacheTtls;
+use crate::infrastructure::cache::memory_store::MemorySettings;
+use crate::infrastructure::cache::redis_store::RedisSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Config file read when `--config` / `MQV_CONFIG` is not given, if present.
+pub const DEFAULT_CONFIG_FILE: &str = "mqusageviewer.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub static_dir: PathBuf,
+    pub workers: Option<usize>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0".to_string(),
+            port: 8888,
+            static_dir: PathBuf::from("./statics"),
+            workers: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Login accounts, and the usage data of the `default` dataset when no
+    /// `[datasets]` are configured.
+    pub path: PathBuf,
+    /// Dataset read by requests that name none; `default` when unset.
+    pub default_dataset: Option<String>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("datasets/mqdata_v2.db"),
+            default_dataset: None,
+        }
+    }
+}
+
+/// Name of the dataset at `database.path` when no `[datasets]` are configured.
+pub const DEFAULT_DATASET: &str = "default";
+
+/// A named set of usage data in its own SQLite file, e.g. one environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetConfig {
+    pub path: PathBuf,
+    /// Display name such as `Production`.
+    pub label: Option<String>,
+    /// Users allowed to read and load the dataset; everyone when absent.
+    pub users: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    pub url: Option<String>,
+    pub connect_timeout_ms: u64,
+    pub command_timeout_ms: u64,
+    pub breaker_failures: u32,
+    pub breaker_cooldown_secs: u64,
+    pub generation_refresh_ms: u64,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        let defaults = RedisSettings::default();
+        Self {
+            url: None,
+            connect_timeout_ms: defaults.connect_timeout.as_millis() as u64,
+            command_timeout_ms: defaults.command_timeout.as_millis() as u64,
+            breaker_failures: defaults.breaker_failures,
+            breaker_cooldown_secs: defaults.breaker_cool_down.as_secs(),
+            generation_refresh_ms: defaults.generation_refresh.as_millis() as u64,
+        }
+    }
+}
+
+impl RedisConfig {
+    pub fn settings(&self) -> RedisSettings {
+        RedisSettings {
+            connect_timeout: Duration::from_millis(self.connect_timeout_ms),
+            command_timeout: Duration::from_millis(self.command_timeout_ms),
+            breaker_failures: self.breaker_failures,
+            breaker_cool_down: Duration::from_secs(self.breaker_cooldown_secs),
+            generation_refresh: Duration::from_millis(self.generation_refresh_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Built-in account; additional accounts are managed with `user add`.
+    pub user_name: Option<String>,
+    pub password: Option<String>,
+    pub secret_value: String,
+    pub salt_key: String,
+    pub token_ttl_hours: i64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            user_name: None,
+            password: None,
+            secret_value: String::new(),
+            salt_key: String::new(),
+            token_ttl_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub ttl: CacheTtls,
+    pub memory: MemorySettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    pub policy: RetentionPolicy,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: 24 * 60,
+            policy: RetentionPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// Bearer token required by `/metrics`; open when unset.
+    pub token: Option<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            token: None,
+        }
+    }
+}
+
+/// Re-export of `mq_data` as Prometheus gauges and OpenMetrics backfill.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqMetricsConfig {
+    /// Needs `metrics.token`, since `/metrics/mq` is outside the login.
+    pub enabled: bool,
+    /// Series whose newest minute row is older than this are not exported.
+    pub lookback_minutes: u64,
+    /// Maximum (mq_function, system_name) series per response.
+    pub max_series: usize,
+    /// Maximum rows in one backfill export; longer ranges must be split.
+    pub max_backfill_rows: usize,
+}
+
+impl Default for MqMetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lookback_minutes: 60,
+            max_series: 1000,
+            max_backfill_rows: 500_000,
+        }
+    }
+}
+
+/// Limits of `/mq/search` and HTTP caching of the `GET` query routes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Rows per page when a paginated request gives no `page_size`.
+    pub page_size: usize,
+    pub max_page_size: usize,
+    /// Most rows an unpaginated, non-streaming search may return; larger
+    /// results are refused.
+    pub max_rows: usize,
+    /// Longest from/to range the search and summary routes accept.
+    pub max_range_days: u32,
+    /// Most rows of an XLSX export, which is built in memory; CSV is
+    /// streamed and has no such limit.
+    pub max_xlsx_rows: usize,
+    /// `Cache-Control: max-age` of `GET` responses whose range may still
+    /// receive data.
+    pub http_max_age_secs: u64,
+    /// `max-age` once the range ended `historical_after_hours` ago.
+    pub http_historical_max_age_secs: u64,
+    pub historical_after_hours: u32,
+    /// Send `public` instead of `private` so shared proxies may cache the
+    /// responses. Only safe when every user may see every function.
+    pub http_public: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            page_size: 1000,
+            max_page_size: 10_000,
+            max_rows: 100_000,
+            max_range_days: 366,
+            max_xlsx_rows: 200_000,
+            http_max_age_secs: 60,
+            http_historical_max_age_secs: 3600,
+            historical_after_hours: 24,
+            http_public: false,
+        }
+    }
+}
+
+/// Live streaming of new usage rows over SSE and WebSocket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LiveConfig {
+    /// How often the database is checked for rows written by other processes.
+    pub poll_interval_secs: u64,
+    /// Idle streams get a heartbeat event (SSE) or ping (WebSocket) this often.
+    pub heartbeat_secs: u64,
+    /// Most rows sent in one event; a larger backlog is sent in several.
+    pub batch_rows: usize,
+    /// Most series one WebSocket connection may subscribe to.
+    pub max_subscriptions: usize,
+    /// WebSocket updates are batched and sent at most this often.
+    pub update_interval_ms: u64,
+    /// A WebSocket client that does not take a message within this time is
+    /// disconnected.
+    pub send_timeout_secs: u64,
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 5,
+            heartbeat_secs: 15,
+            batch_rows: 1000,
+            max_subscriptions: 50,
+            update_interval_ms: 1000,
+            send_timeout_secs: 10,
+        }
+    }
+}
+
+/// Ingestion through `POST /api/v1/ingest` and from MQ statistics files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IngestConfig {
+    /// Largest request body, after gzip decompression.
+    pub max_body_bytes: usize,
+    pub max_records: usize,
+    /// TOML file mapping queues to MQ functions and systems, needed for
+    /// files that count messages per queue (e.g. amqsevt output).
+    pub queue_mapping_path: Option<PathBuf>,
+    pub watch: WatchConfig,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 16 * 1024 * 1024,
+            max_records: 100_000,
+            queue_mapping_path: None,
+            watch: WatchConfig::default(),
+        }
+    }
+}
+
+/// Background loading of files dropped into inbox directories.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    pub enabled: bool,
+    /// How often the inboxes are scanned.
+    pub interval_secs: u64,
+    /// Files modified more recently than this are assumed to be still
+    /// being written and are left for the next scan.
+    pub settle_secs: u64,
+    pub on_duplicate: DuplicatePolicy,
+    pub inboxes: Vec<InboxConfig>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 30,
+            settle_secs: 10,
+            on_duplicate: DuplicatePolicy::Skip,
+            inboxes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InboxConfig {
+    pub path: PathBuf,
+    /// Where loaded files are moved; `processed` inside `path` by default.
+    pub processed_dir: Option<PathBuf>,
+    /// Where files that could not be loaded are moved, with an `.error.txt`
+    /// next to them; `failed` inside `path` by default.
+    pub failed_dir: Option<PathBuf>,
+    /// Tried in order; files matching no route stay in the inbox.
+    pub routes: Vec<InboxRoute>,
+    /// Dataset the files are loaded into; the default dataset when absent.
+    pub dataset: Option<String>,
+}
+
+impl InboxConfig {
+    pub fn processed_dir(&self) -> PathBuf {
+        self.processed_dir.clone().unwrap_or_else(|| self.path.join("processed"))
+    }
+
+    pub fn failed_dir(&self) -> PathBuf {
+        self.failed_dir.clone().unwrap_or_else(|| self.path.join("failed"))
+    }
+}
+
+/// Files whose name matches `pattern` (`*` and `?` wildcards) are read as
+/// `format`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InboxRoute {
+    pub pattern: String,
+    pub format: SourceFormat,
+}
+
+/// Whether TLS clients must present a certificate signed by `client_ca_path`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    #[default]
+    None,
+    Optional,
+    Required,
+}
+
+impl FromStr for ClientAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(ClientAuth::None),
+            "optional" => Ok(ClientAuth::Optional),
+            "required" => Ok(ClientAuth::Required),
+            _ => Err(format!("unknown client auth mode '{}' (expected none, optional or required)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Serve HTTPS on `server.port` instead of plain HTTP.
+    pub enabled: bool,
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// How often the certificate files are checked for changes.
+    pub reload_interval_secs: u64,
+    /// Plain HTTP port that redirects every request to HTTPS.
+    pub redirect_http_port: Option<u16>,
+    pub client_ca_path: Option<PathBuf>,
+    pub client_auth: ClientAuth,
+    /// Client certificate common name -> user name. Requests over a mapped
+    /// certificate are authenticated without a bearer token.
+    pub client_cert_users: BTreeMap<String, String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: PathBuf::from("certs/server.crt"),
+            key_path: PathBuf::from("certs/server.key"),
+            reload_interval_secs: 60,
+            redirect_http_port: None,
+            client_ca_path: None,
+            client_auth: ClientAuth::None,
+            client_cert_users: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub redis: RedisConfig,
+    pub auth: AuthConfig,
+    pub cache: CacheConfig,
+    pub retention: RetentionConfig,
+    pub tls: TlsConfig,
+    pub metrics: MetricsConfig,
+    pub mq_metrics: MqMetricsConfig,
+    pub search: SearchConfig,
+    pub live: LiveConfig,
+    pub ingest: IngestConfig,
+    pub chargeback: ChargebackPolicy,
+    pub datasets: BTreeMap<String, DatasetConfig>,
+}
+
+/// Settings given on the command line; they override every other layer.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub bind_address: Option<String>,
+    pub port: Option<u16>,
+    pub database_path: Option<PathBuf>,
+    pub static_dir: Option<PathBuf>,
+    pub redis_url: Option<String>,
+}
+
+/// Every problem found while loading or validating the configuration, so
+/// they can all be fixed in one go.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for error in &self.errors {
+            writeln!(f, "  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Which checks apply: serving needs credentials and static files, the
+/// `user` subcommand the password salt, the other maintenance subcommands
+/// only the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigPurpose {
+    Serve,
+    Accounts,
+    Maintenance,
+}
+
+fn env_value<T: FromStr>(name: &str, errors: &mut Vec<String>) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            errors.push(format!("environment variable {} has invalid value '{}'", name, value));
+            None
+        }
+    }
+}
+
+/// Retention day counts accept `0` or `forever` to keep rows indefinitely.
+fn env_days(name: &str, target: &mut Option<u32>, errors: &mut Vec<String>) {
+    match std::env::var(name) {
+        Ok(value) if value == "0" || value.eq_ignore_ascii_case("forever") => *target = None,
+        Ok(_) => {
+            if let Some(days) = env_value(name, errors) {
+                *target = Some(days);
+            }
+        }
+        Err(_) => {}
+    }
+}
+
+macro_rules! env_override {
+    ($errors:expr, $( $name:literal => $target:expr ),* $(,)?) => {
+        $( if let Some(value) = env_value($name, $errors) { $target = value; } )*
+    };
+}
+
+impl AppConfig {
+    /// Builds the effective configuration: defaults, then the TOML file,
+    /// then environment variables, then command-line flags.
+    pub fn load(path: Option<&Path>, overrides: &ConfigOverrides) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+        let mut config = match Self::read_file(path) {
+            Ok(config) => config,
+            Err(e) => {
+                errors.push(e);
+                AppConfig::default()
+            }
+        };
+        config.apply_env(&mut errors);
+        config.apply_overrides(overrides);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+
+    fn read_file(path: Option<&Path>) -> Result<Self, String> {
+        let env_path = std::env::var("MQV_CONFIG").ok().map(PathBuf::from);
+        let (path, required) = match path.map(Path::to_path_buf).or(env_path) {
+            Some(path) => (path, true),
+            None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+        if !required && !path.exists() {
+            return Ok(AppConfig::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("cannot parse config file {}: {}", path.display(), e))
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        env_override!(errors,
+            "BIND_ADDRESS" => self.server.bind_address,
+            "PORT" => self.server.port,
+            "STATIC_DIR" => self.server.static_dir,
+            "DATABASE_PATH" => self.database.path,
+            "REDIS_CONNECT_TIMEOUT_MS" => self.redis.connect_timeout_ms,
+            "REDIS_COMMAND_TIMEOUT_MS" => self.redis.command_timeout_ms,
+            "REDIS_BREAKER_FAILURES" => self.redis.breaker_failures,
+            "REDIS_BREAKER_COOLDOWN_SECS" => self.redis.breaker_cooldown_secs,
+            "REDIS_GENERATION_REFRESH_MS" => self.redis.generation_refresh_ms,
+            "SECRET_VALUE" => self.auth.secret_value,
+            "SALT_KEY" => self.auth.salt_key,
+            "TOKEN_TTL_HOURS" => self.auth.token_ttl_hours,
+            "CACHE_TTL_FUNCTIONS_SECS" => self.cache.ttl.functions_secs,
+            "CACHE_TTL_SYSTEMS_SECS" => self.cache.ttl.systems_secs,
+            "CACHE_TTL_SEARCH_SECS" => self.cache.ttl.search_secs,
+            "CACHE_TTL_SUMMARY_SECS" => self.cache.ttl.summary_secs,
+            "CACHE_MEMORY_ENABLED" => self.cache.memory.enabled,
+            "CACHE_MEMORY_MAX_ENTRIES" => self.cache.memory.max_entries,
+            "CACHE_MEMORY_MAX_BYTES" => self.cache.memory.max_bytes,
+            "CACHE_MEMORY_L1_TTL_SECS" => self.cache.memory.l1_ttl_secs,
+            "RETENTION_ENABLED" => self.retention.enabled,
+            "RETENTION_INTERVAL_MINUTES" => self.retention.interval_minutes,
+            "RETENTION_BATCH_BUCKETS" => self.retention.policy.batch_buckets,
+            "RETENTION_VACUUM" => self.retention.policy.vacuum,
+            "TLS_ENABLED" => self.tls.enabled,
+            "TLS_CERT_PATH" => self.tls.cert_path,
+            "TLS_KEY_PATH" => self.tls.key_path,
+            "TLS_RELOAD_INTERVAL_SECS" => self.tls.reload_interval_secs,
+            "TLS_CLIENT_AUTH" => self.tls.client_auth,
+            "METRICS_ENABLED" => self.metrics.enabled,
+            "MQ_METRICS_ENABLED" => self.mq_metrics.enabled,
+            "MQ_METRICS_LOOKBACK_MINUTES" => self.mq_metrics.lookback_minutes,
+            "MQ_METRICS_MAX_SERIES" => self.mq_metrics.max_series,
+            "MQ_METRICS_MAX_BACKFILL_ROWS" => self.mq_metrics.max_backfill_rows,
+            "SEARCH_PAGE_SIZE" => self.search.page_size,
+            "SEARCH_MAX_PAGE_SIZE" => self.search.max_page_size,
+            "SEARCH_MAX_ROWS" => self.search.max_rows,
+            "SEARCH_MAX_RANGE_DAYS" => self.search.max_range_days,
+            "SEARCH_MAX_XLSX_ROWS" => self.search.max_xlsx_rows,
+            "SEARCH_HTTP_MAX_AGE_SECS" => self.search.http_max_age_secs,
+            "SEARCH_HTTP_HISTORICAL_MAX_AGE_SECS" => self.search.http_historical_max_age_secs,
+            "SEARCH_HISTORICAL_AFTER_HOURS" => self.search.historical_after_hours,
+            "SEARCH_HTTP_PUBLIC" => self.search.http_public,
+            "LIVE_POLL_INTERVAL_SECS" => self.live.poll_interval_secs,
+            "LIVE_HEARTBEAT_SECS" => self.live.heartbeat_secs,
+            "LIVE_BATCH_ROWS" => self.live.batch_rows,
+            "LIVE_MAX_SUBSCRIPTIONS" => self.live.max_subscriptions,
+            "LIVE_UPDATE_INTERVAL_MS" => self.live.update_interval_ms,
+            "LIVE_SEND_TIMEOUT_SECS" => self.live.send_timeout_secs,
+            "INGEST_MAX_BODY_BYTES" => self.ingest.max_body_bytes,
+            "INGEST_MAX_RECORDS" => self.ingest.max_records,
+            "INGEST_WATCH_ENABLED" => self.ingest.watch.enabled,
+            "INGEST_WATCH_INTERVAL_SECS" => self.ingest.watch.interval_secs,
+            "INGEST_WATCH_SETTLE_SECS" => self.ingest.watch.settle_secs,
+            "INGEST_WATCH_ON_DUPLICATE" => self.ingest.watch.on_duplicate,
+        );
+        if let Some(token) = env_value("METRICS_TOKEN", errors) {
+            self.metrics.token = Some(token);
+        }
+        if let Some(port) = env_value("TLS_REDIRECT_HTTP_PORT", errors) {
+            self.tls.redirect_http_port = Some(port);
+        }
+        if let Some(path) = env_value("TLS_CLIENT_CA_PATH", errors) {
+            self.tls.client_ca_path = Some(path);
+        }
+        if let Some(path) = env_value("INGEST_QUEUE_MAPPING_PATH", errors) {
+            self.ingest.queue_mapping_path = Some(path);
+        }
+        if let Some(name) = env_value("DATABASE_DEFAULT_DATASET", errors) {
+            self.database.default_dataset = Some(name);
+        }
+        if let Some(url) = env_value("REDIS_URL", errors) {
+            self.redis.url = Some(url);
+        }
+        if let Some(user_name) = env_value("USER_NAME", errors) {
+            self.auth.user_name = Some(user_name);
+        }
+        if let Some(password) = env_value("PASSWORD", errors) {
+            self.auth.password = Some(password);
+        }
+        env_days("RETENTION_MINUTE_DAYS", &mut self.retention.policy.minute_days, errors);
+        env_days("RETENTION_HOURLY_DAYS", &mut self.retention.policy.hourly_days, errors);
+        env_days("RETENTION_DAILY_DAYS", &mut self.retention.policy.daily_days, errors);
+    }
+
+    fn apply_overrides(&mut self, overrides: &ConfigOverrides) {
+        if let Some(bind_address) = &overrides.bind_address {
+            self.server.bind_address = bind_address.clone();
+        }
+        if let Some(port) = overrides.port {
+            self.server.port = port;
+        }
+        if let Some(path) = &overrides.database_path {
+            self.database.path = path.clone();
+        }
+        if let Some(static_dir) = &overrides.static_dir {
+            self.server.static_dir = static_dir.clone();
+        }
+        if let Some(url) = &overrides.redis_url {
+            self.redis.url = Some(url.clone());
+        }
+    }
+
+    /// Every dataset by name: the `[datasets]` tables, or a single
+    /// `default` dataset at `database.path`.
+    pub fn datasets(&self) -> BTreeMap<String, DatasetConfig> {
+        if !self.datasets.is_empty() {
+            return self.datasets.clone();
+        }
+        let dataset = DatasetConfig {
+            path: self.database.path.clone(),
+            label: None,
+            users: None,
+        };
+        BTreeMap::from([(DEFAULT_DATASET.to_string(), dataset)])
+    }
+
+    pub fn default_dataset(&self) -> &str {
+        self.database.default_dataset.as_deref().unwrap_or(DEFAULT_DATASET)
+    }
+
+    pub fn validate(&self, purpose: ConfigPurpose) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        if let Some(parent) = self.database.path.parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.is_dir()
+        {
+            errors.push(format!(
+                "database.path: directory {} does not exist",
+                parent.display()
+            ));
+        }
+        self.validate_datasets(&mut errors);
+        if let Some(url) = &self.redis.url
+            && let Err(e) = redis::Client::open(url.as_str())
+        {
+            errors.push(format!("redis.url: {}", e));
+        }
+        if self.redis.command_timeout_ms == 0 || self.redis.connect_timeout_ms == 0 {
+            errors.push("redis: timeouts must be greater than 0".to_string());
+        }
+        if self.cache.memory.enabled && self.cache.memory.max_entries == 0 {
+            errors.push("cache.memory.max_entries must be greater than 0".to_string());
+        }
+        let ttl = &self.cache.ttl;
+        if [ttl.functions_secs, ttl.systems_secs, ttl.search_secs, ttl.summary_secs].contains(&0) {
+            errors.push("cache.ttl: every TTL must be greater than 0".to_string());
+        }
+        if self.mq_metrics.lookback_minutes == 0 || self.mq_metrics.max_series == 0 || self.mq_metrics.max_backfill_rows == 0 {
+            errors.push("mq_metrics: lookback_minutes, max_series and max_backfill_rows must be greater than 0".to_string());
+        }
+        let search = &self.search;
+        if search.page_size == 0 || search.max_rows == 0 || search.max_range_days == 0 || search.max_xlsx_rows == 0 {
+            errors.push("search: page_size, max_rows, max_range_days and max_xlsx_rows must be greater than 0".to_string());
+        }
+        let live = &self.live;
+        if [live.poll_interval_secs, live.heartbeat_secs, live.send_timeout_secs].contains(&0)
+            || live.batch_rows == 0
+            || live.max_subscriptions == 0
+        {
+            errors.push(
+                "live: poll_interval_secs, heartbeat_secs, send_timeout_secs, batch_rows and max_subscriptions must be greater than 0"
+                    .to_string(),
+            );
+        }
+        if self.ingest.max_body_bytes == 0 || self.ingest.max_records == 0 {
+            errors.push("ingest: max_body_bytes and max_records must be greater than 0".to_string());
+        }
+        if let Some(path) = &self.ingest.queue_mapping_path
+            && !path.is_file()
+        {
+            errors.push(format!("ingest.queue_mapping_path: {} is not a file", path.display()));
+        }
+        if self.search.page_size > self.search.max_page_size {
+            errors.push("search.page_size must not exceed search.max_page_size".to_string());
+        }
+        if let Err(e) = self.chargeback.validate() {
+            errors.push(format!("chargeback: {}", e));
+        }
+        if self.retention.interval_minutes == 0 {
+            errors.push("retention.interval_minutes must be greater than 0".to_string());
+        }
+        let policy = &self.retention.policy;
+        if let (Some(minute), Some(hourly)) = (policy.minute_days, policy.hourly_days)
+            && minute > hourly
+        {
+            errors.push("retention.policy: minute_days must not exceed hourly_days".to_string());
+        }
+        if let (Some(hourly), Some(daily)) = (policy.hourly_days, policy.daily_days)
+            && hourly > daily
+        {
+            errors.push("retention.policy: hourly_days must not exceed daily_days".to_string());
+        }
+
+        if purpose != ConfigPurpose::Maintenance && self.auth.salt_key.is_empty() {
+            errors.push("auth.salt_key (SALT_KEY) must be set".to_string());
+        }
+        if purpose == ConfigPurpose::Serve {
+            if self.ingest.watch.enabled {
+                self.validate_watch(&mut errors);
+            }
+            if self.server.port == 0 {
+                errors.push("server.port must not be 0".to_string());
+            }
+            if !self.server.static_dir.is_dir() {
+                errors.push(format!(
+                    "server.static_dir: {} is not a directory",
+                    self.server.static_dir.display()
+                ));
+            }
+            if self.auth.secret_value.is_empty() {
+                errors.push("auth.secret_value (SECRET_VALUE) must be set".to_string());
+            }
+            if self.auth.user_name.is_some() != self.auth.password.is_some() {
+                errors.push("auth.user_name and auth.password must be set together".to_string());
+            }
+            if self.auth.token_ttl_hours <= 0 {
+                errors.push("auth.token_ttl_hours must be greater than 0".to_string());
+            }
+            if self.tls.enabled {
+                self.validate_tls(&mut errors);
+            }
+            if self.metrics.token.as_deref() == Some("") {
+                errors.push("metrics.token must not be empty when set".to_string());
+            }
+            if self.mq_metrics.enabled && self.metrics.token.is_none() {
+                errors.push("mq_metrics.enabled requires metrics.token (METRICS_TOKEN)".to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+
+    fn validate_datasets(&self, errors: &mut Vec<String>) {
+        let datasets = self.datasets();
+        for (name, dataset) in &self.datasets {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                errors.push(format!("datasets.{}: names may only contain letters, digits, '-' and '_'", name));
+            }
+            if let Some(parent) = dataset.path.parent()
+                && !parent.as_os_str().is_empty()
+                && !parent.is_dir()
+            {
+                errors.push(format!("datasets.{}.path: directory {} does not exist", name, parent.display()));
+            }
+            if dataset.users.as_ref().is_some_and(Vec::is_empty) {
+                errors.push(format!("datasets.{}.users must not be empty; omit it to allow every user", name));
+            }
+        }
+        if !datasets.contains_key(self.default_dataset()) {
+            errors.push(format!("database.default_dataset: no dataset named '{}'", self.default_dataset()));
+        }
+        for inbox in &self.ingest.watch.inboxes {
+            if let Some(name) = &inbox.dataset
+                && !datasets.contains_key(name)
+            {
+                errors.push(format!("ingest.watch.inboxes: {} names unknown dataset '{}'", inbox.path.display(), name));
+            }
+        }
+    }
+
+    fn validate_watch(&self, errors: &mut Vec<String>) {
+        let watch = &self.ingest.watch;
+        if watch.interval_secs == 0 {
+            errors.push("ingest.watch.interval_secs must be greater than 0".to_string());
+        }
+        if watch.inboxes.is_empty() {
+            errors.push("ingest.watch.inboxes must not be empty while the watcher is enabled".to_string());
+        }
+        for inbox in &watch.inboxes {
+            let path = inbox.path.display();
+            if !inbox.path.is_dir() {
+                errors.push(format!("ingest.watch.inboxes: {} is not a directory", path));
+            }
+            if inbox.routes.is_empty() {
+                errors.push(format!("ingest.watch.inboxes: {} has no routes", path));
+            }
+            if inbox.routes.iter().any(|route| route.format.needs_queue_mapping()) && self.ingest.queue_mapping_path.is_none() {
+                errors.push(format!("ingest.watch.inboxes: {} routes files that need ingest.queue_mapping_path", path));
+            }
+        }
+    }
+
+    fn validate_tls(&self, errors: &mut Vec<String>) {
+        let tls = &self.tls;
+        for (name, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+            if !path.is_file() {
+                errors.push(format!("tls.{}: {} is not a file", name, path.display()));
+            }
+        }
+        if tls.reload_interval_secs == 0 {
+            errors.push("tls.reload_interval_secs must be greater than 0".to_string());
+        }
+        if tls.redirect_http_port == Some(self.server.port) {
+            errors.push("tls.redirect_http_port must differ from server.port".to_string());
+        }
+        match (&tls.client_ca_path, tls.client_auth) {
+            (None, ClientAuth::Optional | ClientAuth::Required) => {
+                errors.push("tls.client_ca_path is required when tls.client_auth is enabled".to_string());
+            }
+            (Some(path), _) if !path.is_file() => {
+                errors.push(format!("tls.client_ca_path: {} is not a file", path.display()));
+            }
+            _ => {}
+        }
+        if tls.client_auth == ClientAuth::None && !tls.client_cert_users.is_empty() {
+            errors.push("tls.client_cert_users has no effect while tls.client_auth is none".to_string());
+        }
+    }
+
+    /// The configuration with secrets masked, for display.
+    pub fn redacted(&self) -> Self {
+        let mask = |value: &str| if value.is_empty() { String::new() } else { "********".to_string() };
+        let mut config = self.clone();
+        config.auth.password = config.auth.password.as_deref().map(mask);
+        config.auth.secret_value = mask(&config.auth.secret_value);
+        config.auth.salt_key = mask(&config.auth.salt_key);
+        config.metrics.token = config.metrics.token.as_deref().map(mask);
+        if let Some(url) = &config.redis.url
+            && let Some((scheme, rest)) = url.split_once("://")
+            && let Some((_, host)) = rest.rsplit_once('@')
+        {
+            config.redis.url = Some(format!("{}://********@{}", scheme, host));
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Defaults with the database in the working directory, which exists.
+    fn config() -> AppConfig {
+        let mut config = AppConfig::default();
+        config.database.path = PathBuf::from("mq.db");
+        config
+    }
+
+    fn serve_errors(config: &AppConfig) -> Vec<String> {
+        config.validate(ConfigPurpose::Serve).err().map(|e| e.errors).unwrap_or_default()
+    }
+
+    #[test]
+    fn flags_beat_the_environment_which_beats_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mqusageviewer.toml");
+        let file = "[server]\nbind_address = \"10.0.0.1\"\nport = 1111\nworkers = 3\n\n[auth]\nsalt_key = \"file-salt\"\n";
+        std::fs::write(&path, file).unwrap();
+        let overrides = ConfigOverrides { port: Some(3333), ..Default::default() };
+
+        // The only test reading these variables, so no other test sees them.
+        unsafe {
+            std::env::set_var("BIND_ADDRESS", "10.0.0.2");
+            std::env::set_var("PORT", "2222");
+            std::env::set_var("TOKEN_TTL_HOURS", "soon");
+        }
+        let invalid = AppConfig::load(Some(&path), &overrides).unwrap_err();
+        unsafe { std::env::remove_var("TOKEN_TTL_HOURS") };
+        let config = AppConfig::load(Some(&path), &overrides);
+        unsafe {
+            std::env::remove_var("BIND_ADDRESS");
+            std::env::remove_var("PORT");
+        }
+
+        assert_eq!(invalid.errors, vec!["environment variable TOKEN_TTL_HOURS has invalid value 'soon'"]);
+        let config = config.unwrap();
+        assert_eq!(config.server.port, 3333);
+        assert_eq!(config.server.bind_address, "10.0.0.2");
+        assert_eq!(config.server.workers, Some(3));
+        assert_eq!(config.auth.salt_key, "file-salt");
+        assert!(AppConfig::load(Some(&dir.path().join("missing.toml")), &overrides).is_err());
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let mut config = config();
+        config.server.port = 0;
+        config.server.static_dir = PathBuf::from("/nonexistent/statics");
+        let errors = serve_errors(&config);
+        for expected in [
+            "server.port must not be 0",
+            "server.static_dir: /nonexistent/statics is not a directory",
+            "auth.secret_value (SECRET_VALUE) must be set",
+            "auth.salt_key (SALT_KEY) must be set",
+        ] {
+            assert!(errors.iter().any(|e| e == expected), "{} missing from {:?}", expected, errors);
+        }
+        // Maintenance subcommands only need the database.
+        assert!(config.validate(ConfigPurpose::Maintenance).is_ok());
+    }
+
+    #[test]
+    fn accounts_need_the_salt_key() {
+        let mut config = config();
+        let errors = |config: &AppConfig| config.validate(ConfigPurpose::Accounts).err().map(|e| e.errors).unwrap_or_default();
+        assert_eq!(errors(&config), vec!["auth.salt_key (SALT_KEY) must be set"]);
+        config.auth.salt_key = "pepper".to_string();
+        assert!(errors(&config).is_empty());
+    }
+
+    #[test]
+    fn mq_metrics_need_a_metrics_token() {
+        let requirement = "mq_metrics.enabled requires metrics.token (METRICS_TOKEN)".to_string();
+        let mut config = AppConfig::default();
+        assert!(!serve_errors(&config).contains(&requirement));
+        config.mq_metrics.enabled = true;
+        assert!(serve_errors(&config).contains(&requirement));
+        config.metrics.token = Some("scrape-secret".to_string());
+        assert!(!serve_errors(&config).contains(&requirement));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::application::mq_log_usage_service::get_max_usage_id;
+use crate::infrastructure::cache::ResponseCache;
+use actix_web::rt::time::sleep;
+use log::{debug, error, info};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Newest row id of the usage table, so live streams wake up when rows
+/// land. In-process writers call [`DataWatch::notify`] after committing;
+/// rows written by other processes (e.g. the `import` command) are picked
+/// up by polling, which also bumps the cache generation for them.
+pub struct DataWatch {
+    sender: watch::Sender<i64>,
+}
+
+impl DataWatch {
+    pub fn new(db: &Mutex<Connection>) -> Self {
+        let max_id = Self::read_max_id(db).unwrap_or(0);
+        Self {
+            sender: watch::Sender::new(max_id),
+        }
+    }
+
+    fn read_max_id(db: &Mutex<Connection>) -> Option<i64> {
+        let connection = db.lock().ok()?;
+        match get_max_usage_id(&connection) {
+            Ok(max_id) => Some(max_id.unwrap_or(0)),
+            Err(e) => {
+                error!("Failed to read the newest usage row id: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<i64> {
+        self.sender.subscribe()
+    }
+
+    pub fn latest(&self) -> i64 {
+        *self.sender.borrow()
+    }
+
+    /// Publishes `max_id` if it differs from the last known value. Lower
+    /// values are published too: retention may delete the newest rows.
+    /// Returns whether it differed.
+    pub fn notify(&self, max_id: i64) -> bool {
+        self.sender.send_if_modified(|current| {
+            if *current == max_id {
+                return false;
+            }
+            debug!("Newest usage row id is now {}", max_id);
+            *current = max_id;
+            true
+        })
+    }
+
+    /// Re-reads the newest row id from the database and publishes it.
+    /// Returns whether it changed.
+    pub fn refresh(&self, db: &Mutex<Connection>) -> bool {
+        Self::read_max_id(db).is_some_and(|max_id| self.notify(max_id))
+    }
+
+    /// Polls the database every `interval` for the lifetime of the server.
+    /// In-process writers bump the generation themselves, so a change seen
+    /// here comes from another process and its cached responses are stale.
+    pub fn spawn(self: Arc<Self>, db: Arc<Mutex<Connection>>, cache: Arc<ResponseCache>, interval: Duration) {
+        info!("Polling for new usage rows every {} s", interval.as_secs());
+        actix_web::rt::spawn(async move {
+            loop {
+                sleep(interval).await;
+                if self.refresh(&db) {
+                    cache.bump_generation().await;
+                }
+            }
+        });
+    }
+}
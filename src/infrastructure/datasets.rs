@@ -0,0 +1,108 @@
+use crate::application::error::AppError;
+use crate::infrastructure::cache::ResponseCache;
+use crate::infrastructure::config::AppConfig;
+use crate::infrastructure::data_watch::DataWatch;
+use crate::infrastructure::metrics::Metrics;
+use crate::infrastructure::retention_job::RetentionJob;
+use crate::infrastructure::schema;
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// An open dataset: its database with the live stream and retention job
+/// that work on it.
+pub struct Dataset {
+    pub name: String,
+    pub label: Option<String>,
+    pub db: Arc<Mutex<Connection>>,
+    pub data_watch: Arc<DataWatch>,
+    pub retention: Arc<RetentionJob>,
+    users: Option<Vec<String>>,
+}
+
+impl Dataset {
+    pub fn allows(&self, user: &str) -> bool {
+        self.users.as_ref().is_none_or(|users| users.iter().any(|u| u == user))
+    }
+}
+
+/// Every configured dataset, in name order.
+pub struct Datasets {
+    datasets: Vec<Arc<Dataset>>,
+    default: usize,
+}
+
+impl Datasets {
+    /// Opens and migrates every dataset. One at `database.path` shares the
+    /// `accounts` connection.
+    pub fn open(
+        config: &AppConfig,
+        accounts: &Arc<Mutex<Connection>>,
+        metrics: &Arc<Metrics>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut datasets = Vec::new();
+        for (name, dataset) in config.datasets() {
+            let db = if dataset.path == config.database.path {
+                accounts.clone()
+            } else {
+                let connection = schema::open_database(&dataset.path)
+                    .map_err(|e| format!("Failed to open dataset {} at {}: {}", name, dataset.path.display(), e))?;
+                Arc::new(Mutex::new(connection))
+            };
+            let retention = RetentionJob::new(
+                config.retention.policy.clone(),
+                config.retention.enabled,
+                Duration::from_secs(config.retention.interval_minutes * 60),
+                metrics.clone(),
+            );
+            datasets.push(Arc::new(Dataset {
+                data_watch: Arc::new(DataWatch::new(&db)),
+                retention: Arc::new(retention),
+                name,
+                label: dataset.label,
+                db,
+                users: dataset.users,
+            }));
+        }
+        let default = datasets
+            .iter()
+            .position(|dataset| dataset.name == config.default_dataset())
+            .ok_or_else(|| format!("no dataset named '{}'", config.default_dataset()))?;
+        Ok(Self { datasets, default })
+    }
+
+    /// Starts the retention job and the live stream polling of every dataset.
+    pub fn spawn(&self, cache: &Arc<ResponseCache>, poll_interval: Duration) {
+        for dataset in &self.datasets {
+            dataset.retention.clone().spawn(dataset.db.clone(), cache.clone());
+            dataset.data_watch.clone().spawn(dataset.db.clone(), cache.clone(), poll_interval);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Dataset>> {
+        self.datasets.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<Dataset>> {
+        self.datasets.iter().find(|dataset| dataset.name == name)
+    }
+
+    pub fn default_dataset(&self) -> &Arc<Dataset> {
+        &self.datasets[self.default]
+    }
+
+    /// The dataset a request of `user` names, or the default one: `404`
+    /// when it does not exist, `403` when the user may not read it.
+    pub fn resolve(&self, name: Option<&str>, user: &str) -> Result<&Arc<Dataset>, AppError> {
+        let dataset = match name {
+            Some(name) => self
+                .get(name)
+                .ok_or_else(|| AppError::NotFound(format!("unknown dataset '{}'", name)))?,
+            None => self.default_dataset(),
+        };
+        if !dataset.allows(user) {
+            return Err(AppError::Forbidden(format!("no access to dataset '{}'", dataset.name)));
+        }
+        Ok(dataset)
+    }
+}
@@ -0,0 +1,100 @@
+use crate::application::{import_service, maintenance_service, queue_depth_service};
+use crate::domain::import::{DuplicatePolicy, ImportReport, ParsedRecord, SourceFormat};
+use crate::domain::queue_depth::ParsedDepth;
+use crate::domain::queue_mapping::QueueMapping;
+use crate::infrastructure::parsers::amqsevt::parse_amqsevt_json;
+use crate::infrastructure::parsers::csv_usage::parse_usage_csv;
+use crate::infrastructure::parsers::runmqsc::{parse_runmqsc, snapshot_time};
+use chrono::{DateTime, Local};
+use rusqlite::{Connection, Transaction};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::Path;
+
+/// Outcome of loading one file.
+#[derive(Debug, Default)]
+pub struct FileImport {
+    pub report: ImportReport,
+    /// `QMGR/QUEUE` of queues the mapping does not cover.
+    pub unmapped: BTreeSet<String>,
+}
+
+/// Records of a file, by the table they go to.
+enum Records {
+    Usage(Vec<ParsedRecord>),
+    Depth(Vec<ParsedDepth>),
+}
+
+/// Time a runmqsc snapshot was taken: from its name, else its modification time.
+fn taken_at(path: &Path, file: &File) -> Result<DateTime<Local>, String> {
+    if let Some(time) = snapshot_time(path) {
+        return Ok(time);
+    }
+    let modified = file.metadata().and_then(|m| m.modified()).map_err(|e| e.to_string())?;
+    Ok(DateTime::<Local>::from(modified))
+}
+
+/// Parses `path` as `format` and writes its rows in one transaction.
+/// Files of at least [`maintenance_service::LARGE_IMPORT_ROWS`] records
+/// mark the database busy while they load. `mapping` is required for
+/// formats that [`SourceFormat::needs_queue_mapping`].
+pub fn import_file(
+    connection: &mut Connection,
+    path: &Path,
+    format: SourceFormat,
+    mapping: Option<&QueueMapping>,
+    duplicate_policy: DuplicatePolicy,
+    dry_run: bool,
+) -> Result<FileImport, Box<dyn std::error::Error>> {
+    import_file_with(connection, path, format, mapping, duplicate_policy, dry_run, |_, _| Ok(()))
+}
+
+/// [`import_file`], running `before_commit` in the import's transaction
+/// so that what it writes is committed with the rows or not at all.
+pub fn import_file_with(
+    connection: &mut Connection,
+    path: &Path,
+    format: SourceFormat,
+    mapping: Option<&QueueMapping>,
+    duplicate_policy: DuplicatePolicy,
+    dry_run: bool,
+    before_commit: impl FnOnce(&Transaction, &ImportReport) -> rusqlite::Result<()>,
+) -> Result<FileImport, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let mapping = || mapping.ok_or("no queue mapping configured");
+    let (records, unmapped) = match format {
+        SourceFormat::Csv => (Records::Usage(parse_usage_csv(file)?), BTreeSet::new()),
+        SourceFormat::Amqsevt => {
+            let parsed = parse_amqsevt_json(file, mapping()?)?;
+            (Records::Usage(parsed.records), parsed.unmapped)
+        }
+        SourceFormat::Runmqsc => {
+            let parsed = parse_runmqsc(&file, taken_at(path, &file)?, mapping()?)?;
+            (Records::Depth(parsed.records), parsed.unmapped)
+        }
+    };
+    let count = match &records {
+        Records::Usage(records) => records.len(),
+        Records::Depth(samples) => samples.len(),
+    };
+    let write = |connection: &mut Connection| -> rusqlite::Result<ImportReport> {
+        let tx = connection.transaction()?;
+        let report = match records {
+            Records::Usage(records) => import_service::write_usage(&tx, records, duplicate_policy)?,
+            Records::Depth(samples) => queue_depth_service::write_depth(&tx, samples, duplicate_policy)?,
+        };
+        before_commit(&tx, &report)?;
+        if dry_run {
+            tx.rollback()?;
+        } else {
+            tx.commit()?;
+        }
+        Ok(report)
+    };
+    let report = if !dry_run && count >= maintenance_service::LARGE_IMPORT_ROWS {
+        maintenance_service::run(connection, &format!("import of {}", path.display()), write)?
+    } else {
+        write(connection)?
+    };
+    Ok(FileImport { report, unmapped })
+}
@@ -0,0 +1,411 @@
+use crate::application::ingested_file_service::{find_ingested_file, record_ingested_file};
+use crate::domain::import::{IngestedFile, SourceFormat};
+use crate::domain::queue_mapping::QueueMapping;
+use crate::infrastructure::cache::ResponseCache;
+use crate::infrastructure::config::{InboxConfig, WatchConfig};
+use crate::infrastructure::data_watch::DataWatch;
+use crate::infrastructure::file_import::{FileImport, import_file_with};
+use crate::infrastructure::metrics::{Metrics, RowCount};
+use actix_web::rt::time::sleep;
+use actix_web::web;
+use chrono::Local;
+use log::{debug, error, info, warn};
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Maximum number of rejected records logged per file.
+const MAX_REJECTIONS_LOGGED: usize = 5;
+
+/// Whether `name` matches `pattern`, where `*` matches any run of
+/// characters and `?` any single one.
+fn matches_pattern(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, _) => name.is_empty(),
+        (Some('*'), _) => matches_pattern(&pattern[1..], name) || (!name.is_empty() && matches_pattern(pattern, &name[1..])),
+        (Some('?'), Some(_)) => matches_pattern(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && matches_pattern(&pattern[1..], &name[1..]),
+        (Some(_), None) => false,
+    }
+}
+
+/// Whether a failed load may succeed on a later scan: the database or the
+/// file system failed, rather than the file being unreadable.
+fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.is::<rusqlite::Error>() || error.is::<std::io::Error>()
+}
+
+/// Outcome of loading a file.
+enum Loaded {
+    /// The same content was loaded before, as this file.
+    Before(IngestedFile),
+    Imported(FileImport),
+}
+
+impl RowCount for Loaded {
+    fn row_count(&self) -> usize {
+        match self {
+            Loaded::Before(_) => 0,
+            Loaded::Imported(imported) => imported.report.changed_rows(),
+        }
+    }
+}
+
+fn checksum(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Moves `path` into `dir`, adding a timestamp to the name if a file of
+/// that name is already there. Returns the new path.
+fn move_into(path: &Path, dir: &Path) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let mut target = dir.join(&name);
+    if target.exists() {
+        target = dir.join(format!("{}.{}", name, Local::now().format("%Y%m%d%H%M%S%3f")));
+    }
+    if fs::rename(path, &target).is_err() {
+        // Across file systems: copy, then remove the original.
+        fs::copy(path, &target)?;
+        fs::remove_file(path)?;
+    }
+    Ok(target)
+}
+
+/// Loads files dropped into the configured inboxes, routing them to a
+/// parser by name. Loaded files go to the inbox's processed folder and
+/// unreadable ones to its failed folder; files that failed on a database
+/// or file system error stay for the next scan. A file whose content was
+/// loaded before is moved to processed without loading it again.
+pub struct IngestWatcher {
+    config: WatchConfig,
+    mapping: Option<QueueMapping>,
+    metrics: Arc<Metrics>,
+}
+
+impl IngestWatcher {
+    pub fn new(config: WatchConfig, mapping: Option<QueueMapping>, metrics: Arc<Metrics>) -> Self {
+        Self {
+            config,
+            mapping,
+            metrics,
+        }
+    }
+
+    /// Scans the inboxes every `interval_secs` for the lifetime of the server.
+    pub fn spawn(
+        self: Arc<Self>,
+        db: Arc<Mutex<Connection>>,
+        cache: Arc<ResponseCache>,
+        data_watch: Arc<DataWatch>,
+    ) {
+        let inboxes: Vec<String> = self.config.inboxes.iter().map(|i| i.path.display().to_string()).collect();
+        info!("Watching {} every {} s", inboxes.join(", "), self.config.interval_secs);
+        actix_web::rt::spawn(async move {
+            loop {
+                let started = Instant::now();
+                let (watcher, scan_db) = (self.clone(), db.clone());
+                match web::block(move || watcher.scan(&scan_db)).await {
+                    Ok((changed, failed)) => {
+                        if changed > 0 {
+                            cache.bump_generation().await;
+                            data_watch.refresh(&db);
+                        }
+                        self.metrics.record_job("ingest_watch", failed == 0, started.elapsed());
+                    }
+                    Err(e) => {
+                        error!("Inbox scan failed: {}", e);
+                        self.metrics.record_job("ingest_watch", false, started.elapsed());
+                    }
+                }
+                sleep(Duration::from_secs(self.config.interval_secs)).await;
+            }
+        });
+    }
+
+    /// Loads every settled file of every inbox. Returns the number of rows
+    /// changed and of files that failed.
+    fn scan(&self, db: &Mutex<Connection>) -> (usize, usize) {
+        let (mut changed, mut failed) = (0, 0);
+        for inbox in &self.config.inboxes {
+            for (path, format) in self.ready_files(inbox) {
+                match self.load(db, &path, format) {
+                    Ok(rows) => {
+                        changed += rows;
+                        if let Err(e) = move_into(&path, &inbox.processed_dir()) {
+                            error!("Cannot move {} to {}: {}", path.display(), inbox.processed_dir().display(), e);
+                        }
+                    }
+                    Err(e) if is_transient(e.as_ref()) => {
+                        failed += 1;
+                        warn!("Cannot load {} now, leaving it for the next scan: {}", path.display(), e);
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        error!("Failed to load {}: {}", path.display(), e);
+                        match move_into(&path, &inbox.failed_dir()) {
+                            Ok(target) => {
+                                let report = target.with_file_name(format!(
+                                    "{}.error.txt",
+                                    target.file_name().unwrap_or_default().to_string_lossy()
+                                ));
+                                if let Err(e) = fs::write(&report, format!("{}\n", e)) {
+                                    error!("Cannot write {}: {}", report.display(), e);
+                                }
+                            }
+                            Err(e) => error!("Cannot move {} to {}: {}", path.display(), inbox.failed_dir().display(), e),
+                        }
+                    }
+                }
+            }
+        }
+        (changed, failed)
+    }
+
+    /// Files of `inbox` that match a route and have not been modified for
+    /// `settle_secs`, oldest first so snapshots load in order.
+    fn ready_files(&self, inbox: &InboxConfig) -> Vec<(PathBuf, SourceFormat)> {
+        let entries = match fs::read_dir(&inbox.path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Cannot read inbox {}: {}", inbox.path.display(), e);
+                return Vec::new();
+            }
+        };
+        let settle = Duration::from_secs(self.config.settle_secs);
+        let now = SystemTime::now();
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else { continue };
+            if !metadata.is_file() {
+                continue;
+            }
+            let name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+            let route = inbox
+                .routes
+                .iter()
+                .find(|route| matches_pattern(&route.pattern.chars().collect::<Vec<_>>(), &name));
+            let Some(route) = route else { continue };
+            let modified = metadata.modified().unwrap_or(now);
+            if now.duration_since(modified).unwrap_or_default() < settle {
+                debug!("{} is still being written", entry.path().display());
+                continue;
+            }
+            files.push((modified, entry.path(), route.format));
+        }
+        files.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        files.into_iter().map(|(_, path, format)| (path, format)).collect()
+    }
+
+    /// Loads one file unless its content was loaded before, recording its
+    /// checksum in the import's transaction. Returns the number of rows
+    /// changed.
+    fn load(&self, db: &Mutex<Connection>, path: &Path, format: SourceFormat) -> Result<usize, Box<dyn std::error::Error>> {
+        let checksum = checksum(path)?;
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let loaded = self.metrics.with_db_mut(db, "ingest_file", |connection| {
+            if let Some(previous) = find_ingested_file(connection, &checksum)? {
+                return Ok(Loaded::Before(previous));
+            }
+            let mapping = self.mapping.as_ref();
+            let imported = import_file_with(connection, path, format, mapping, self.config.on_duplicate, false, |tx, report| {
+                record_ingested_file(
+                    tx,
+                    &IngestedFile {
+                        checksum: checksum.clone(),
+                        file_name: file_name.clone(),
+                        format,
+                        loaded_at: Local::now(),
+                        inserted: report.inserted,
+                        replaced: report.replaced,
+                        skipped: report.skipped,
+                        rejected: report.rejected.len(),
+                    },
+                )
+            })?;
+            Ok::<_, Box<dyn std::error::Error>>(Loaded::Imported(imported))
+        })?;
+        let imported = match loaded {
+            Loaded::Before(previous) => {
+                info!(
+                    "{} has the same content as {}, loaded at {}; not loading it again",
+                    file_name, previous.file_name, previous.loaded_at
+                );
+                return Ok(0);
+            }
+            Loaded::Imported(imported) => imported,
+        };
+
+        let report = &imported.report;
+        info!(
+            "Loaded {} as {}: {} inserted, {} replaced, {} skipped, {} rejected",
+            file_name,
+            format.as_str(),
+            report.inserted,
+            report.replaced,
+            report.skipped,
+            report.rejected.len()
+        );
+        if !imported.unmapped.is_empty() {
+            let queues = imported.unmapped.iter().cloned().collect::<Vec<_>>();
+            warn!("{}: no mapping for {} queue(s): {}", file_name, queues.len(), queues.join(", "));
+        }
+        for rejected in report.rejected.iter().take(MAX_REJECTIONS_LOGGED) {
+            warn!("{} {} {}: {}", file_name, format.record_unit(), rejected.index, rejected.reason);
+        }
+        Ok(report.changed_rows())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::config::InboxRoute;
+
+    const USAGE: &str = "date_time,system_name,mq_function,work_total\n2026-10-19 06:40:00,SYS-A,PAY,120\n";
+
+    fn watcher(dir: &Path, settle_secs: u64) -> IngestWatcher {
+        let inbox = InboxConfig {
+            path: dir.to_path_buf(),
+            processed_dir: None,
+            failed_dir: None,
+            routes: vec![InboxRoute { pattern: "usage_*.csv".to_string(), format: SourceFormat::Csv }],
+            dataset: None,
+        };
+        let config = WatchConfig { settle_secs, inboxes: vec![inbox], ..Default::default() };
+        IngestWatcher::new(config, None, Arc::new(Metrics::new()))
+    }
+
+    fn database() -> Mutex<Connection> {
+        let mut connection = Connection::open_in_memory().unwrap();
+        crate::infrastructure::schema::migrate(&mut connection).unwrap();
+        Mutex::new(connection)
+    }
+
+    fn usage_rows(db: &Mutex<Connection>) -> i64 {
+        db.lock().unwrap().query_row("SELECT COUNT(*) FROM mq_data", [], |row| row.get(0)).unwrap()
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = match fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect(),
+            Err(_) => Vec::new(),
+        };
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn patterns_match_whole_names() {
+        let matches = |pattern: &str, name: &str| {
+            matches_pattern(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+        };
+        assert!(matches("usage_*.csv", "usage_20261019.csv"));
+        assert!(matches("usage_*.csv", "usage_.csv"));
+        assert!(matches("QM?-*.out", "QM1-20261019.out"));
+        assert!(matches("*", ""));
+        assert!(!matches("usage_*.csv", "usage_20261019.csv.part"));
+        assert!(!matches("QM?-*.out", "QM12-20261019.out"));
+        assert!(!matches("usage_*.csv", "other.csv"));
+    }
+
+    #[test]
+    fn files_still_being_written_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("usage_1.csv"), USAGE).unwrap();
+        fs::write(dir.path().join("notes.txt"), "not routed").unwrap();
+        fs::create_dir(dir.path().join("usage_dir.csv")).unwrap();
+
+        let inbox = &watcher(dir.path(), 3600).config.inboxes[0];
+        assert!(watcher(dir.path(), 3600).ready_files(inbox).is_empty());
+        let ready = watcher(dir.path(), 0).ready_files(inbox);
+        assert_eq!(ready, vec![(dir.path().join("usage_1.csv"), SourceFormat::Csv)]);
+    }
+
+    #[test]
+    fn the_same_content_is_loaded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watcher, db) = (watcher(dir.path(), 0), database());
+
+        fs::write(dir.path().join("usage_1.csv"), USAGE).unwrap();
+        assert_eq!(watcher.scan(&db), (1, 0));
+        assert_eq!(usage_rows(&db), 1);
+        assert_eq!(names(&dir.path().join("processed")), vec!["usage_1.csv"]);
+
+        fs::write(dir.path().join("usage_2.csv"), USAGE).unwrap();
+        assert_eq!(watcher.scan(&db), (0, 0));
+        assert_eq!(usage_rows(&db), 1);
+        assert_eq!(names(&dir.path().join("processed")), vec!["usage_1.csv", "usage_2.csv"]);
+        let recorded = find_ingested_file(&db.lock().unwrap(), &checksum(&dir.path().join("processed/usage_2.csv")).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!((recorded.file_name.as_str(), recorded.inserted), ("usage_1.csv", 1));
+    }
+
+    #[test]
+    fn unreadable_files_are_moved_to_failed_with_the_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watcher, db) = (watcher(dir.path(), 0), database());
+        fs::write(dir.path().join("usage_bad.csv"), "system_name,work_total\nSYS-A,1\n").unwrap();
+
+        assert_eq!(watcher.scan(&db), (0, 1));
+        let failed = dir.path().join("failed");
+        assert_eq!(names(&failed), vec!["usage_bad.csv", "usage_bad.csv.error.txt"]);
+        let reason = fs::read_to_string(failed.join("usage_bad.csv.error.txt")).unwrap();
+        assert!(reason.contains("date_time"), "{}", reason);
+        assert!(names(&dir.path().join("processed")).is_empty());
+    }
+
+    #[test]
+    fn database_errors_leave_the_file_for_the_next_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let watcher = watcher(dir.path(), 0);
+        // No schema: every statement fails.
+        let db = Mutex::new(Connection::open_in_memory().unwrap());
+        fs::write(dir.path().join("usage_1.csv"), USAGE).unwrap();
+
+        assert_eq!(watcher.scan(&db), (0, 1));
+        assert!(dir.path().join("usage_1.csv").exists());
+        assert!(names(&dir.path().join("failed")).is_empty());
+
+        let db = database();
+        assert_eq!(watcher.scan(&db), (1, 0));
+        assert_eq!(names(&dir.path().join("processed")), vec!["usage_1.csv"]);
+    }
+
+    #[test]
+    fn a_poisoned_connection_is_still_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watcher, db) = (watcher(dir.path(), 0), Arc::new(database()));
+        let holder = db.clone();
+        let _ = std::thread::spawn(move || {
+            let _connection = holder.lock().unwrap();
+            panic!("poisons the lock");
+        })
+        .join();
+        assert!(db.is_poisoned());
+        fs::write(dir.path().join("usage_1.csv"), USAGE).unwrap();
+        assert_eq!(watcher.scan(&db), (1, 0));
+    }
+
+    #[test]
+    fn checksum_is_written_with_the_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watcher, db) = (watcher(dir.path(), 0), database());
+        // The rows go in, then recording the checksum fails: neither stays.
+        db.lock()
+            .unwrap()
+            .execute_batch("CREATE TRIGGER refuse BEFORE INSERT ON ingested_files BEGIN SELECT RAISE(ABORT, 'disk full'); END")
+            .unwrap();
+        fs::write(dir.path().join("usage_1.csv"), USAGE).unwrap();
+
+        assert_eq!(watcher.scan(&db), (0, 1));
+        assert_eq!(usage_rows(&db), 0);
+        assert!(dir.path().join("usage_1.csv").exists());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::domain::model::{SeriesCount, UsagePage};
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Prefix of every metric the viewer exports about itself.
+pub const PREFIX: &str = "mqusageviewer";
+
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+const ROW_BUCKETS: &[f64] = &[0.0, 1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0, 1_000_000.0];
+
+/// Escapes a label value for the Prometheus text format.
+pub fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Appends one sample line, e.g. `name{a="x",b="y"} 1.5`.
+pub fn write_sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    write_sample_at(out, name, labels, value, None);
+}
+
+/// Like [`write_sample`], with an optional timestamp (unit depends on the
+/// format: milliseconds for Prometheus text, seconds for OpenMetrics).
+pub fn write_sample_at(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64, timestamp: Option<i64>) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (idx, (label, value)) in labels.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", label, escape_label(value));
+        }
+        out.push('}');
+    }
+    match timestamp {
+        Some(timestamp) => {
+            let _ = writeln!(out, " {} {}", value, timestamp);
+        }
+        None => {
+            let _ = writeln!(out, " {}", value);
+        }
+    }
+}
+
+/// Appends the `# HELP` and `# TYPE` lines of a metric family.
+pub fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Counter family keyed by label values.
+pub struct CounterVec {
+    name: String,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    fn new(name: &str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Self {
+            name: format!("{}_{}", PREFIX, name),
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, label_values: &[&str]) {
+        let key = label_values.iter().map(|v| v.to_string()).collect();
+        *self.values.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    fn encode(&self, out: &mut String) {
+        write_header(out, &self.name, "counter", self.help);
+        for (values, count) in self.values.lock().unwrap().iter() {
+            let labels: Vec<_> = self.labels.iter().copied().zip(values.iter().map(String::as_str)).collect();
+            write_sample(out, &self.name, &labels, *count as f64);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct HistogramData {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Histogram family with fixed buckets, keyed by label values.
+pub struct HistogramVec {
+    name: String,
+    help: &'static str,
+    labels: &'static [&'static str],
+    buckets: &'static [f64],
+    values: Mutex<BTreeMap<Vec<String>, HistogramData>>,
+}
+
+impl HistogramVec {
+    fn new(name: &str, help: &'static str, labels: &'static [&'static str], buckets: &'static [f64]) -> Self {
+        Self {
+            name: format!("{}_{}", PREFIX, name),
+            help,
+            labels,
+            buckets,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn observe(&self, label_values: &[&str], value: f64) {
+        let key = label_values.iter().map(|v| v.to_string()).collect();
+        let mut values = self.values.lock().unwrap();
+        let data = values.entry(key).or_insert_with(|| HistogramData {
+            counts: vec![0; self.buckets.len()],
+            sum: 0.0,
+            count: 0,
+        });
+        for (bound, count) in self.buckets.iter().zip(data.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        data.sum += value;
+        data.count += 1;
+    }
+
+    pub fn observe_duration(&self, label_values: &[&str], duration: Duration) {
+        self.observe(label_values, duration.as_secs_f64());
+    }
+
+    fn encode(&self, out: &mut String) {
+        write_header(out, &self.name, "histogram", self.help);
+        let bucket_name = format!("{}_bucket", self.name);
+        let bounds: Vec<String> = self.buckets.iter().map(f64::to_string).collect();
+        for (values, data) in self.values.lock().unwrap().iter() {
+            let mut labels: Vec<_> = self.labels.iter().copied().zip(values.iter().map(String::as_str)).collect();
+            for (le, count) in bounds.iter().zip(&data.counts) {
+                labels.push(("le", le));
+                write_sample(out, &bucket_name, &labels, *count as f64);
+                labels.pop();
+            }
+            labels.push(("le", "+Inf"));
+            write_sample(out, &bucket_name, &labels, data.count as f64);
+            labels.pop();
+            write_sample(out, &format!("{}_sum", self.name), &labels, data.sum);
+            write_sample(out, &format!("{}_count", self.name), &labels, data.count as f64);
+        }
+    }
+}
+
+/// Number of rows a service function returned, for the rows histogram.
+pub trait RowCount {
+    fn row_count(&self) -> usize;
+}
+
+impl<T> RowCount for Vec<T> {
+    fn row_count(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> RowCount for Option<T> {
+    fn row_count(&self) -> usize {
+        usize::from(self.is_some())
+    }
+}
+
+impl RowCount for UsagePage {
+    fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+impl RowCount for SeriesCount {
+    fn row_count(&self) -> usize {
+        1
+    }
+}
+
+/// Operational metrics of the viewer, exported at `/metrics`.
+pub struct Metrics {
+    pub http_requests: CounterVec,
+    pub http_duration: HistogramVec,
+    pub db_lock_wait: HistogramVec,
+    pub db_query_duration: HistogramVec,
+    pub db_rows: HistogramVec,
+    pub cache_requests: CounterVec,
+    pub logins: CounterVec,
+    pub job_runs: CounterVec,
+    pub job_duration: HistogramVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            http_requests: CounterVec::new(
+                "http_requests_total",
+                "HTTP requests by route pattern, method and status.",
+                &["route", "method", "status"],
+            ),
+            http_duration: HistogramVec::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route pattern and method.",
+                &["route", "method"],
+                LATENCY_BUCKETS,
+            ),
+            db_lock_wait: HistogramVec::new(
+                "db_lock_wait_seconds",
+                "Time spent waiting for the SQLite connection, by service function.",
+                &["function"],
+                LATENCY_BUCKETS,
+            ),
+            db_query_duration: HistogramVec::new(
+                "db_query_duration_seconds",
+                "Time spent in SQLite, by service function.",
+                &["function"],
+                LATENCY_BUCKETS,
+            ),
+            db_rows: HistogramVec::new(
+                "db_rows_returned",
+                "Rows returned by service function.",
+                &["function"],
+                ROW_BUCKETS,
+            ),
+            cache_requests: CounterVec::new(
+                "cache_requests_total",
+                "Response cache lookups by route and result (hit or miss).",
+                &["route", "result"],
+            ),
+            logins: CounterVec::new("logins_total", "Login attempts by result.", &["result"]),
+            job_runs: CounterVec::new(
+                "background_job_runs_total",
+                "Background job runs by job and outcome.",
+                &["job", "outcome"],
+            ),
+            job_duration: HistogramVec::new(
+                "background_job_duration_seconds",
+                "Background job run time.",
+                &["job"],
+                LATENCY_BUCKETS,
+            ),
+        }
+    }
+
+    /// Locks the database and runs one service function on it, recording
+    /// the lock wait, the query time and, on success, the rows returned.
+    pub fn with_db<T: RowCount, E>(
+        &self,
+        db: &Mutex<Connection>,
+        function: &str,
+        query: impl FnOnce(&Connection) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.with_db_mut(db, function, |connection| query(connection))
+    }
+
+    /// [`Metrics::with_db`] for service functions that open a transaction.
+    pub fn with_db_mut<T: RowCount, E>(
+        &self,
+        db: &Mutex<Connection>,
+        function: &str,
+        query: impl FnOnce(&mut Connection) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let waiting = Instant::now();
+        // A panic while the lock was held rolled back any open transaction,
+        // so the connection is still usable.
+        let mut connection = db.lock().unwrap_or_else(PoisonError::into_inner);
+        self.db_lock_wait.observe_duration(&[function], waiting.elapsed());
+
+        let running = Instant::now();
+        let result = query(&mut connection);
+        self.db_query_duration.observe_duration(&[function], running.elapsed());
+        if let Ok(rows) = &result {
+            self.db_rows.observe(&[function], rows.row_count() as f64);
+        }
+        result
+    }
+
+    pub fn record_job(&self, job: &str, success: bool, duration: Duration) {
+        self.job_runs.inc(&[job, if success { "success" } else { "failure" }]);
+        self.job_duration.observe_duration(&[job], duration);
+    }
+
+    /// Prometheus text exposition of every family.
+    pub fn encode(&self, out: &mut String) {
+        self.http_requests.encode(out);
+        self.http_duration.encode(out);
+        self.db_lock_wait.encode(out);
+        self.db_query_duration.encode(out);
+        self.db_rows.encode(out);
+        self.cache_requests.encode(out);
+        self.logins.encode(out);
+        self.job_runs.encode(out);
+        self.job_duration.encode(out);
+    }
+}
@@ -1,14 +1,55 @@
+use crate::application::error::AppError;
 use crate::domain::auth::Claims;
 use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::tls::ClientIdentity;
 use actix_web::{
-    body::{BoxBody, MessageBody}, dev::{forward_ready, ServiceRequest, ServiceResponse, Transform},
-    http::header::AUTHORIZATION,
+    body::BoxBody, dev::{forward_ready, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderValue, ACCEPT, AUTHORIZATION, UPGRADE},
     web,
+    HttpMessage,
     Error,
-    HttpResponse,
+    ResponseError,
 };
 use futures_util::future::{ready, LocalBoxFuture, Ready};
 use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde::Deserialize;
+
+/// Query parameter carrying the bearer token where browsers cannot set
+/// headers.
+pub const ACCESS_TOKEN_PARAM: &str = "access_token";
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+/// The `access_token` query parameter, accepted only on `EventSource`
+/// (`Accept: text/event-stream`) and WebSocket upgrade requests.
+fn query_token(req: &ServiceRequest) -> Option<String> {
+    let header = |name| req.headers().get(name).and_then(|h: &HeaderValue| h.to_str().ok());
+    let event_stream = header(ACCEPT).is_some_and(|accept| accept.contains("text/event-stream"));
+    let websocket = header(UPGRADE).is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    if !event_stream && !websocket {
+        return None;
+    }
+    web::Query::<TokenQuery>::from_query(req.query_string()).ok()?.into_inner().access_token
+}
+
+/// Request line for the access log with the `access_token` value masked.
+pub fn redacted_request_line(req: &ServiceRequest) -> String {
+    let query = req
+        .query_string()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((ACCESS_TOKEN_PARAM, _)) => format!("{}=REDACTED", ACCESS_TOKEN_PARAM),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let separator = if query.is_empty() { "" } else { "?" };
+    format!("{} {}{}{} {:?}", req.method(), req.path(), separator, query, req.version())
+}
 
 #[derive(Clone)]
 pub struct AuthMiddleware {
@@ -63,33 +104,45 @@ where
             .and_then(|h| h.to_str().ok());
         //let app_state = self.app_state.clone(); // ✅ ใช้ app_state ได้ตรงนี้
 
-        let is_valid = if let Some(header_value) = auth_header {
-            if header_value.starts_with("Bearer ") {
-                let token = header_value.trim_start_matches("Bearer ").trim();
-                let decoding_result = decode::<Claims>(
-                    token,
-                    &DecodingKey::from_secret(self.app_state.secret_value.as_bytes()), // สามารถเปลี่ยนมาอ่านจาก app_state ก็ได้
-                    &Validation::default(),
-                );
-                decoding_result.is_ok()
-            } else {
-                false
-            }
-        } else {
-            false
+        let token = match auth_header {
+            Some(header_value) => header_value
+                .starts_with("Bearer ")
+                .then(|| header_value.trim_start_matches("Bearer ").trim().to_string()),
+            None => query_token(&req),
         };
+        let claims = token.and_then(|token| {
+            let decoding_result = decode::<Claims>(
+                &token,
+                &DecodingKey::from_secret(self.app_state.auth.secret_value.as_bytes()), // สามารถเปลี่ยนมาอ่านจาก app_state ก็ได้
+                &Validation::default(),
+            );
+            decoding_result.ok().map(|data| data.claims)
+        });
+        // Without a token, fall back to a mutual-TLS certificate mapped to a user
+        let identity = req.conn_data::<ClientIdentity>().cloned();
+        let claims = claims.or_else(|| {
+            let user = identity.as_ref()?.user.clone()?;
+            let exp = chrono::Utc::now() + chrono::Duration::hours(self.app_state.auth.token_ttl_hours);
+            Some(Claims {
+                sub: user,
+                exp: exp.timestamp() as usize,
+            })
+        });
 
-        if is_valid {
-            let fut = self.service.call(req);
-            Box::pin(async move { fut.await })
+        if let Some(claims) = claims {
+            // Handlers read the authenticated user through `web::ReqData<Claims>`
+            req.extensions_mut().insert(claims);
+            Box::pin(self.service.call(req))
         } else {
-            Box::pin(async move {
-                Ok(req.into_response(
-                    HttpResponse::Unauthorized()
-                        .body("Unauthorized")
-                        .map_into_boxed_body(),
-                ))
-            })
+            let error = match identity {
+                // A verified certificate without a mapped user is known but not allowed in
+                Some(identity) if auth_header.is_none() => {
+                    log::debug!("Client certificate CN={} is not mapped to a user", identity.common_name);
+                    AppError::Forbidden("Client certificate is not mapped to a user".to_string())
+                }
+                _ => AppError::Unauthorized("Missing or invalid bearer token".to_string()),
+            };
+            Box::pin(async move { Ok(req.into_response(error.error_response())) })
         }
     }
 }
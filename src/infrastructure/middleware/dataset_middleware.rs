@@ -0,0 +1,190 @@
+use crate::domain::auth::Claims;
+use crate::infrastructure::app_state::AppState;
+use actix_web::{
+    body::BoxBody, dev::{forward_ready, Extensions, ServiceRequest, ServiceResponse, Transform},
+    web,
+    HttpMessage,
+    Error,
+    ResponseError,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use serde::Deserialize;
+use std::rc::Rc;
+
+/// Path segment naming the dataset in `/api/v1/datasets/{dataset}/...`.
+pub const DATASET_PARAM: &str = "dataset";
+
+#[derive(Deserialize)]
+struct DatasetQuery {
+    dataset: Option<String>,
+}
+
+/// The dataset named by the path prefix, else by the `dataset` query
+/// parameter.
+fn requested_dataset(req: &ServiceRequest) -> Option<String> {
+    if let Some(name) = req.match_info().get(DATASET_PARAM) {
+        return Some(name.to_string());
+    }
+    web::Query::<DatasetQuery>::from_query(req.query_string()).ok()?.into_inner().dataset
+}
+
+/// Gives the handlers behind it an [`AppState`] of the requested dataset,
+/// after checking the user may read it. Must run after [`AuthMiddleware`].
+///
+/// [`AuthMiddleware`]: super::auth_middleware::AuthMiddleware
+#[derive(Clone)]
+pub struct DatasetMiddleware {
+    app_state: web::Data<AppState>,
+}
+
+impl DatasetMiddleware {
+    pub fn new(app_state: web::Data<AppState>) -> Self {
+        Self { app_state }
+    }
+}
+
+impl<S> Transform<S, ServiceRequest> for DatasetMiddleware
+where
+    S: actix_service::Service<ServiceRequest, Response=ServiceResponse<BoxBody>, Error=Error>
+    + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = DatasetMiddlewareMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DatasetMiddlewareMiddleware {
+            service,
+            app_state: self.app_state.clone(),
+        }))
+    }
+}
+
+pub struct DatasetMiddlewareMiddleware<S> {
+    service: S,
+    app_state: web::Data<AppState>,
+}
+
+impl<S> actix_service::Service<ServiceRequest> for DatasetMiddlewareMiddleware<S>
+where
+    S: actix_service::Service<ServiceRequest, Response=ServiceResponse<BoxBody>, Error=Error>
+    + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let name = requested_dataset(&req);
+        let user = req.extensions().get::<Claims>().map(|claims| claims.sub.clone()).unwrap_or_default();
+        match self.app_state.datasets.resolve(name.as_deref(), &user) {
+            Ok(dataset) => {
+                // Found before the application-wide state by `web::Data<AppState>`
+                let mut data = Extensions::new();
+                data.insert(web::Data::new(self.app_state.for_dataset(dataset)));
+                req.add_data_container(Rc::new(data));
+                Box::pin(self.service.call(req))
+            }
+            Err(error) => Box::pin(async move { Ok(req.into_response(error.error_response())) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::config::{AppConfig, DatasetConfig};
+    use actix_web::http::StatusCode;
+    use actix_web::{App, HttpResponse, test};
+
+    /// `prod`, the default, open to everyone and `dev` only to bob.
+    fn state(dir: &tempfile::TempDir) -> AppState {
+        let mut config = AppConfig::default();
+        config.database.path = dir.path().join("accounts.db");
+        config.database.default_dataset = Some("prod".to_string());
+        let dataset = |name: &str, users: Option<Vec<String>>| DatasetConfig {
+            path: dir.path().join(format!("{}.db", name)),
+            label: None,
+            users,
+        };
+        config.datasets.insert("prod".to_string(), dataset("prod", None));
+        config.datasets.insert("dev".to_string(), dataset("dev", Some(vec!["bob".to_string()])));
+        AppState::for_tests(&config)
+    }
+
+    async fn dataset_name(app_state: web::Data<AppState>) -> HttpResponse {
+        HttpResponse::Ok().body(app_state.dataset.clone())
+    }
+
+    /// Status and, when allowed, the dataset `user` gets for `uri`.
+    async fn resolve(state: &AppState, user: &'static str, uri: &str) -> (StatusCode, String) {
+        let data = web::Data::new(state.clone());
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .service(
+                    web::scope("/api/v1/datasets/{dataset}")
+                        .wrap(DatasetMiddleware::new(data.clone()))
+                        .route("/which", web::get().to(dataset_name)),
+                )
+                .service(
+                    web::scope("/api/v1")
+                        .wrap(DatasetMiddleware::new(data.clone()))
+                        .route("/which", web::get().to(dataset_name)),
+                )
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(Claims { sub: user.to_string(), exp: usize::MAX });
+                    actix_service::Service::call(srv, req)
+                }),
+        )
+        .await;
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        let status = resp.status();
+        let body = test::read_body(resp).await;
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[actix_web::test]
+    async fn unknown_datasets_are_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        assert_eq!(resolve(&state, "bob", "/api/v1/datasets/qa/which").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(resolve(&state, "bob", "/api/v1/which?dataset=qa").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn only_listed_users_reach_a_restricted_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        assert_eq!(resolve(&state, "alice", "/api/v1/datasets/dev/which").await.0, StatusCode::FORBIDDEN);
+        assert_eq!(resolve(&state, "alice", "/api/v1/which?dataset=dev").await.0, StatusCode::FORBIDDEN);
+        assert_eq!(resolve(&state, "", "/api/v1/which?dataset=dev").await.0, StatusCode::FORBIDDEN);
+        assert_eq!(resolve(&state, "bob", "/api/v1/datasets/dev/which").await, (StatusCode::OK, "dev".to_string()));
+    }
+
+    #[actix_web::test]
+    async fn datasets_without_users_are_open_to_everyone() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        for user in ["alice", "bob"] {
+            assert_eq!(resolve(&state, user, "/api/v1/datasets/prod/which").await, (StatusCode::OK, "prod".to_string()));
+        }
+    }
+
+    #[actix_web::test]
+    async fn prefix_and_query_parameter_select_the_same_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let by_path = resolve(&state, "bob", "/api/v1/datasets/dev/which").await;
+        let by_query = resolve(&state, "bob", "/api/v1/which?dataset=dev").await;
+        assert_eq!(by_path, by_query);
+        assert_eq!(by_query.1, "dev");
+        // The prefix wins over a query parameter; neither means the default.
+        assert_eq!(resolve(&state, "bob", "/api/v1/datasets/dev/which?dataset=prod").await.1, "dev");
+        assert_eq!(resolve(&state, "alice", "/api/v1/which").await, (StatusCode::OK, "prod".to_string()));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::infrastructure::metrics::Metrics;
+use actix_web::{
+    body::BoxBody, dev::{forward_ready, ServiceRequest, ServiceResponse, Transform},
+    http::Method, Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Counts requests and records their latency per route pattern. Paths
+/// without a pattern (static files, unknown paths) are grouped under
+/// `other`, and non-standard methods under `OTHER`, to bound cardinality.
+#[derive(Clone)]
+pub struct MetricsMiddleware {
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsMiddleware {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Transform<S, ServiceRequest> for MetricsMiddleware
+where
+    S: actix_service::Service<ServiceRequest, Response=ServiceResponse<BoxBody>, Error=Error>
+    + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = MetricsMiddlewareMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsMiddlewareMiddleware {
+            service,
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+/// Label of a request method: the standard methods by name, anything
+/// else as `OTHER`.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::PATCH => "PATCH",
+        Method::TRACE => "TRACE",
+        _ => "OTHER",
+    }
+}
+
+pub struct MetricsMiddlewareMiddleware<S> {
+    service: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> actix_service::Service<ServiceRequest> for MetricsMiddlewareMiddleware<S>
+where
+    S: actix_service::Service<ServiceRequest, Response=ServiceResponse<BoxBody>, Error=Error>
+    + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = method_label(req.method());
+        let metrics = self.metrics.clone();
+        let future = self.service.call(req);
+        Box::pin(async move {
+            let result = future.await;
+            let (route, status) = match &result {
+                Ok(res) => (res.request().match_pattern(), res.status().as_u16()),
+                Err(e) => (None, e.as_response_error().status_code().as_u16()),
+            };
+            let route = route.filter(|r| !r.is_empty()).unwrap_or_else(|| "other".to_string());
+            metrics.http_requests.inc(&[&route, method, &status.to_string()]);
+            metrics.http_duration.observe_duration(&[&route, method], started.elapsed());
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_standard_methods_share_one_label() {
+        assert_eq!(method_label(&Method::GET), "GET");
+        assert_eq!(method_label(&Method::PATCH), "PATCH");
+        assert_eq!(method_label(&Method::from_bytes(b"PROPFIND").unwrap()), "OTHER");
+        assert_eq!(method_label(&Method::from_bytes(b"X-RANDOM-1234").unwrap()), "OTHER");
+    }
+}
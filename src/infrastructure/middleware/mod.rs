@@ -1 +1,3 @@
 pub mod auth_middleware;
+pub mod dataset_middleware;
+pub mod metrics_middleware;
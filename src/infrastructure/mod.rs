@@ -1,2 +1,14 @@
 pub mod app_state;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod config;
+pub mod data_watch;
+pub mod datasets;
+pub mod file_import;
+pub mod ingest_watcher;
+pub mod metrics;
 pub mod middleware;
+pub mod parsers;
+pub mod retention_job;
+pub mod schema;
+pub mod tls;
@@ -0,0 +1,253 @@
+use crate::domain::import::ParsedRecord;
+use crate::domain::model::{MQLogUsage, QueueDimensions};
+use crate::domain::queue_mapping::{CountedOps, QueueMapping};
+use crate::infrastructure::parsers::parse_local_datetime;
+use chrono::{DateTime, Duration, DurationRound, Local};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+
+/// Usage rows read from an amqsevt file, and the queues no rule maps.
+#[derive(Debug, Default)]
+pub struct AmqsevtRecords {
+    pub records: Vec<ParsedRecord>,
+    /// `QMGR/QUEUE` of queues without a rule; their counts are dropped.
+    pub unmapped: BTreeSet<String>,
+}
+
+/// (interval start, mq_function, system_name, queue_manager, queue_name)
+type SeriesKey = (DateTime<Local>, String, String, String, String);
+
+/// Messages of one series in one interval.
+struct Usage {
+    /// Event that first contributed to the row.
+    index: usize,
+    work_total: f64,
+    trans_per_sec: f64,
+}
+
+fn text<'a>(object: &'a Map<String, Value>, name: &str) -> Option<&'a str> {
+    object.get(name).and_then(Value::as_str).map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Sum of the counts under `names`; amqsevt writes most counts as
+/// `[non-persistent, persistent]` pairs.
+fn count(queue: &Map<String, Value>, names: &[&str]) -> f64 {
+    names
+        .iter()
+        .filter_map(|name| queue.get(*name))
+        .map(|value| match value {
+            Value::Array(values) => values.iter().filter_map(Value::as_f64).sum(),
+            other => other.as_f64().unwrap_or_default(),
+        })
+        .sum()
+}
+
+/// `startDate` + `startTime` and the like; MQ writes times as `HH.MM.SS`
+/// in the queue manager's local time.
+fn event_time(data: &Map<String, Value>, prefix: &str) -> Result<DateTime<Local>, String> {
+    let date = text(data, &format!("{}Date", prefix)).ok_or_else(|| format!("{}Date is missing", prefix))?;
+    let time = text(data, &format!("{}Time", prefix)).ok_or_else(|| format!("{}Time is missing", prefix))?;
+    parse_local_datetime(&format!("{} {}", date, time.replace('.', ":")))
+}
+
+/// Adds the queue counts of one statistics or accounting message to
+/// `usage`. Other messages (MQI or channel statistics) are ignored.
+fn add_event(
+    event: &Value,
+    index: usize,
+    mapping: &QueueMapping,
+    usage: &mut BTreeMap<SeriesKey, Usage>,
+    unmapped: &mut BTreeSet<String>,
+) -> Result<(), String> {
+    let data = event.get("eventData").and_then(Value::as_object).ok_or("eventData is missing")?;
+    let Some(queues) = ["queueStatisticsData", "queueAccountingData"]
+        .iter()
+        .find_map(|name| data.get(*name))
+        .and_then(Value::as_array)
+    else {
+        return Ok(());
+    };
+    let queue_manager = text(data, "queueMgrName")
+        .or_else(|| event.pointer("/eventSource/queueMgr").and_then(Value::as_str))
+        .ok_or("queueMgrName is missing")?;
+    let start = event_time(data, "start")?;
+    let end = event_time(data, "end")?;
+    let seconds = (end - start).num_milliseconds() as f64 / 1000.0;
+    if seconds <= 0.0 {
+        return Err(format!("the interval {} to {} is empty", start, end));
+    }
+    let date_time = start.duration_trunc(Duration::minutes(1)).map_err(|e| e.to_string())?;
+
+    let mut counted = Vec::new();
+    for queue in queues {
+        let queue = queue.as_object().ok_or("queue data is not an object")?;
+        let queue_name = text(queue, "queueName").ok_or("queueName is missing")?;
+        let Some(rule) = mapping.resolve(queue_manager, queue_name) else {
+            unmapped.insert(format!("{}/{}", queue_manager, queue_name));
+            continue;
+        };
+        let puts = count(queue, &["puts", "put1s"]);
+        let gets = count(queue, &["gets"]);
+        let messages = match rule.count {
+            CountedOps::Puts => puts,
+            CountedOps::Gets => gets,
+            CountedOps::All => puts + gets,
+        };
+        let key = (
+            date_time,
+            rule.mq_function.clone(),
+            rule.system_name(queue_manager).to_string(),
+            queue_manager.to_string(),
+            queue_name.to_string(),
+        );
+        counted.push((key, messages));
+    }
+    for (key, messages) in counted {
+        let entry = usage.entry(key).or_insert(Usage {
+            index,
+            work_total: 0.0,
+            trans_per_sec: 0.0,
+        });
+        entry.work_total += messages;
+        entry.trans_per_sec += messages / seconds;
+    }
+    Ok(())
+}
+
+/// Reads the queue statistics (`STATQ`) and queue accounting (`ACCTQ`)
+/// messages of an `amqsevt -o json` dump, a sequence of JSON objects.
+///
+/// Every interval becomes one row per mapped queue, dated at the start of
+/// the interval and carrying the function and system of its rule and its
+/// queue manager and queue: `work_total` is the number of messages and
+/// `trans_per_sec` that number over the interval length. Each entry
+/// carries the position of its first event in the file.
+pub fn parse_amqsevt_json<R: Read>(reader: R, mapping: &QueueMapping) -> Result<AmqsevtRecords, String> {
+    let mut result = AmqsevtRecords::default();
+    let mut usage = BTreeMap::new();
+    let events = serde_json::Deserializer::from_reader(reader).into_iter::<Value>();
+    for (idx, event) in events.enumerate() {
+        let index = idx + 1;
+        let event = match event {
+            Ok(event) => event,
+            Err(e) if idx == 0 => return Err(format!("not amqsevt JSON output: {}", e)),
+            Err(e) => {
+                // The stream cannot be resynchronised after a syntax error.
+                result.records.push((index, Err(format!("malformed JSON: {}", e))));
+                break;
+            }
+        };
+        if let Err(reason) = add_event(&event, index, mapping, &mut usage, &mut result.unmapped) {
+            result.records.push((index, Err(reason)));
+        }
+    }
+    result.records.extend(usage.into_iter().map(|(key, usage)| {
+        let (date_time, mq_function, system_name, queue_manager, queue_name) = key;
+        let queue = QueueDimensions {
+            queue_manager: Some(queue_manager),
+            queue_name: Some(queue_name),
+            channel: None,
+        };
+        let record = MQLogUsage::new(date_time, system_name, mq_function, usage.work_total, usage.trans_per_sec)
+            .with_queue(queue);
+        (usage.index, Ok(record))
+    }));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const SAMPLE: &str = include_str!("../../../tests/fixtures/amqsevt_statq.json");
+
+    fn mapping() -> QueueMapping {
+        toml::from_str(include_str!("../../../tests/fixtures/queue-mapping.toml")).unwrap()
+    }
+
+    fn parse(input: &str) -> Result<AmqsevtRecords, String> {
+        parse_amqsevt_json(input.as_bytes(), &mapping())
+    }
+
+    #[test]
+    fn statistics_and_accounting_become_rows_per_mapped_queue() {
+        let parsed = parse(SAMPLE).unwrap();
+        let rows: Vec<(usize, MQLogUsage)> =
+            parsed.records.into_iter().map(|(index, record)| (index, record.unwrap())).collect();
+        let summary: Vec<(usize, &str, &str, Option<&str>, f64)> = rows
+            .iter()
+            .map(|(index, row)| {
+                (*index, row.mq_function.as_str(), row.system_name.as_str(), row.queue.queue_name.as_deref(), row.work_total)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "PAY", "SYS-A", Some("PAY.IN"), 155.0),
+                (1, "PAY", "SYS-A", Some("PAY.OUT"), 10.0),
+                (3, "ORDERS", "QM1", Some("ORDERS.IN"), 30.0),
+            ]
+        );
+        assert_eq!(rows[0].1.date_time, Local.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap());
+        assert!((rows[0].1.trans_per_sec - 155.0 / 900.0).abs() < 1e-9);
+        assert_eq!(rows[2].1.date_time, Local.with_ymd_and_hms(2024, 5, 1, 10, 15, 0).unwrap());
+        assert!((rows[2].1.trans_per_sec - 0.5).abs() < 1e-9);
+        assert_eq!(rows[0].1.queue.queue_manager.as_deref(), Some("QM1"));
+        assert_eq!(parsed.unmapped.into_iter().collect::<Vec<_>>(), vec!["QM1/OTHER.Q".to_string()]);
+    }
+
+    #[test]
+    fn input_that_is_not_json_is_refused() {
+        assert!(parse("AMQ8409I: Display Queue details.").is_err());
+        assert!(parse("{\"eventData\": ").is_err());
+    }
+
+    #[test]
+    fn bad_events_are_rejected_one_by_one() {
+        let events = r#"
+            {"eventData": {"queueMgrName": "QM1", "startDate": "2024-05-01", "startTime": "10.00.00",
+                           "queueStatisticsData": [{"queueName": "PAY.IN", "puts": [1, 0]}]}}
+            {"eventData": {"queueMgrName": "QM1", "startDate": "2024-05-01", "startTime": "10.00.00",
+                           "endDate": "2024-05-01", "endTime": "10.00.00",
+                           "queueStatisticsData": [{"queueName": "PAY.IN", "puts": [1, 0]}]}}
+            {"eventData": {"queueMgrName": "QM1", "startDate": "2024-05-01", "startTime": "25.00.00",
+                           "endDate": "2024-05-01", "endTime": "10.15.00",
+                           "queueStatisticsData": [{"queueName": "PAY.IN"}]}}
+            {"eventData": {"queueMgrName": "QM1", "startDate": "2024-05-01", "startTime": "10.00.00",
+                           "endDate": "2024-05-01", "endTime": "10.15.00",
+                           "queueStatisticsData": ["PAY.IN"]}}
+            {"eventSource": {"queueMgr": "QM1"}}
+            [1, 2, 3]
+        "#;
+        let parsed = parse(events).unwrap();
+        let errors: Vec<(usize, String)> =
+            parsed.records.into_iter().map(|(index, record)| (index, record.unwrap_err())).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, "endDate is missing".to_string()),
+                (2, format!("the interval {0} to {0} is empty", Local.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap())),
+                (3, "invalid date_time '2024-05-01 25:00:00'".to_string()),
+                (4, "queue data is not an object".to_string()),
+                (5, "eventData is missing".to_string()),
+                (6, "eventData is missing".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_input_never_panics() {
+        for end in (0..SAMPLE.len()).filter(|end| SAMPLE.is_char_boundary(*end)) {
+            if let Ok(parsed) = parse(&SAMPLE[..end]) {
+                // Everything before the cut is kept; the cut event is reported.
+                assert!(parsed.records.len() <= 4, "cut at {}", end);
+            }
+        }
+        let cut = SAMPLE.find("\"queueName\" : \"ORDERS.IN\"").unwrap();
+        let parsed = parse(&SAMPLE[..cut]).unwrap();
+        assert!(parsed.records.iter().any(|(index, record)| *index == 3 && record.is_err()));
+        assert_eq!(parsed.records.iter().filter(|(_, record)| record.is_ok()).count(), 2);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::domain::catalog::{CatalogKind, CatalogMetadata, CatalogRecord, ParsedCatalogRecord};
+use serde::Deserialize;
+use std::io::Read;
+
+/// Top level of a YAML catalog: lists of entries, each a `name` with the
+/// metadata fields.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CatalogYaml {
+    #[serde(default)]
+    systems: Vec<serde_norway::Value>,
+    #[serde(default)]
+    functions: Vec<serde_norway::Value>,
+}
+
+fn yaml_record(kind: CatalogKind, value: serde_norway::Value) -> Result<CatalogRecord, String> {
+    let serde_norway::Value::Mapping(mut mapping) = value else {
+        return Err("entry must be a mapping".to_string());
+    };
+    let name = match mapping.remove("name") {
+        Some(serde_norway::Value::String(name)) => name,
+        Some(_) => return Err("name must be a string".to_string()),
+        None => return Err("entry is missing name".to_string()),
+    };
+    let metadata: CatalogMetadata =
+        serde_norway::from_value(serde_norway::Value::Mapping(mapping)).map_err(|e| e.to_string())?;
+    Ok(CatalogRecord { kind, name, metadata })
+}
+
+/// Reads a YAML document with `systems` and `functions` lists. Entries are
+/// numbered from 1, systems first.
+pub fn parse_catalog_yaml(text: &str) -> Result<Vec<ParsedCatalogRecord>, String> {
+    let catalog: CatalogYaml = serde_norway::from_str(text).map_err(|e| e.to_string())?;
+    let entries = catalog
+        .systems
+        .into_iter()
+        .map(|value| (CatalogKind::System, value))
+        .chain(catalog.functions.into_iter().map(|value| (CatalogKind::Function, value)));
+    Ok(entries
+        .enumerate()
+        .map(|(idx, (kind, value))| (idx + 1, yaml_record(kind, value)))
+        .collect())
+}
+
+/// Reads catalog entries from CSV with a header row. `kind` (`system` or
+/// `function`) and `name` are required; `display_name`, `description`,
+/// `owner_team`, `contact`, `criticality`, `tags` (separated by `;`),
+/// `expected_tps_min` and `expected_tps_max` are optional, and may be empty.
+pub fn parse_catalog_csv<R: Read>(reader: R) -> Result<Vec<ParsedCatalogRecord>, String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+
+    let kind_col = column("kind").ok_or("CSV is missing the kind column")?;
+    let name_col = column("name").ok_or("CSV is missing the name column")?;
+    let text_cols = ["display_name", "description", "owner_team", "contact"].map(column);
+    let criticality_col = column("criticality");
+    let tags_col = column("tags");
+    let tps_cols = ["expected_tps_min", "expected_tps_max"].map(|name| (name, column(name)));
+
+    let mut records = Vec::new();
+    for (idx, row) in reader.records().enumerate() {
+        let line = idx + 2;
+        let parsed = row.map_err(|e| e.to_string()).and_then(|row| {
+            let field = |col: usize| row.get(col).unwrap_or_default();
+            let text = |col: Option<usize>| col.map(field).filter(|v| !v.is_empty()).map(String::from);
+            let [display_name, description, owner_team, contact] = text_cols.map(text);
+            let [expected_tps_min, expected_tps_max] = tps_cols.map(|(name, col)| {
+                text(col)
+                    .map(|value| value.parse().map_err(|_| format!("invalid {} '{}'", name, value)))
+                    .transpose()
+            });
+            Ok(CatalogRecord {
+                kind: field(kind_col).parse()?,
+                name: field(name_col).to_string(),
+                metadata: CatalogMetadata {
+                    display_name,
+                    description,
+                    owner_team,
+                    contact,
+                    criticality: text(criticality_col).map(|value| value.parse()).transpose()?,
+                    tags: text(tags_col).map(|tags| tags.split(';').map(String::from).collect()).unwrap_or_default(),
+                    expected_tps_min: expected_tps_min?,
+                    expected_tps_max: expected_tps_max?,
+                },
+            })
+        });
+        records.push((line, parsed));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::catalog::Criticality;
+
+    fn reasons(records: Vec<ParsedCatalogRecord>) -> Vec<(usize, Result<String, String>)> {
+        records
+            .into_iter()
+            .map(|(index, record)| (index, record.map(|record| format!("{}/{}", record.kind.as_str(), record.name))))
+            .collect()
+    }
+
+    #[test]
+    fn yaml_lists_systems_then_functions() {
+        let yaml = "
+functions:
+  - name: PAY
+    owner_team: payments
+    criticality: high
+    tags: [payments, tier-1]
+    expected_tps_min: 0.5
+    expected_tps_max: 20
+systems:
+  - name: SYS-A
+    display_name: Core banking
+";
+        let records = parse_catalog_yaml(yaml).unwrap();
+        let pay = records[1].1.as_ref().unwrap();
+        assert_eq!(pay.metadata.owner_team.as_deref(), Some("payments"));
+        assert_eq!(pay.metadata.criticality, Some(Criticality::High));
+        assert_eq!(pay.metadata.tags, vec!["payments", "tier-1"]);
+        assert_eq!((pay.metadata.expected_tps_min, pay.metadata.expected_tps_max), (Some(0.5), Some(20.0)));
+        assert_eq!(
+            reasons(records),
+            vec![(1, Ok("system/SYS-A".to_string())), (2, Ok("function/PAY".to_string()))]
+        );
+    }
+
+    #[test]
+    fn bad_yaml_entries_are_reported_one_by_one() {
+        let yaml = "
+systems:
+  - SYS-A
+  - display_name: No name
+  - name: 7
+  - name: SYS-B
+    criticality: urgent
+  - name: SYS-C
+    colour: blue
+  - name: SYS-D
+";
+        let records = reasons(parse_catalog_yaml(yaml).unwrap());
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[0], (1, Err("entry must be a mapping".to_string())));
+        assert_eq!(records[1], (2, Err("entry is missing name".to_string())));
+        assert_eq!(records[2], (3, Err("name must be a string".to_string())));
+        assert!(records[3].1.as_ref().unwrap_err().contains("urgent"));
+        assert!(records[4].1.as_ref().unwrap_err().contains("colour"));
+        assert_eq!(records[5], (6, Ok("system/SYS-D".to_string())));
+    }
+
+    #[test]
+    fn yaml_that_is_not_a_catalog_is_refused() {
+        assert!(parse_catalog_yaml("queues:\n  - name: PAY.IN\n").is_err());
+        assert!(parse_catalog_yaml("systems: [name: SYS-A").is_err());
+        assert!(parse_catalog_yaml("- SYS-A\n- SYS-B\n").is_err());
+    }
+
+    #[test]
+    fn csv_rows_carry_optional_columns() {
+        let csv = "\
+Kind,Name,Owner_Team,Criticality,Tags,Expected_TPS_Max
+system,SYS-A,core,critical,tier-1;core,
+function,PAY,,,,12.5
+function,QRY,,,,fast
+queue,PAY.IN,,,,
+function,ORD,,severe,,
+";
+        let records = parse_catalog_csv(csv.as_bytes()).unwrap();
+        let sys_a = records[0].1.as_ref().unwrap();
+        assert_eq!(sys_a.metadata.owner_team.as_deref(), Some("core"));
+        assert_eq!(sys_a.metadata.criticality, Some(Criticality::Critical));
+        assert_eq!(sys_a.metadata.tags, vec!["tier-1", "core"]);
+        assert_eq!(sys_a.metadata.expected_tps_max, None);
+        let pay = records[1].1.as_ref().unwrap();
+        assert_eq!((pay.metadata.owner_team.as_deref(), pay.metadata.expected_tps_max), (None, Some(12.5)));
+        assert_eq!(
+            reasons(records),
+            vec![
+                (2, Ok("system/SYS-A".to_string())),
+                (3, Ok("function/PAY".to_string())),
+                (4, Err("invalid expected_tps_max 'fast'".to_string())),
+                (5, Err("unknown kind 'queue' (system, function)".to_string())),
+                (6, Err("unknown criticality 'severe' (low, medium, high, critical)".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_without_kind_or_name_is_refused() {
+        assert!(parse_catalog_csv("name,owner_team\nSYS-A,core\n".as_bytes()).is_err());
+        assert!(parse_catalog_csv("kind,owner_team\nsystem,core\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::domain::import::ParsedRecord;
+use crate::domain::model::{DepthMetrics, MQLogUsage, QueueDimensions};
+use crate::infrastructure::parsers::parse_local_datetime;
+use std::io::Read;
+
+/// Reads usage rows from CSV with a header row. Required columns are
+/// `system_name`, `mq_function`, `work_total` and either `date_time` or
+/// `date` + `minute`. `trans_per_sec` defaults to `work_total / 60`.
+/// `queue_manager`, `queue_name`, `channel`, `cur_depth`, `max_depth` and
+/// `oldest_msg_age_secs` are optional, and may be empty.
+///
+/// Each entry carries its line number so rejected rows can be reported.
+pub fn parse_usage_csv<R: Read>(reader: R) -> Result<Vec<ParsedRecord>, String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+
+    let date_time_col = column("date_time");
+    let date_col = column("date");
+    let minute_col = column("minute");
+    if date_time_col.is_none() && (date_col.is_none() || minute_col.is_none()) {
+        return Err("CSV needs a date_time column or date and minute columns".to_string());
+    }
+    let system_col = column("system_name").ok_or("CSV is missing the system_name column")?;
+    let function_col = column("mq_function").ok_or("CSV is missing the mq_function column")?;
+    let work_col = column("work_total").ok_or("CSV is missing the work_total column")?;
+    let tps_col = column("trans_per_sec");
+    let queue_cols = ["queue_manager", "queue_name", "channel"].map(column);
+    let depth_cols = ["cur_depth", "max_depth", "oldest_msg_age_secs"].map(|name| (name, column(name)));
+
+    let mut records = Vec::new();
+    for (idx, row) in reader.records().enumerate() {
+        let line = idx + 2;
+        let parsed = row.map_err(|e| e.to_string()).and_then(|row| {
+            let field = |col: usize| row.get(col).unwrap_or_default();
+            let date_time = match date_time_col {
+                Some(col) => parse_local_datetime(field(col))?,
+                None => parse_local_datetime(&format!(
+                    "{} {}",
+                    field(date_col.unwrap_or_default()),
+                    field(minute_col.unwrap_or_default())
+                ))?,
+            };
+            let work_total: f64 = field(work_col)
+                .parse()
+                .map_err(|_| format!("invalid work_total '{}'", field(work_col)))?;
+            let trans_per_sec = match tps_col.map(field).filter(|v| !v.is_empty()) {
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| format!("invalid trans_per_sec '{}'", value))?,
+                None => work_total / 60.0,
+            };
+            let text = |col: Option<usize>| col.map(field).filter(|v| !v.is_empty()).map(String::from);
+            let [queue_manager, queue_name, channel] = queue_cols.map(text);
+            let [cur_depth, max_depth, oldest_msg_age_secs] = depth_cols
+                .map(|(name, col)| {
+                    text(col)
+                        .map(|value| value.parse().map_err(|_| format!("invalid {} '{}'", name, value)))
+                        .transpose()
+                });
+            Ok(MQLogUsage::new(
+                date_time,
+                field(system_col).to_string(),
+                field(function_col).to_string(),
+                work_total,
+                trans_per_sec,
+            )
+            .with_queue(QueueDimensions {
+                queue_manager,
+                queue_name,
+                channel,
+            })
+            .with_depth(DepthMetrics {
+                cur_depth: cur_depth?,
+                max_depth: max_depth?,
+                oldest_msg_age_secs: oldest_msg_age_secs?,
+            }))
+        });
+        records.push((line, parsed));
+    }
+    Ok(records)
+}
@@ -0,0 +1,87 @@
+use crate::domain::import::ParsedRecord;
+use crate::domain::model::{DepthMetrics, MQLogUsage, QueueDimensions};
+use crate::infrastructure::parsers::parse_local_datetime;
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+/// One usage record as sent to the ingestion API. Either `date_time` or
+/// `date` + `minute` is required; `trans_per_sec` defaults to
+/// `work_total / 60`. The queue dimensions and depths are optional.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UsageRecordInput {
+    /// RFC 3339, or `YYYY-MM-DD HH:MM[:SS]` in the server's time zone.
+    #[schema(example = "2024-05-01T10:15:00+02:00")]
+    pub date_time: Option<String>,
+    #[schema(example = "2024-05-01")]
+    pub date: Option<String>,
+    #[schema(example = "10:15")]
+    pub minute: Option<String>,
+    pub system_name: String,
+    pub mq_function: String,
+    pub work_total: f64,
+    pub trans_per_sec: Option<f64>,
+    #[serde(flatten)]
+    pub queue: QueueDimensions,
+    #[serde(flatten)]
+    pub depth: DepthMetrics,
+}
+
+impl UsageRecordInput {
+    fn into_usage(self) -> Result<MQLogUsage, String> {
+        let date_time = match (self.date_time, self.date, self.minute) {
+            (Some(date_time), _, _) => parse_local_datetime(&date_time)?,
+            (None, Some(date), Some(minute)) => parse_local_datetime(&format!("{} {}", date, minute))?,
+            _ => return Err("date_time or date and minute are required".to_string()),
+        };
+        let trans_per_sec = self.trans_per_sec.unwrap_or(self.work_total / 60.0);
+        Ok(MQLogUsage::new(
+            date_time,
+            self.system_name,
+            self.mq_function,
+            self.work_total,
+            trans_per_sec,
+        )
+        .with_queue(self.queue)
+        .with_depth(self.depth))
+    }
+}
+
+fn parse_record(value: Value) -> Result<MQLogUsage, String> {
+    serde_json::from_value::<UsageRecordInput>(value)
+        .map_err(|e| e.to_string())
+        .and_then(UsageRecordInput::into_usage)
+}
+
+/// Reads a JSON array of records, or an object with a `records` array.
+/// Each entry carries its position in the array, starting at 0; a record
+/// of the wrong shape is rejected without failing the others.
+pub fn parse_usage_json(body: &[u8]) -> Result<Vec<ParsedRecord>, String> {
+    let records = match serde_json::from_slice::<Value>(body).map_err(|e| e.to_string())? {
+        Value::Array(records) => records,
+        Value::Object(mut object) => match object.remove("records") {
+            Some(Value::Array(records)) => records,
+            _ => return Err("the object must have a records array".to_string()),
+        },
+        _ => return Err("the body must be an array of records or an object with a records array".to_string()),
+    };
+    Ok(records.into_iter().map(parse_record).enumerate().collect())
+}
+
+/// Reads one JSON record per line, skipping blank lines. Each entry
+/// carries its line number; a malformed line is rejected without failing
+/// the others.
+pub fn parse_usage_ndjson(body: &[u8]) -> Result<Vec<ParsedRecord>, String> {
+    let body = std::str::from_utf8(body).map_err(|e| format!("the body is not UTF-8: {}", e))?;
+    Ok(body
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            let parsed = serde_json::from_str::<Value>(line)
+                .map_err(|e| format!("malformed JSON: {}", e))
+                .and_then(parse_record);
+            (idx + 1, parsed)
+        })
+        .collect())
+}
@@ -0,0 +1,29 @@
+pub mod amqsevt;
+pub mod catalog;
+pub mod csv_usage;
+pub mod json_usage;
+pub mod runmqsc;
+
+use crate::domain::queue_mapping::QueueMapping;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::path::Path;
+
+/// Parses RFC 3339 timestamps, or naive `YYYY-MM-DD HH:MM[:SS]` values
+/// interpreted in the server's local time zone.
+pub fn parse_local_datetime(value: &str) -> Result<DateTime<Local>, String> {
+    let value = value.trim();
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.with_timezone(&Local));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .ok_or_else(|| format!("invalid date_time '{}'", value))
+}
+
+/// Reads a TOML file of `[[queue]]` rules.
+pub fn load_queue_mapping(path: &Path) -> Result<QueueMapping, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,274 @@
+use crate::domain::queue_depth::{ParsedDepth, QueueDepth};
+use crate::domain::queue_mapping::QueueMapping;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+const QUEUE_MANAGER_PREFIX: &str = "Starting MQSC for queue manager ";
+
+/// Depth samples read from one runmqsc snapshot, and the queues no rule
+/// maps.
+#[derive(Debug, Default)]
+pub struct RunmqscRecords {
+    pub records: Vec<ParsedDepth>,
+    /// `QMGR/QUEUE` of queues without a rule; they are not stored.
+    pub unmapped: BTreeSet<String>,
+}
+
+/// Attributes of one queue, merged over every command of the snapshot.
+struct QueueAttributes {
+    /// Line of the first attribute seen for the queue.
+    line: usize,
+    values: HashMap<String, String>,
+}
+
+/// `KEYWORD(value)` pairs of one line of MQSC output; values may contain
+/// blanks and balanced parentheses.
+fn attributes(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('(') {
+        let keyword = rest[..open].trim_start();
+        let keyword = &keyword[keyword.rfind(' ').map_or(0, |space| space + 1)..];
+        let mut depth = 0;
+        let close = rest[open..].char_indices().find_map(|(idx, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(open + idx)
+        });
+        let Some(close) = close else { break };
+        if !keyword.is_empty() && keyword.chars().all(|c| c.is_ascii_alphanumeric()) {
+            pairs.push((keyword.to_ascii_uppercase(), rest[open + 1..close].trim().to_string()));
+        }
+        rest = &rest[close + 1..];
+    }
+    pairs
+}
+
+/// Time in a file name such as `qstatus_20240501_1015.txt` or
+/// `QM1-20240501T101500.out`, in the server's time zone.
+pub fn snapshot_time(path: &Path) -> Option<DateTime<Local>> {
+    let stem = path.file_stem()?.to_str()?;
+    let groups: Vec<&str> = stem.split(|c: char| !c.is_ascii_digit()).filter(|g| !g.is_empty()).collect();
+    groups.iter().enumerate().find_map(|(idx, group)| {
+        let digits = match (group.len(), groups.get(idx + 1)) {
+            (12 | 14, _) => group.to_string(),
+            (8, Some(time)) if matches!(time.len(), 4 | 6) => format!("{}{}", group, time),
+            _ => return None,
+        };
+        let digits = if digits.len() == 12 { digits + "00" } else { digits };
+        let naive = NaiveDateTime::parse_from_str(&digits, "%Y%m%d%H%M%S").ok()?;
+        Local.from_local_datetime(&naive).earliest()
+    })
+}
+
+fn number<T: std::str::FromStr>(values: &HashMap<String, String>, keyword: &str) -> Result<Option<T>, String> {
+    match values.get(keyword).filter(|v| !v.is_empty()) {
+        Some(value) => value.parse().map(Some).map_err(|_| format!("invalid {} '{}'", keyword, value)),
+        None => Ok(None),
+    }
+}
+
+fn depth_sample(
+    taken_at: DateTime<Local>,
+    queue_manager: &str,
+    queue_name: &str,
+    values: &HashMap<String, String>,
+    mq_function: &str,
+    system_name: &str,
+) -> Result<Option<QueueDepth>, String> {
+    // Queues without a depth are not local queues (e.g. aliases listed by DISPLAY QUEUE).
+    let Some(cur_depth) = number::<i64>(values, "CURDEPTH")? else {
+        return Ok(None);
+    };
+    let interval = number::<f64>(values, "RESETINT")?.filter(|secs| *secs > 0.0);
+    let rate = |keyword| -> Result<Option<f64>, String> {
+        Ok(number::<f64>(values, keyword)?.zip(interval).map(|(count, secs)| count / secs))
+    };
+    Ok(Some(QueueDepth {
+        date_time: taken_at,
+        queue_manager: queue_manager.to_string(),
+        queue_name: queue_name.to_string(),
+        system_name: system_name.to_string(),
+        mq_function: mq_function.to_string(),
+        cur_depth,
+        max_depth: number(values, "MAXDEPTH")?,
+        oldest_msg_age_secs: number(values, "MSGAGE")?,
+        enqueue_rate: rate("MSGSIN")?,
+        dequeue_rate: rate("MSGSOUT")?,
+        depth_rate: None,
+    }))
+}
+
+/// Reads the output of a runmqsc session taken at `taken_at`, e.g.
+///
+/// ```text
+/// DISPLAY QSTATUS(*) TYPE(QUEUE) ALL
+/// DISPLAY QLOCAL(*) CURDEPTH MAXDEPTH
+/// RESET QSTATS(*)
+/// ```
+///
+/// The attributes each command shows for a queue are merged, so one sample
+/// per local queue results. `RESET QSTATS` output adds the enqueue and
+/// dequeue rates; `depth_rate` is left to the caller, which knows the
+/// previous snapshot. Handle status (`TYPE(HANDLE)`) is ignored.
+pub fn parse_runmqsc<R: Read>(
+    reader: R,
+    taken_at: DateTime<Local>,
+    mapping: &QueueMapping,
+) -> Result<RunmqscRecords, String> {
+    let mut queue_manager = None;
+    let mut queues: BTreeMap<String, QueueAttributes> = BTreeMap::new();
+    let mut current: Option<(usize, Vec<(String, String)>)> = None;
+    let mut finish = |record: Option<(usize, Vec<(String, String)>)>| {
+        let Some((line, pairs)) = record else { return };
+        let values: HashMap<String, String> = pairs.into_iter().collect();
+        if values.get("TYPE").is_some_and(|t| t == "HANDLE") {
+            return;
+        }
+        if let Some(queue) = values.get("QUEUE").cloned() {
+            let entry = queues.entry(queue).or_insert_with(|| QueueAttributes {
+                line,
+                values: HashMap::new(),
+            });
+            entry.values.extend(values);
+        }
+    };
+
+    for (idx, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix(QUEUE_MANAGER_PREFIX) {
+            queue_manager = Some(name.trim_end_matches('.').trim().to_string());
+        } else if trimmed.starts_with("AMQ") {
+            // Each object of a DISPLAY or RESET command starts with a message id.
+            finish(current.replace((idx + 1, Vec::new())));
+        } else if trimmed.split_once(" : ").is_some_and(|(n, _)| n.trim().parse::<u32>().is_ok()) {
+            // Echo of the next command.
+            finish(current.take());
+        } else if let Some((_, pairs)) = current.as_mut() {
+            pairs.extend(attributes(trimmed));
+        }
+    }
+    finish(current.take());
+    let queue_manager = queue_manager.ok_or("no 'Starting MQSC for queue manager' line; is this runmqsc output?")?;
+
+    let mut result = RunmqscRecords::default();
+    for (queue_name, queue) in queues {
+        let Some(rule) = mapping.resolve(&queue_manager, &queue_name) else {
+            result.unmapped.insert(format!("{}/{}", queue_manager, queue_name));
+            continue;
+        };
+        let system_name = rule.system_name(&queue_manager);
+        match depth_sample(taken_at, &queue_manager, &queue_name, &queue.values, &rule.mq_function, system_name) {
+            Ok(Some(sample)) => result.records.push((queue.line, Ok(sample))),
+            Ok(None) => {}
+            Err(reason) => result.records.push((queue.line, Err(reason))),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("../../../tests/fixtures/runmqsc_qm1.txt");
+
+    fn mapping() -> QueueMapping {
+        toml::from_str(include_str!("../../../tests/fixtures/queue-mapping.toml")).unwrap()
+    }
+
+    fn taken_at() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 10, 15, 0).unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<RunmqscRecords, String> {
+        parse_runmqsc(input, taken_at(), &mapping())
+    }
+
+    #[test]
+    fn commands_are_merged_into_one_sample_per_queue() {
+        let parsed = parse(SAMPLE.as_bytes()).unwrap();
+        let samples: Vec<(usize, QueueDepth)> =
+            parsed.records.into_iter().map(|(line, sample)| (line, sample.unwrap())).collect();
+        assert_eq!(samples.len(), 2);
+
+        let (line, pay_in) = &samples[0];
+        assert_eq!(*line, 6);
+        assert_eq!(pay_in.date_time, taken_at());
+        assert_eq!(
+            (pay_in.queue_manager.as_str(), pay_in.queue_name.as_str(), pay_in.mq_function.as_str(), pay_in.system_name.as_str()),
+            ("QM1", "PAY.IN", "PAY", "SYS-A")
+        );
+        // The handle status listing its own CURDEPTH is ignored.
+        assert_eq!(pay_in.cur_depth, 42);
+        assert_eq!(pay_in.max_depth, Some(5000));
+        assert_eq!(pay_in.oldest_msg_age_secs, Some(17));
+        assert_eq!(pay_in.enqueue_rate, Some(2.0));
+        assert_eq!(pay_in.dequeue_rate, Some(1.8));
+        assert_eq!(pay_in.depth_rate, None);
+
+        let (_, pay_out) = &samples[1];
+        assert_eq!(pay_out.queue_name, "PAY.OUT");
+        assert_eq!(pay_out.cur_depth, 0);
+        assert_eq!(pay_out.oldest_msg_age_secs, None);
+        assert_eq!(pay_out.enqueue_rate, Some(0.1));
+
+        assert_eq!(parsed.unmapped.into_iter().collect::<Vec<_>>(), vec!["QM1/OTHER.Q".to_string()]);
+    }
+
+    #[test]
+    fn output_without_a_queue_manager_is_refused() {
+        assert!(parse(b"").is_err());
+        assert!(parse(b"AMQ8409I: Display Queue details.\n   QUEUE(PAY.IN) CURDEPTH(1)\n").is_err());
+        assert!(parse(b"Starting MQSC for queue manager QM1.\n\xff\xfe CURDEPTH(1)\n").is_err());
+    }
+
+    #[test]
+    fn bad_attribute_values_are_reported_per_queue() {
+        let output = "Starting MQSC for queue manager QM1.\n\
+                      AMQ8409I: Display Queue details.\n   QUEUE(PAY.IN) CURDEPTH(many)\n\
+                      AMQ8409I: Display Queue details.\n   QUEUE(PAY.OUT) CURDEPTH(3) RESETINT(x)\n\
+                      AMQ8409I: Display Queue details.\n   QUEUE(PAY.ALIAS) TYPE(QALIAS)\n\
+                      AMQ8409I: Display Queue details.\n   QUEUE(PAY.X) CURDEPTH(4) DESCR(unbalanced (\n";
+        let parsed = parse(output.as_bytes()).unwrap();
+        let records: Vec<(usize, Result<i64, String>)> = parsed
+            .records
+            .into_iter()
+            .map(|(line, sample)| (line, sample.map(|sample| sample.cur_depth)))
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                (2, Err("invalid CURDEPTH 'many'".to_string())),
+                (4, Err("invalid RESETINT 'x'".to_string())),
+                (8, Ok(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_output_never_panics() {
+        for end in (0..SAMPLE.len()).filter(|end| SAMPLE.is_char_boundary(*end)) {
+            let cut = &SAMPLE[..end];
+            match parse(cut.as_bytes()) {
+                Ok(parsed) => assert!(parsed.records.len() <= 2, "cut at {}", end),
+                Err(_) => assert!(!cut.contains("queue manager QM"), "cut at {}", end),
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_time_comes_from_the_file_name() {
+        let at = |y, mo, d, h, mi, s| Local.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap();
+        assert_eq!(snapshot_time(Path::new("qstatus_20240501_1015.txt")), Some(at(2024, 5, 1, 10, 15, 0)));
+        assert_eq!(snapshot_time(Path::new("QM1-20240501T101530.out")), Some(at(2024, 5, 1, 10, 15, 30)));
+        assert_eq!(snapshot_time(Path::new("qstatus.txt")), None);
+        assert_eq!(snapshot_time(Path::new("qstatus_20241301_1015.txt")), None);
+    }
+}
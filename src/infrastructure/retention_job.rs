@@ -0,0 +1,173 @@
+use crate::application::retention_service::apply_retention;
+use crate::domain::retention::{RetentionPolicy, RetentionReport};
+use crate::infrastructure::cache::ResponseCache;
+use crate::infrastructure::metrics::Metrics;
+use actix_web::rt::time::sleep;
+use actix_web::web;
+use chrono::Local;
+use log::{error, info, warn};
+use rusqlite::Connection;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Marks a run in progress; clears the mark when dropped, however the run
+/// ends.
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Scheduled enforcement of the data retention policy. Shared through
+/// `AppState` so the admin API can inspect and trigger it.
+pub struct RetentionJob {
+    pub policy: RetentionPolicy,
+    pub enabled: bool,
+    pub interval: Duration,
+    running: Arc<AtomicBool>,
+    last_report: Mutex<Option<RetentionReport>>,
+    metrics: Arc<Metrics>,
+}
+
+impl RetentionJob {
+    pub fn new(policy: RetentionPolicy, enabled: bool, interval: Duration, metrics: Arc<Metrics>) -> Self {
+        Self {
+            policy,
+            enabled,
+            interval,
+            running: Arc::new(AtomicBool::new(false)),
+            last_report: Mutex::new(None),
+            metrics,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Marks a run in progress, unless one already is.
+    fn try_start(&self) -> Option<RunningGuard> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        Some(RunningGuard(self.running.clone()))
+    }
+
+    pub fn last_report(&self) -> Option<RetentionReport> {
+        self.last_report.lock().unwrap().clone()
+    }
+
+    /// Runs the policy once on the blocking thread pool. Returns `None` when
+    /// a run is already in progress.
+    pub async fn run_now(
+        self: &Arc<Self>,
+        db: Arc<Mutex<Connection>>,
+        cache: &ResponseCache,
+    ) -> Option<RetentionReport> {
+        let Some(guard) = self.try_start() else {
+            warn!("Retention run requested while another run is in progress");
+            return None;
+        };
+
+        let started = Instant::now();
+        let policy = self.policy.clone();
+        let result = web::block(move || {
+            // Held by the blocking task, so the mark outlives a dropped
+            // request but not the work itself.
+            let _guard = guard;
+            let mut report = RetentionReport {
+                started_at: Some(Local::now()),
+                ..Default::default()
+            };
+            if let Err(e) = apply_retention(&db, &policy, Local::now(), &mut report) {
+                report.error = Some(e.to_string());
+            }
+            report.finished_at = Some(Local::now());
+            report
+        })
+        .await;
+
+        let report = result.unwrap_or_else(|e| RetentionReport {
+            error: Some(format!("Retention task failed: {}", e)),
+            ..Default::default()
+        });
+        match &report.error {
+            Some(e) => error!("Retention run failed: {} ({:?})", e, report),
+            None => info!(
+                "Retention run finished: {} minute rows -> {} hourly rows, {} hourly rows -> {} daily rows, {} daily rows deleted, vacuumed: {}",
+                report.minute_rows_downsampled,
+                report.hourly_rows_written,
+                report.hourly_rows_downsampled,
+                report.daily_rows_written,
+                report.daily_rows_deleted,
+                report.vacuumed
+            ),
+        }
+
+        self.metrics.record_job("retention", report.error.is_none(), started.elapsed());
+
+        if report.minute_rows_downsampled + report.hourly_rows_downsampled + report.daily_rows_deleted > 0 {
+            cache.bump_generation().await;
+        }
+
+        *self.last_report.lock().unwrap() = Some(report.clone());
+        Some(report)
+    }
+
+    /// Starts the periodic background run if the job is enabled.
+    pub fn spawn(self: Arc<Self>, db: Arc<Mutex<Connection>>, cache: Arc<ResponseCache>) {
+        if !self.enabled {
+            info!("Retention job disabled (set retention.enabled / RETENTION_ENABLED=true to enable)");
+            return;
+        }
+        info!(
+            "Retention job scheduled every {} minutes with policy {:?}",
+            self.interval.as_secs() / 60,
+            self.policy
+        );
+        actix_web::rt::spawn(async move {
+            loop {
+                sleep(self.interval).await;
+                self.run_now(db.clone(), &cache).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job() -> RetentionJob {
+        RetentionJob::new(RetentionPolicy::default(), false, Duration::from_secs(60), Arc::new(Metrics::new()))
+    }
+
+    #[test]
+    fn one_run_at_a_time() {
+        let job = job();
+        let guard = job.try_start().expect("first run starts");
+        assert!(job.is_running());
+        assert!(job.try_start().is_none());
+        drop(guard);
+        assert!(!job.is_running());
+        assert!(job.try_start().is_some());
+    }
+
+    #[test]
+    fn abandoned_run_releases_the_mark() {
+        let job = job();
+        let mut run = Box::pin(async {
+            let _guard = job.try_start().unwrap();
+            std::future::pending::<()>().await;
+        });
+        let waker = futures_util::task::noop_waker();
+        let _ = run.as_mut().poll(&mut std::task::Context::from_waker(&waker));
+        assert!(job.is_running());
+        // A request future dropped mid-run, e.g. on client disconnect.
+        drop(run);
+        assert!(!job.is_running());
+    }
+}
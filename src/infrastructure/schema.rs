@@ -0,0 +1,129 @@
+use crate::application::maintenance_service;
+use log::info;
+use rusqlite::Connection;
+use std::path::Path;
+
+/// Ordered list of schema migrations. The index + 1 of each entry is the
+/// `PRAGMA user_version` the database has once that migration is applied.
+const MIGRATIONS: &[&str] = &[
+    // 1: base usage table (no-op for databases created by the original loader)
+    "CREATE TABLE IF NOT EXISTS mq_data (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        date_time TEXT NOT NULL,
+        date TEXT NOT NULL,
+        minute TEXT NOT NULL,
+        system_name TEXT NOT NULL,
+        mq_function TEXT NOT NULL,
+        work_total REAL NOT NULL,
+        trans_per_sec REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_mq_data_function_time ON mq_data (mq_function, date_time);",
+    // 2: resolution of each row, used by the retention policy to downsample
+    "ALTER TABLE mq_data ADD COLUMN granularity TEXT NOT NULL DEFAULT 'minute';
+    CREATE INDEX IF NOT EXISTS idx_mq_data_granularity_time ON mq_data (granularity, date_time);",
+    // 3: accounts managed with the `user` subcommand
+    "CREATE TABLE IF NOT EXISTS users (
+        username TEXT PRIMARY KEY,
+        password_hash TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );",
+    // 4: running migrations and large imports, reported by /readyz
+    "CREATE TABLE IF NOT EXISTS maintenance (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        operation TEXT NOT NULL,
+        started_at TEXT NOT NULL
+    );",
+    // 5: queue depth snapshots loaded from runmqsc output
+    "CREATE TABLE IF NOT EXISTS mq_queue_depth (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        date_time TEXT NOT NULL,
+        queue_manager TEXT NOT NULL,
+        queue_name TEXT NOT NULL,
+        system_name TEXT NOT NULL,
+        mq_function TEXT NOT NULL,
+        cur_depth INTEGER NOT NULL,
+        max_depth INTEGER,
+        enqueue_rate REAL,
+        dequeue_rate REAL,
+        depth_rate REAL,
+        UNIQUE (queue_manager, queue_name, date_time)
+    );
+    CREATE INDEX IF NOT EXISTS idx_mq_queue_depth_function_time ON mq_queue_depth (mq_function, date_time);",
+    // 6: checksums of files loaded by the inbox watcher
+    "CREATE TABLE IF NOT EXISTS ingested_files (
+        checksum TEXT PRIMARY KEY,
+        file_name TEXT NOT NULL,
+        format TEXT NOT NULL,
+        loaded_at TEXT NOT NULL,
+        inserted INTEGER NOT NULL,
+        replaced INTEGER NOT NULL,
+        skipped INTEGER NOT NULL,
+        rejected INTEGER NOT NULL
+    );",
+    // 7: optional queue-level dimensions and depth of usage rows, and the
+    // age of the oldest message of depth snapshots
+    "ALTER TABLE mq_data ADD COLUMN queue_manager TEXT;
+    ALTER TABLE mq_data ADD COLUMN queue_name TEXT;
+    ALTER TABLE mq_data ADD COLUMN channel TEXT;
+    ALTER TABLE mq_data ADD COLUMN cur_depth INTEGER;
+    ALTER TABLE mq_data ADD COLUMN max_depth INTEGER;
+    ALTER TABLE mq_data ADD COLUMN oldest_msg_age_secs INTEGER;
+    CREATE INDEX IF NOT EXISTS idx_mq_data_function_queue ON mq_data (mq_function, queue_manager, queue_name, date_time);
+    ALTER TABLE mq_queue_depth ADD COLUMN oldest_msg_age_secs INTEGER;",
+    // 8: metadata of systems and functions; tags are a JSON array
+    "CREATE TABLE IF NOT EXISTS catalog (
+        kind TEXT NOT NULL,
+        name TEXT NOT NULL,
+        display_name TEXT,
+        description TEXT,
+        owner_team TEXT,
+        contact TEXT,
+        criticality TEXT,
+        tags TEXT NOT NULL DEFAULT '[]',
+        expected_tps_min REAL,
+        expected_tps_max REAL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (kind, name)
+    );",
+];
+
+/// Version a fully migrated database reports.
+pub fn latest_version() -> usize {
+    MIGRATIONS.len()
+}
+
+pub fn schema_version(connection: &Connection) -> rusqlite::Result<usize> {
+    connection.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+pub fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let current = schema_version(connection)?;
+    if current >= latest_version() {
+        return Ok(());
+    }
+    // Databases older than the maintenance table cannot be marked busy.
+    if current >= 4 {
+        let name = format!("migration to schema version {}", latest_version());
+        return maintenance_service::run(connection, &name, |connection| apply_migrations(connection, current));
+    }
+    apply_migrations(connection, current)
+}
+
+fn apply_migrations(connection: &mut Connection, current: usize) -> rusqlite::Result<()> {
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = idx + 1;
+        info!("Applying schema migration {}", version);
+        let tx = connection.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Opens the SQLite database and brings its schema up to date.
+pub fn open_database(path: &Path) -> rusqlite::Result<Connection> {
+    let mut connection = Connection::open(path)?;
+    migrate(&mut connection)?;
+    Ok(connection)
+}
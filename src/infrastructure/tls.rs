@@ -0,0 +1,181 @@
+use crate::infrastructure::config::{ClientAuth, TlsConfig};
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use actix_web::rt::time::sleep;
+use log::{error, info};
+use rustls::RootCertStore;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::CertificateDer;
+use rustls::server::{ClientHello, ResolvesServerCert, ServerConfig, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// Identity of a client that presented a certificate, stored in the
+/// connection data of every request made over that connection.
+#[derive(Debug, Clone)]
+pub struct ClientIdentity {
+    pub common_name: String,
+    /// User the common name is mapped to in `tls.client_cert_users`.
+    pub user: Option<String>,
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("cannot read certificates from {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path, provider: &CryptoProvider) -> Result<CertifiedKey, String> {
+    let certs = read_certs(cert_path)?;
+    let file = File::open(key_path).map_err(|e| format!("cannot open {}: {}", key_path.display(), e))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("cannot read private key from {}: {}", key_path.display(), e))?
+        .ok_or_else(|| format!("no private key found in {}", key_path.display()))?;
+    let signing_key = provider
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| format!("unsupported private key in {}: {}", key_path.display(), e))?;
+    let certified = CertifiedKey::new(certs, signing_key);
+    certified
+        .keys_match()
+        .map_err(|e| format!("{} does not match {}: {}", key_path.display(), cert_path.display(), e))?;
+    Ok(certified)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Serves the certificate currently on disk.
+///
+/// `reload_if_changed` swaps in the new certificate when either file's
+/// modification time changes. A broken pair (e.g. the key is rewritten
+/// before the certificate) is logged and the previous certificate is kept,
+/// so the next check picks up the completed pair.
+#[derive(Debug)]
+pub struct ReloadingCertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    loaded_at: Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl ReloadingCertResolver {
+    pub fn new(cert_path: PathBuf, key_path: PathBuf, provider: Arc<CryptoProvider>) -> Result<Self, String> {
+        let stamps = (modified(&cert_path), modified(&key_path));
+        let certified = load_certified_key(&cert_path, &key_path, &provider)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            provider,
+            current: RwLock::new(Arc::new(certified)),
+            loaded_at: Mutex::new(stamps),
+        })
+    }
+
+    pub fn reload_if_changed(&self) {
+        let stamps = (modified(&self.cert_path), modified(&self.key_path));
+        let mut loaded_at = self.loaded_at.lock().unwrap();
+        if *loaded_at == stamps {
+            return;
+        }
+        match load_certified_key(&self.cert_path, &self.key_path, &self.provider) {
+            Ok(certified) => {
+                *self.current.write().unwrap() = Arc::new(certified);
+                *loaded_at = stamps;
+                info!("Reloaded TLS certificate from {}", self.cert_path.display());
+            }
+            Err(e) => error!("TLS certificate reload failed, keeping the previous one: {}", e),
+        }
+    }
+
+    /// Checks the files every `interval` for the lifetime of the server.
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        actix_web::rt::spawn(async move {
+            loop {
+                sleep(interval).await;
+                self.reload_if_changed();
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// Builds the rustls configuration (and the resolver, for reloading) from
+/// the `[tls]` settings.
+pub fn server_config(tls: &TlsConfig) -> Result<(ServerConfig, Arc<ReloadingCertResolver>), String> {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let resolver = Arc::new(ReloadingCertResolver::new(
+        tls.cert_path.clone(),
+        tls.key_path.clone(),
+        provider.clone(),
+    )?);
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+    let builder = match (&tls.client_ca_path, tls.client_auth) {
+        (Some(ca_path), ClientAuth::Optional | ClientAuth::Required) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("invalid CA certificate in {}: {}", ca_path.display(), e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if tls.client_auth == ClientAuth::Optional {
+                verifier.allow_unauthenticated()
+            } else {
+                verifier
+            };
+            builder.with_client_cert_verifier(verifier.build().map_err(|e| e.to_string())?)
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    Ok((builder.with_cert_resolver(resolver.clone()), resolver))
+}
+
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    let name = parsed.subject().iter_common_name().next()?.as_str().ok()?.to_string();
+    Some(name)
+}
+
+/// `HttpServer::on_connect` callback recording the verified client
+/// certificate of a TLS connection as a [`ClientIdentity`].
+pub fn client_identity_hook(
+    users: BTreeMap<String, String>,
+) -> impl Fn(&dyn Any, &mut Extensions) + Send + Sync + 'static {
+    move |connection, data| {
+        let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+            return;
+        };
+        let (_, session) = stream.get_ref();
+        if let Some(common_name) = session
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .and_then(common_name)
+        {
+            let user = users.get(&common_name).cloned();
+            data.insert(ClientIdentity { common_name, user });
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::application::error::AppError;
+use crate::domain::retention::RetentionReport;
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::cache::CacheStats;
+use crate::interface::dto::{ApiResponse, RetentionStatusResponse};
+use actix_web::{get, post, web};
+
+#[utoipa::path(tag = "admin", security(("bearer_auth" = [])), responses((status = 200, body = ApiResponse<RetentionStatusResponse>)))]
+#[get("/admin/retention")]
+pub async fn retention_status(app_state: web::Data<AppState>) -> impl actix_web::Responder {
+    let job = &app_state.retention;
+    let status = RetentionStatusResponse {
+        enabled: job.enabled,
+        interval_minutes: job.interval.as_secs() / 60,
+        policy: job.policy.clone(),
+        running: job.is_running(),
+        last_report: job.last_report(),
+    };
+    ApiResponse::<RetentionStatusResponse>::success("Success", Some(status))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Report of the run", body = ApiResponse<RetentionReport>),
+        (status = 409, description = "A run is already in progress", body = ApiResponse<serde_json::Value>),
+        (status = 500, description = "The run failed", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[post("/admin/retention/run")]
+pub async fn run_retention(app_state: web::Data<AppState>) -> Result<ApiResponse<RetentionReport>, AppError> {
+    match app_state
+        .retention
+        .run_now(app_state.db.clone(), &app_state.cache)
+        .await
+    {
+        Some(report) => match &report.error {
+            None => Ok(ApiResponse::<RetentionReport>::success("Success", Some(report))),
+            Some(e) => Err(AppError::internal("run_retention", e)),
+        },
+        None => Err(AppError::Conflict("Retention run already in progress".to_string())),
+    }
+}
+
+#[utoipa::path(tag = "admin", security(("bearer_auth" = [])), responses((status = 200, body = ApiResponse<CacheStats>)))]
+#[get("/admin/cache")]
+pub async fn cache_stats(app_state: web::Data<AppState>) -> impl actix_web::Responder {
+    ApiResponse::<CacheStats>::success("Success", Some(app_state.cache.stats().await))
+}
+
+#[utoipa::path(tag = "admin", security(("bearer_auth" = [])), responses((status = 200, description = "The new cache generation", body = ApiResponse<u64>)))]
+#[post("/admin/cache/invalidate")]
+pub async fn invalidate_cache(app_state: web::Data<AppState>) -> impl actix_web::Responder {
+    let generation = app_state.cache.bump_generation().await;
+    ApiResponse::<u64>::success("Success", Some(generation))
+}
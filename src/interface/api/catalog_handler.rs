@@ -0,0 +1,193 @@
+use crate::application::catalog_service::{delete_entry, find_entry, import_entries, list_entries, upsert_entry};
+use crate::application::error::AppError;
+use crate::domain::auth::Claims;
+use crate::domain::catalog::{CatalogEntry, CatalogMetadata, ParsedCatalogRecord};
+use crate::domain::import::ImportReport;
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::parsers::catalog::{parse_catalog_csv, parse_catalog_yaml};
+use crate::interface::api::ingest_handler::read_body;
+use crate::interface::dto::{ApiResponse, CatalogImportQuery, CatalogPath, CatalogQuery, TagQuery};
+use actix_web::{HttpMessage, HttpRequest, delete, get, post, put, web};
+use log::info;
+
+/// The entry named by the path, or `404`.
+fn existing_entry(app_state: &AppState, path: &CatalogPath) -> Result<CatalogEntry, AppError> {
+    let connection = app_state.accounts.lock().unwrap();
+    find_entry(&connection, path.kind, path.name.trim())
+        .map_err(|e| AppError::internal("find_entry", e))?
+        .ok_or_else(|| AppError::NotFound(format!("no catalog entry for {} '{}'", path.kind.as_str(), path.name.trim())))
+}
+
+/// Catalog entries, filtered by kind and tags.
+#[utoipa::path(
+    tag = "catalog",
+    params(CatalogQuery, TagQuery),
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = ApiResponse<Vec<CatalogEntry>>))
+)]
+#[get("/catalog")]
+pub async fn list_catalog(
+    app_state: web::Data<AppState>,
+    query: web::Query<CatalogQuery>,
+    tags: web::Query<TagQuery>,
+) -> Result<ApiResponse<Vec<CatalogEntry>>, AppError> {
+    let tags = tags.tag_list();
+    let entries = {
+        let connection = app_state.accounts.lock().unwrap();
+        list_entries(&connection, query.kind).map_err(|e| AppError::internal("list_entries", e))?
+    };
+    let entries = entries.into_iter().filter(|entry| entry.metadata.has_tags(&tags)).collect();
+    Ok(ApiResponse::success("Success", Some(entries)))
+}
+
+#[utoipa::path(
+    tag = "catalog",
+    params(
+        ("kind" = String, Path, description = "`system` or `function` (plural accepted)"),
+        ("name" = String, Path, description = "System or function name"),
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, body = ApiResponse<CatalogEntry>),
+        (status = 404, description = "No entry", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/catalog/{kind}/{name}")]
+pub async fn get_catalog_entry(
+    app_state: web::Data<AppState>,
+    path: web::Path<CatalogPath>,
+) -> Result<ApiResponse<CatalogEntry>, AppError> {
+    Ok(ApiResponse::success("Success", Some(existing_entry(&app_state, &path)?)))
+}
+
+/// Creates or replaces the metadata of a system or function. Names need
+/// not have usage data yet.
+#[utoipa::path(
+    tag = "catalog",
+    params(
+        ("kind" = String, Path, description = "`system` or `function` (plural accepted)"),
+        ("name" = String, Path, description = "System or function name"),
+    ),
+    request_body = CatalogMetadata,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The stored entry; the message says whether it was created", body = ApiResponse<CatalogEntry>),
+        (status = 400, description = "Invalid metadata", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[put("/catalog/{kind}/{name}")]
+pub async fn put_catalog_entry(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<CatalogPath>,
+    body: web::Json<CatalogMetadata>,
+) -> Result<ApiResponse<CatalogEntry>, AppError> {
+    let name = path.name.trim();
+    if name.is_empty() {
+        return Err(AppError::field("name", "required", "name must not be empty"));
+    }
+    let metadata = body.into_inner().normalized();
+    metadata.validate().map_err(|message| AppError::field("body", "invalid", message))?;
+    let created = {
+        let connection = app_state.accounts.lock().unwrap();
+        upsert_entry(&connection, path.kind, name, &metadata).map_err(|e| AppError::internal("upsert_entry", e))?
+    };
+    info!("Catalog entry {} '{}' {} by {}", path.kind.as_str(), name, if created { "created" } else { "updated" }, claims.sub);
+    let entry = existing_entry(&app_state, &path)?;
+    Ok(ApiResponse::success(if created { "Created" } else { "Updated" }, Some(entry)))
+}
+
+#[utoipa::path(
+    tag = "catalog",
+    params(
+        ("kind" = String, Path, description = "`system` or `function` (plural accepted)"),
+        ("name" = String, Path, description = "System or function name"),
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, body = ApiResponse<serde_json::Value>),
+        (status = 404, description = "No entry", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[delete("/catalog/{kind}/{name}")]
+pub async fn delete_catalog_entry(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<CatalogPath>,
+) -> Result<ApiResponse<()>, AppError> {
+    let name = path.name.trim();
+    let deleted = {
+        let connection = app_state.accounts.lock().unwrap();
+        delete_entry(&connection, path.kind, name).map_err(|e| AppError::internal("delete_entry", e))?
+    };
+    if !deleted {
+        return Err(AppError::NotFound(format!("no catalog entry for {} '{}'", path.kind.as_str(), name)));
+    }
+    info!("Catalog entry {} '{}' deleted by {}", path.kind.as_str(), name, claims.sub);
+    Ok(ApiResponse::success("Deleted", None))
+}
+
+/// Parses the body as YAML or CSV according to its Content-Type.
+fn parse_catalog(req: &HttpRequest, body: &[u8]) -> Result<Vec<ParsedCatalogRecord>, AppError> {
+    let mime = req.mime_type().map_err(|e| AppError::field("body", "invalid_content_type", e.to_string()))?;
+    match mime.as_ref().map(|mime| mime.essence_str()) {
+        Some("application/yaml" | "application/x-yaml" | "text/yaml") => {
+            let text = std::str::from_utf8(body).map_err(|e| AppError::field("body", "invalid", e.to_string()))?;
+            parse_catalog_yaml(text).map_err(|e| AppError::field("body", "malformed_yaml", e))
+        }
+        Some("text/csv") => parse_catalog_csv(body).map_err(|e| AppError::field("body", "malformed_csv", e)),
+        other => Err(AppError::field(
+            "body",
+            "invalid_content_type",
+            format!(
+                "Content-Type {} is not supported; use application/yaml or text/csv",
+                other.unwrap_or("(none)")
+            ),
+        )),
+    }
+}
+
+/// Loads many entries at once from YAML (`systems` and `functions` lists
+/// of entries with a `name`) or CSV (`kind`, `name` and metadata columns,
+/// tags separated by `;`).
+#[utoipa::path(
+    tag = "catalog",
+    params(CatalogImportQuery),
+    request_body(
+        description = "YAML or CSV catalog, optionally with `Content-Encoding: gzip`",
+        content((String = "application/yaml"), (String = "text/csv")),
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Outcome of the import; `rejected` gives the reason per entry", body = ApiResponse<ImportReport>),
+        (status = 400, description = "Unreadable body or unsupported Content-Type", body = ApiResponse<serde_json::Value>),
+        (status = 413, description = "Body too large", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[post("/catalog/import")]
+pub async fn import_catalog(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    query: web::Query<CatalogImportQuery>,
+    payload: web::Payload,
+) -> Result<ApiResponse<ImportReport>, AppError> {
+    let body = read_body(&req, payload, app_state.ingest.max_body_bytes).await?;
+    let records = parse_catalog(&req, &body)?;
+    let CatalogImportQuery { on_duplicate, dry_run } = query.into_inner();
+    let report = {
+        let mut connection = app_state.accounts.lock().unwrap();
+        import_entries(&mut connection, records, on_duplicate, dry_run)
+            .map_err(|e| AppError::internal("import_entries", e))?
+    };
+    info!(
+        "Catalog import by {}{}: {} inserted, {} replaced, {} skipped, {} rejected",
+        claims.sub,
+        if dry_run { " (dry run)" } else { "" },
+        report.inserted,
+        report.replaced,
+        report.skipped,
+        report.rejected.len()
+    );
+    Ok(ApiResponse::success("Success", Some(report)))
+}
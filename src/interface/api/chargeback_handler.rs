@@ -0,0 +1,156 @@
+use crate::application::catalog_service::metadata_by_name;
+use crate::application::chargeback_service::{build_report, get_monthly_usage};
+use crate::application::error::AppError;
+use crate::domain::catalog::CatalogKind;
+use crate::domain::chargeback::{ChargeAmounts, ChargebackMonth, ChargebackReport, CostBasis};
+use crate::infrastructure::app_state::AppState;
+use crate::interface::dto::{ApiResponse, ChargebackQuery};
+use crate::interface::export::{
+    Cell, ExportFormat, ExportQuery, ExportTable, NDJSON_CONTENT_TYPE, file_response, ndjson_lines,
+};
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use chrono::Local;
+
+const AMOUNT_COLUMNS: [&str; 6] = ["work_total", "share", "usage_cost", "shared_cost", "minimum_adjustment", "total_cost"];
+
+fn amount_cells(amounts: &ChargeAmounts) -> [Cell; 6] {
+    [
+        amounts.work_total,
+        amounts.share,
+        amounts.usage_cost,
+        amounts.shared_cost,
+        amounts.minimum_adjustment,
+        amounts.total_cost,
+    ]
+    .map(Cell::Number)
+}
+
+/// The cost of the month: `cost` or `rate` of the request, else the
+/// configured one.
+fn cost_basis(app_state: &AppState, query: &ChargebackQuery, month: &ChargebackMonth) -> Result<CostBasis, AppError> {
+    for (name, value) in [("cost", query.cost), ("rate", query.rate)] {
+        if let Some(value) = value
+            && (!value.is_finite() || value < 0.0)
+        {
+            return Err(AppError::field(name, "invalid", format!("{} must be a non-negative number", name)));
+        }
+    }
+    match (query.cost, query.rate) {
+        (Some(_), Some(_)) => Err(AppError::field("rate", "conflict", "give cost or rate, not both")),
+        (Some(cost), None) => Ok(CostBasis::Fixed(cost)),
+        (None, Some(rate)) => Ok(CostBasis::Rate(rate)),
+        (None, None) => app_state.chargeback.basis(month).ok_or_else(|| {
+            AppError::field(
+                "cost",
+                "required",
+                format!(
+                    "no platform cost for {}: pass cost or rate, or set chargeback.monthly_cost or chargeback.rate_per_work",
+                    month
+                ),
+            )
+        }),
+    }
+}
+
+/// One row per consumer, system and function, whose amounts add up to
+/// the consumer's.
+fn detail_table(report: &ChargebackReport) -> ExportTable {
+    let mut columns = vec!["month", "consumer", "team", "system_name", "mq_function"];
+    columns.extend(AMOUNT_COLUMNS);
+    let rows = report
+        .consumers
+        .iter()
+        .flat_map(|consumer| {
+            let team = consumer.team.clone().unwrap_or_else(|| consumer.consumer.clone());
+            consumer.functions.iter().map(move |function| {
+                let mut row = vec![
+                    Cell::Text(report.month.clone()),
+                    Cell::Text(consumer.consumer.clone()),
+                    Cell::Text(team.clone()),
+                    Cell::Text(function.system_name.clone()),
+                    Cell::Text(function.mq_function.clone()),
+                ];
+                row.extend(amount_cells(&function.amounts));
+                row
+            })
+        })
+        .collect();
+    ExportTable { sheet: "functions".to_string(), columns, rows }
+}
+
+fn summary_table(report: &ChargebackReport) -> ExportTable {
+    let mut columns = vec!["month", "consumer", "team"];
+    columns.extend(AMOUNT_COLUMNS);
+    let rows = report
+        .consumers
+        .iter()
+        .map(|consumer| {
+            let mut row = vec![
+                Cell::Text(report.month.clone()),
+                Cell::Text(consumer.consumer.clone()),
+                Cell::Text(consumer.team.clone().unwrap_or_else(|| consumer.consumer.clone())),
+            ];
+            row.extend(amount_cells(&consumer.amounts));
+            row
+        })
+        .collect();
+    ExportTable { sheet: "consumers".to_string(), columns, rows }
+}
+
+/// Monthly cost allocation: the platform cost (or a rate per unit of
+/// work_total) split over teams or systems by their work_total, plus the
+/// configured shared-cost pools and minimum charge. CSV has one row per
+/// consumer, system and function; XLSX adds a sheet of consumers; NDJSON
+/// has one consumer per line.
+#[utoipa::path(
+    tag = "chargeback",
+    params(ChargebackQuery, ExportQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Charges per team or system with per-function detail", content(
+            (ApiResponse<ChargebackReport> = "application/json"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 400, description = "Invalid month, cost or rate, or no cost configured", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/chargeback")]
+pub async fn chargeback(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    query: web::Query<ChargebackQuery>,
+    export: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
+    let month = match &query.month {
+        Some(month) => ChargebackMonth::parse(month).map_err(|e| AppError::field("month", "invalid", e))?,
+        None => ChargebackMonth::previous(Local::now()),
+    };
+    let basis = cost_basis(&app_state, &query, &month)?;
+    let usage = app_state
+        .metrics
+        .with_db(&app_state.db, "get_monthly_usage", |connection| get_monthly_usage(connection, &month))
+        .map_err(|e| AppError::internal("get_monthly_usage", e))?;
+    let systems = {
+        let connection = app_state.accounts.lock().unwrap();
+        metadata_by_name(&connection, CatalogKind::System).map_err(|e| AppError::internal("metadata_by_name", e))?
+    };
+    let allocate_by = query.by.unwrap_or(app_state.chargeback.allocate_by);
+    let report = build_report(&month, &app_state.chargeback, basis, allocate_by, usage, &systems);
+
+    let filename = format!("chargeback_{}_{}", report.month, report.allocate_by.as_str());
+    match ExportFormat::negotiate(&req, &export) {
+        ExportFormat::Json => Ok(HttpResponse::from(ApiResponse::success("Success", Some(report)))),
+        ExportFormat::Ndjson => {
+            let body = ndjson_lines(report.consumers).map_err(|e| AppError::internal("chargeback", e))?;
+            Ok(HttpResponse::Ok().content_type(NDJSON_CONTENT_TYPE).body(body))
+        }
+        ExportFormat::Csv => {
+            file_response(ExportFormat::Csv, &filename, vec![detail_table(&report)], app_state.search.max_xlsx_rows)
+        }
+        ExportFormat::Xlsx => {
+            let tables = vec![summary_table(&report), detail_table(&report)];
+            file_response(ExportFormat::Xlsx, &filename, tables, app_state.search.max_xlsx_rows)
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::application::error::AppError;
+use crate::application::mq_log_usage_service::get_latest_date_time;
+use crate::domain::auth::Claims;
+use crate::infrastructure::app_state::AppState;
+use crate::interface::dto::{ApiResponse, DatasetResponse};
+use actix_web::{get, web};
+
+/// Datasets the user may read, by name. Any `/api/v1` route reads one of
+/// them when prefixed with `/datasets/{dataset}` or given `?dataset=`.
+#[utoipa::path(
+    tag = "datasets",
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = ApiResponse<Vec<DatasetResponse>>))
+)]
+#[get("/datasets")]
+pub async fn list_datasets(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+) -> Result<ApiResponse<Vec<DatasetResponse>>, AppError> {
+    let default = &app_state.datasets.default_dataset().name;
+    let mut datasets = Vec::new();
+    for dataset in app_state.datasets.iter().filter(|dataset| dataset.allows(&claims.sub)) {
+        let latest_data = app_state
+            .metrics
+            .with_db(&dataset.db, "get_latest_date_time", get_latest_date_time)
+            .map_err(|e| AppError::internal("get_latest_date_time", e))?;
+        datasets.push(DatasetResponse {
+            name: dataset.name.clone(),
+            label: dataset.label.clone(),
+            default: &dataset.name == default,
+            latest_data,
+        });
+    }
+    Ok(ApiResponse::success("Success", Some(datasets)))
+}
@@ -0,0 +1,395 @@
+//! Grafana JSON (SimpleJSON) datasource protocol, mounted at
+//! `/api/v1/grafana`.
+//!
+//! Targets are `<measure>:<function>[:<system>]` where the measure is `tps`
+//! or `work`, `*` as function means every function, `*` as system breaks
+//! the result down into one series per system, and `{a,b}` (Grafana's
+//! multi-value variable format) expands to one series per value.
+
+use crate::application::error::AppError;
+use crate::application::mq_log_usage_service::{
+    UsageFilter, get_all_system_name_list, get_bucketed_usage, get_mq_function_list, get_system_name_list,
+};
+use crate::domain::model::{QueueDimensions, UsageBucket, UsageDimension};
+use crate::infrastructure::app_state::AppState;
+use crate::interface::dto::{
+    ApiResponse, GrafanaAdhocFilter, GrafanaAnnotation, GrafanaAnnotationRequest, GrafanaQueryRequest,
+    GrafanaRange, GrafanaSearchRequest, GrafanaTagKey, GrafanaTagValue, GrafanaTagValuesRequest,
+    GrafanaTimeSeries,
+};
+use actix_web::{HttpResponse, ResponseError, post, routes, web};
+use chrono::{Duration, Local};
+use log::debug;
+use std::collections::BTreeSet;
+
+/// Points an annotation query is bucketed into when looking for peaks.
+const ANNOTATION_POINTS: i64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Measure {
+    Tps,
+    Work,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SystemSelector {
+    Sum,
+    One(String),
+    Each,
+}
+
+/// Buckets of one named series.
+type NamedSeries = (String, Vec<UsageBucket>);
+
+#[derive(Debug, Clone)]
+struct SeriesQuery {
+    measure: Measure,
+    mq_function: Option<String>,
+    system: SystemSelector,
+}
+
+impl SeriesQuery {
+    fn name(&self, system_name: Option<&str>) -> String {
+        let measure = match self.measure {
+            Measure::Tps => "tps",
+            Measure::Work => "work",
+        };
+        let function = self.mq_function.as_deref().unwrap_or("*");
+        let system = match (&self.system, system_name) {
+            (_, Some(system_name)) => Some(system_name),
+            (SystemSelector::One(system_name), None) => Some(system_name.as_str()),
+            _ => None,
+        };
+        match system {
+            Some(system) => format!("{}:{}:{}", measure, function, system),
+            None => format!("{}:{}", measure, function),
+        }
+    }
+
+    /// Ad hoc filters narrow a target: `mq_function` applies to `*`
+    /// targets, `system_name` restricts every target to one system.
+    fn apply_filters(&mut self, filters: &[GrafanaAdhocFilter]) {
+        for filter in filters {
+            if filter.operator != "=" {
+                debug!("Ignoring ad hoc filter {} {} {}", filter.key, filter.operator, filter.value);
+                continue;
+            }
+            match filter.key.as_str() {
+                "mq_function" if self.mq_function.is_none() => self.mq_function = Some(filter.value.clone()),
+                "system_name" => self.system = SystemSelector::One(filter.value.clone()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// `{a,b}` -> `[a, b]`, anything else -> itself.
+fn expand(value: &str) -> Vec<String> {
+    match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        Some(values) => values.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect(),
+        None => vec![value.trim().to_string()],
+    }
+}
+
+fn parse_target(target: &str) -> Result<Vec<SeriesQuery>, String> {
+    let mut parts = target.trim().splitn(3, ':');
+    let measure = match parts.next().unwrap_or_default() {
+        "tps" => Measure::Tps,
+        "work" => Measure::Work,
+        other => return Err(format!("unknown measure '{}' in target '{}' (expected tps or work)", other, target)),
+    };
+    let functions = expand(parts.next().unwrap_or("*"));
+    let systems = match parts.next() {
+        None => vec![SystemSelector::Sum],
+        Some(system) => expand(system)
+            .into_iter()
+            .map(|s| if s == "*" { SystemSelector::Each } else { SystemSelector::One(s) })
+            .collect(),
+    };
+
+    let mut queries = Vec::new();
+    for function in &functions {
+        for system in &systems {
+            queries.push(SeriesQuery {
+                measure,
+                mq_function: (function != "*" && !function.is_empty()).then(|| function.clone()),
+                system: system.clone(),
+            });
+        }
+    }
+    Ok(queries)
+}
+
+/// Bucket width honoring both Grafana's interval and `maxDataPoints`,
+/// rounded up to whole minutes (the resolution of `mq_data`).
+fn bucket_secs(range: &GrafanaRange, interval_ms: Option<i64>, max_data_points: Option<i64>) -> i64 {
+    let span_ms = (range.to - range.from).num_milliseconds().max(0);
+    let by_points = max_data_points
+        .filter(|points| *points > 0)
+        .map(|points| (span_ms + points - 1) / points)
+        .unwrap_or(0);
+    let bucket_ms = interval_ms.unwrap_or(0).max(by_points).max(60_000);
+    (bucket_ms + 59_999) / 60_000 * 60
+}
+
+/// Runs one series query and groups the buckets into named series.
+fn load_series(
+    app_state: &AppState,
+    query: &SeriesQuery,
+    range: &GrafanaRange,
+    bucket_secs: i64,
+) -> Result<Vec<NamedSeries>, Box<dyn std::error::Error>> {
+    let system_name = match &query.system {
+        SystemSelector::One(system_name) => Some(system_name.as_str()),
+        _ => None,
+    };
+    let buckets = app_state.metrics.with_db(&app_state.db, "get_bucketed_usage", |connection| {
+        get_bucketed_usage(
+            connection,
+            &range.from.with_timezone(&Local),
+            &range.to.with_timezone(&Local),
+            bucket_secs,
+            &UsageFilter {
+                mq_function: query.mq_function.as_deref(),
+                system_name,
+                queue: &QueueDimensions::default(),
+            },
+            (query.system == SystemSelector::Each).then_some(UsageDimension::System),
+        )
+    })?;
+
+    let mut series: Vec<NamedSeries> = Vec::new();
+    for bucket in buckets {
+        let name = query.name(bucket.group.as_deref());
+        match series.last_mut() {
+            Some((last, points)) if *last == name => points.push(bucket),
+            _ => series.push((name, vec![bucket])),
+        }
+    }
+    if series.is_empty() && query.system != SystemSelector::Each {
+        series.push((query.name(None), Vec::new()));
+    }
+    Ok(series)
+}
+
+/// The range limits of the search routes: `from` not after `to` and no
+/// longer than `search.max_range_days`.
+fn check_range(app_state: &AppState, range: &GrafanaRange) -> Result<(), String> {
+    let max_range_days = app_state.search.max_range_days;
+    if range.from > range.to {
+        return Err("range.from must not be after range.to".to_string());
+    }
+    if range.to - range.from > Duration::days(max_range_days as i64) {
+        return Err(format!("the range must not exceed {} days", max_range_days));
+    }
+    Ok(())
+}
+
+/// Adds the points of `series` to `total`, refusing more than
+/// `search.max_rows` points in one response.
+fn count_points(app_state: &AppState, total: &mut usize, series: &[NamedSeries]) -> Result<(), String> {
+    *total += series.iter().map(|(_, buckets)| buckets.len()).sum::<usize>();
+    if *total > app_state.search.max_rows {
+        return Err(format!(
+            "the query returns more than {} points; narrow the range or the targets",
+            app_state.search.max_rows
+        ));
+    }
+    Ok(())
+}
+
+fn bad_request(message: &str) -> HttpResponse {
+    AppError::invalid(message).error_response()
+}
+
+fn internal_error(operation: &str, e: Box<dyn std::error::Error>) -> HttpResponse {
+    AppError::internal(operation, e).error_response()
+}
+
+/// Connection test of the datasource.
+#[utoipa::path(get, path = "/grafana", tag = "grafana", security(("bearer_auth" = [])), responses((status = 200, body = String, content_type = "text/plain")))]
+#[routes]
+#[get("/grafana")]
+#[get("/grafana/")]
+pub async fn grafana_test() -> HttpResponse {
+    HttpResponse::Ok().body("OK")
+}
+
+/// Metric and template variable lookup. `""` lists example targets,
+/// `functions` the functions, `systems` every system and
+/// `systems:<function>` the systems of a function.
+#[utoipa::path(
+    tag = "grafana",
+    request_body = GrafanaSearchRequest,
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = Vec<String>), (status = 400, body = ApiResponse<serde_json::Value>))
+)]
+#[post("/grafana/search")]
+pub async fn grafana_search(app_state: web::Data<AppState>, body: web::Json<GrafanaSearchRequest>) -> HttpResponse {
+    let target = body.target.trim();
+    let result = match target.split_once(':') {
+        _ if target.is_empty() => app_state
+            .metrics
+            .with_db(&app_state.db, "get_mq_function_list", get_mq_function_list)
+            .map(|functions| {
+                let mut targets = vec!["tps:*".to_string(), "work:*".to_string(), "tps:*:*".to_string()];
+                for function in functions {
+                    targets.push(format!("tps:{}", function));
+                    targets.push(format!("tps:{}:*", function));
+                    targets.push(format!("work:{}", function));
+                }
+                targets
+            }),
+        _ if target == "functions" => {
+            app_state.metrics.with_db(&app_state.db, "get_mq_function_list", get_mq_function_list)
+        }
+        _ if target == "systems" => {
+            app_state.metrics.with_db(&app_state.db, "get_all_system_name_list", get_all_system_name_list)
+        }
+        Some(("systems", functions)) => {
+            app_state.metrics.with_db(&app_state.db, "get_system_name_list", |connection| {
+                let mut systems = BTreeSet::new();
+                for function in expand(functions) {
+                    systems.extend(get_system_name_list(connection, &function)?);
+                }
+                Ok(systems.into_iter().collect::<Vec<_>>())
+            })
+        }
+        _ => {
+            return bad_request(&format!(
+                "unknown search '{}' (expected '', functions, systems or systems:<function>)",
+                target
+            ));
+        }
+    };
+    match result {
+        Ok(values) => HttpResponse::Ok().json(values),
+        Err(e) => internal_error("grafana_search", e),
+    }
+}
+
+#[utoipa::path(
+    tag = "grafana",
+    request_body = GrafanaQueryRequest,
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = Vec<GrafanaTimeSeries>), (status = 400, body = ApiResponse<serde_json::Value>))
+)]
+#[post("/grafana/query")]
+pub async fn grafana_query(app_state: web::Data<AppState>, body: web::Json<GrafanaQueryRequest>) -> HttpResponse {
+    if let Err(e) = check_range(&app_state, &body.range) {
+        return bad_request(&e);
+    }
+    let bucket_secs = bucket_secs(&body.range, body.interval_ms, body.max_data_points);
+
+    let (mut response, mut points) = (Vec::new(), 0);
+    for target in body.targets.iter().filter(|t| !t.hide && !t.target.trim().is_empty()) {
+        let queries = match parse_target(&target.target) {
+            Ok(queries) => queries,
+            Err(e) => return bad_request(&format!("{} (refId {})", e, target.ref_id)),
+        };
+        for mut query in queries {
+            query.apply_filters(&body.adhoc_filters);
+            let series = match load_series(&app_state, &query, &body.range, bucket_secs) {
+                Ok(series) => series,
+                Err(e) => return internal_error("grafana_query", e),
+            };
+            if let Err(e) = count_points(&app_state, &mut points, &series) {
+                return bad_request(&e);
+            }
+            for (name, buckets) in series {
+                let datapoints = buckets
+                    .iter()
+                    .map(|bucket| {
+                        let value = match query.measure {
+                            Measure::Tps => bucket.trans_per_sec,
+                            Measure::Work => bucket.work_total,
+                        };
+                        (value, bucket.bucket_start * 1000)
+                    })
+                    .collect();
+                response.push(GrafanaTimeSeries { target: name, datapoints });
+            }
+        }
+    }
+    HttpResponse::Ok().json(response)
+}
+
+/// Marks the peak TPS of every series matched by the annotation query (a
+/// target, e.g. `tps:PAY:*`) in the dashboard's time range.
+#[utoipa::path(
+    tag = "grafana",
+    request_body = GrafanaAnnotationRequest,
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = Vec<GrafanaAnnotation>), (status = 400, body = ApiResponse<serde_json::Value>))
+)]
+#[post("/grafana/annotations")]
+pub async fn grafana_annotations(
+    app_state: web::Data<AppState>,
+    body: web::Json<GrafanaAnnotationRequest>,
+) -> HttpResponse {
+    let queries = match check_range(&app_state, &body.range).and_then(|()| parse_target(&body.annotation.query)) {
+        Ok(queries) => queries,
+        Err(e) => return bad_request(&e),
+    };
+    let bucket_secs = bucket_secs(&body.range, None, Some(ANNOTATION_POINTS));
+
+    let (mut annotations, mut points) = (Vec::new(), 0);
+    for query in queries {
+        let series = match load_series(&app_state, &query, &body.range, bucket_secs) {
+            Ok(series) => series,
+            Err(e) => return internal_error("grafana_annotations", e),
+        };
+        if let Err(e) = count_points(&app_state, &mut points, &series) {
+            return bad_request(&e);
+        }
+        for (name, buckets) in series {
+            let Some(peak) = buckets.iter().max_by(|a, b| a.trans_per_sec.total_cmp(&b.trans_per_sec)) else {
+                continue;
+            };
+            let mut tags = vec!["peak".to_string()];
+            tags.extend(query.mq_function.clone());
+            tags.extend(peak.group.clone());
+            annotations.push(GrafanaAnnotation {
+                annotation: body.annotation.name.clone(),
+                time: peak.bucket_start * 1000,
+                title: format!("Peak {}", name),
+                text: format!("{:.2} TPS, work {:.0}", peak.trans_per_sec, peak.work_total),
+                tags,
+            });
+        }
+    }
+    HttpResponse::Ok().json(annotations)
+}
+
+#[utoipa::path(tag = "grafana", security(("bearer_auth" = [])), responses((status = 200, body = Vec<GrafanaTagKey>)))]
+#[post("/grafana/tag-keys")]
+pub async fn grafana_tag_keys() -> HttpResponse {
+    HttpResponse::Ok().json([
+        GrafanaTagKey { kind: "string", text: "mq_function" },
+        GrafanaTagKey { kind: "string", text: "system_name" },
+    ])
+}
+
+#[utoipa::path(
+    tag = "grafana",
+    request_body = GrafanaTagValuesRequest,
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = Vec<GrafanaTagValue>))
+)]
+#[post("/grafana/tag-values")]
+pub async fn grafana_tag_values(
+    app_state: web::Data<AppState>,
+    body: web::Json<GrafanaTagValuesRequest>,
+) -> HttpResponse {
+    let result = match body.key.as_str() {
+        "mq_function" => app_state.metrics.with_db(&app_state.db, "get_mq_function_list", get_mq_function_list),
+        "system_name" => {
+            app_state.metrics.with_db(&app_state.db, "get_all_system_name_list", get_all_system_name_list)
+        }
+        _ => Ok(Vec::new()),
+    };
+    match result {
+        Ok(values) => HttpResponse::Ok().json(values.into_iter().map(|text| GrafanaTagValue { text }).collect::<Vec<_>>()),
+        Err(e) => internal_error("grafana_tag_values", e),
+    }
+}
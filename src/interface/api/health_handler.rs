@@ -0,0 +1,177 @@
+use crate::application::{maintenance_service, mq_log_usage_service};
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::cache::redis_store::RedisHealth;
+use crate::infrastructure::schema;
+use crate::interface::dto::{ApiResponse, ReadinessResponse, VersionResponse};
+use actix_web::http::StatusCode;
+use actix_web::rt::time::sleep;
+use actix_web::{HttpResponse, get, web};
+use chrono::DateTime;
+use log::error;
+use rusqlite::Connection;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+
+/// How long readiness waits for a dataset connection that another request
+/// holds before reporting the dataset busy.
+const BUSY_GRACE: Duration = Duration::from_millis(250);
+
+/// The connection, unless it stays held for longer than `grace`, as it is
+/// while this process runs a large import or a migration.
+async fn lock_within(db: &Mutex<Connection>, grace: Duration) -> Option<MutexGuard<'_, Connection>> {
+    let deadline = Instant::now() + grace;
+    loop {
+        match db.try_lock() {
+            Ok(connection) => return Some(connection),
+            Err(TryLockError::Poisoned(e)) => return Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => sleep(Duration::from_millis(10)).await,
+            Err(TryLockError::WouldBlock) => return None,
+        }
+    }
+}
+
+async fn redis_state(app_state: &AppState) -> RedisHealth {
+    app_state.cache.redis_health().await.unwrap_or(RedisHealth {
+        configured: false,
+        reachable: false,
+        breaker: None,
+    })
+}
+
+/// Redis is optional, so this always answers 200 and reports the circuit
+/// breaker state in the body rather than failing the probe.
+#[utoipa::path(tag = "health", responses((status = 200, body = ApiResponse<RedisHealth>)))]
+#[get("/health/redis")]
+pub async fn redis_health(app_state: web::Data<AppState>) -> impl actix_web::Responder {
+    ApiResponse::<RedisHealth>::success("Success", Some(redis_state(&app_state).await))
+}
+
+/// Liveness: answers as long as the process can serve requests.
+#[utoipa::path(tag = "health", responses((status = 200, body = ApiResponse<serde_json::Value>)))]
+#[get("/healthz")]
+pub async fn healthz() -> impl actix_web::Responder {
+    ApiResponse::<()>::success("OK", None)
+}
+
+/// Readiness: 503 unless SQLite answers, the schema is the version this
+/// build expects and no migration or large import is running, in every
+/// dataset. `schema_version` is the oldest one found. A dataset whose
+/// connection stays busy is reported as such instead of waiting for it.
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready", body = ApiResponse<ReadinessResponse>),
+        (status = 503, description = "Not ready; `problems` lists why", body = ApiResponse<ReadinessResponse>),
+    )
+)]
+#[get("/readyz")]
+pub async fn readyz(app_state: web::Data<AppState>) -> HttpResponse {
+    let mut problems = Vec::new();
+    let expected = schema::latest_version();
+    let (mut database, mut schema_version, mut maintenance) = (true, None, Vec::new());
+    let several = app_state.datasets.iter().count() > 1;
+    for dataset in app_state.datasets.iter() {
+        let mut problem = |message: String| {
+            problems.push(if several { format!("dataset '{}': {}", dataset.name, message) } else { message })
+        };
+        let Some(connection) = lock_within(&dataset.db, BUSY_GRACE).await else {
+            problem("database busy with a long-running write".to_string());
+            continue;
+        };
+        match connection.query_row("SELECT 1", [], |_| Ok(())) {
+            Ok(()) => {
+                let current = schema::schema_version(&connection)
+                    .map_err(|e| problem(format!("cannot read schema version: {}", e)))
+                    .ok();
+                if let Some(current) = current {
+                    if current != expected {
+                        problem(format!("schema version {} (expected {})", current, expected));
+                    }
+                    schema_version = Some(schema_version.map_or(current, |oldest: usize| oldest.min(current)));
+                }
+                let markers = maintenance_service::active(&connection)
+                    .map_err(|e| problem(format!("cannot read maintenance markers: {}", e)))
+                    .unwrap_or_default();
+                for marker in &markers {
+                    problem(format!("{} running since {}", marker.operation, marker.started_at.to_rfc3339()));
+                }
+                maintenance.extend(markers);
+            }
+            Err(e) => {
+                error!("Readiness check: dataset {} unreachable: {}", dataset.name, e);
+                problem(format!("database unreachable: {}", e));
+                database = false;
+            }
+        }
+    }
+
+    let ready = problems.is_empty();
+    let response = ApiResponse {
+        success: ready,
+        message: if ready { "Ready" } else { "Not ready" }.to_string(),
+        error: None,
+        status_code: if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE },
+        data: Some(ReadinessResponse {
+            ready,
+            database,
+            schema_version,
+            expected_schema_version: expected,
+            maintenance,
+            redis: redis_state(&app_state).await,
+            problems,
+        }),
+    };
+    HttpResponse::from(response)
+}
+
+#[utoipa::path(tag = "health", responses((status = 200, body = ApiResponse<VersionResponse>)))]
+#[get("/version")]
+pub async fn version(app_state: web::Data<AppState>) -> impl actix_web::Responder {
+    let latest_data = {
+        let connection = app_state.db.lock().unwrap();
+        mq_log_usage_service::get_latest_date_time(&connection).unwrap_or_else(|e| {
+            error!("Failed to read the latest data timestamp: {}", e);
+            None
+        })
+    };
+    let build_time = env!("MQV_BUILD_TIME")
+        .parse()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0));
+    ApiResponse::success(
+        "Success",
+        Some(VersionResponse {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: env!("MQV_GIT_HASH").to_string(),
+            build_time,
+            latest_data,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn held_connection_is_reported_busy() {
+        let db = std::sync::Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+        let (held_tx, held_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let holder = {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                let _connection = db.lock().unwrap();
+                held_tx.send(()).unwrap();
+                let _ = release_rx.recv();
+            })
+        };
+        held_rx.recv().unwrap();
+        let started = Instant::now();
+        assert!(lock_within(&db, Duration::from_millis(30)).await.is_none());
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        assert!(lock_within(&db, Duration::ZERO).await.is_some());
+    }
+}
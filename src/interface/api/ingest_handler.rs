@@ -0,0 +1,251 @@
+use crate::application::error::AppError;
+use crate::application::import_service::import_usage;
+use crate::application::maintenance_service::{self, LARGE_IMPORT_ROWS};
+use crate::domain::auth::Claims;
+use crate::domain::import::ImportReport;
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::parsers::json_usage::{parse_usage_json, parse_usage_ndjson};
+use crate::interface::dto::{ApiResponse, IngestQuery};
+use actix_web::dev::Decompress;
+use actix_web::{HttpMessage, HttpRequest, post, web};
+use futures_util::StreamExt;
+use log::info;
+
+enum IngestFormat {
+    Json,
+    Ndjson,
+}
+
+/// JSON unless the Content-Type names newline-delimited JSON.
+fn ingest_format(req: &HttpRequest) -> Result<IngestFormat, AppError> {
+    let mime = req.mime_type().map_err(|e| AppError::field("body", "invalid_content_type", e.to_string()))?;
+    match mime.as_ref().map(|mime| mime.essence_str()) {
+        None | Some("application/json") => Ok(IngestFormat::Json),
+        Some("application/x-ndjson" | "application/jsonl") => Ok(IngestFormat::Ndjson),
+        Some(other) => Err(AppError::field(
+            "body",
+            "invalid_content_type",
+            format!("Content-Type {} is not supported; use application/json or application/x-ndjson", other),
+        )),
+    }
+}
+
+/// Reads the body, decoded per its Content-Encoding, refusing more than
+/// `limit` decoded bytes.
+pub(crate) async fn read_body(req: &HttpRequest, payload: web::Payload, limit: usize) -> Result<web::BytesMut, AppError> {
+    let mut payload = Decompress::from_headers(payload, req.headers());
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| AppError::field("body", "invalid", format!("cannot read the body: {}", e)))?;
+        if body.len() + chunk.len() > limit {
+            return Err(AppError::PayloadTooLarge(format!("The body exceeds the limit of {} bytes", limit)));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[utoipa::path(
+    tag = "mq",
+    params(IngestQuery),
+    request_body(
+        description = "Usage records as a JSON array (or `{\"records\": [...]}`) or as NDJSON, optionally with `Content-Encoding: gzip`",
+        content(
+            (Vec<crate::infrastructure::parsers::json_usage::UsageRecordInput> = "application/json"),
+            (crate::infrastructure::parsers::json_usage::UsageRecordInput = "application/x-ndjson"),
+        ),
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Outcome of the batch; `rejected` gives the reason per record", body = ApiResponse<ImportReport>),
+        (status = 400, description = "Unreadable body or unsupported Content-Type", body = ApiResponse<serde_json::Value>),
+        (status = 413, description = "Too many bytes or records", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[post("/ingest")]
+pub async fn ingest(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    query: web::Query<IngestQuery>,
+    payload: web::Payload,
+) -> Result<ApiResponse<ImportReport>, AppError> {
+    let format = ingest_format(&req)?;
+    let limits = &app_state.ingest;
+    let body = read_body(&req, payload, limits.max_body_bytes).await?;
+    let records = match format {
+        IngestFormat::Json => parse_usage_json(&body),
+        IngestFormat::Ndjson => parse_usage_ndjson(&body),
+    }
+    .map_err(|e| AppError::field("body", "malformed_json", e))?;
+    if records.len() > limits.max_records {
+        return Err(AppError::PayloadTooLarge(format!(
+            "{} records exceed the limit of {} per request",
+            records.len(),
+            limits.max_records
+        )));
+    }
+
+    let IngestQuery { on_duplicate, dry_run } = query.into_inner();
+    let state = app_state.clone();
+    let user = claims.sub.clone();
+    let report = web::block(move || {
+        let report = {
+            let mut connection = state.db.lock().unwrap();
+            if !dry_run && records.len() >= LARGE_IMPORT_ROWS {
+                let name = format!("ingest by {}", user);
+                maintenance_service::run(&mut connection, &name, |connection| {
+                    import_usage(connection, records, on_duplicate, dry_run)
+                })
+            } else {
+                import_usage(&mut connection, records, on_duplicate, dry_run)
+            }
+        };
+        if let Ok(report) = &report
+            && !dry_run
+            && report.changed_rows() > 0
+        {
+            state.data_watch.refresh(&state.db);
+        }
+        report
+    })
+    .await
+    .map_err(|e| AppError::internal("ingest", e))?
+    .map_err(|e| AppError::internal("import_usage", e))?;
+
+    if !dry_run && report.changed_rows() > 0 {
+        app_state.cache.bump_generation().await;
+    }
+    info!(
+        "Ingest by {}{}: {} inserted, {} replaced, {} skipped, {} rejected",
+        claims.sub,
+        if dry_run { " (dry run)" } else { "" },
+        report.inserted,
+        report.replaced,
+        report.skipped,
+        report.rejected.len()
+    );
+    Ok(ApiResponse::<ImportReport>::success("Success", Some(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::config::AppConfig;
+    use actix_web::dev::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+    use actix_web::{App, test};
+    use chrono::{Duration, NaiveDate};
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn state(dir: &tempfile::TempDir, max_body_bytes: usize, max_records: usize) -> AppState {
+        let mut config = AppConfig::default();
+        config.database.path = dir.path().join("mq.db");
+        config.ingest.max_body_bytes = max_body_bytes;
+        config.ingest.max_records = max_records;
+        AppState::for_tests(&config)
+    }
+
+    /// `count` NDJSON records a minute apart.
+    fn ndjson(count: usize) -> String {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        (0..count)
+            .map(|i| {
+                let date_time = start + Duration::minutes(i as i64);
+                format!(
+                    "{{\"date_time\": \"{}\", \"system_name\": \"SYS-A\", \"mq_function\": \"PAY\", \"work_total\": 60}}\n",
+                    date_time.format("%Y-%m-%d %H:%M")
+                )
+            })
+            .collect()
+    }
+
+    fn gzip(body: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn count(state: &AppState, sql: &str) -> i64 {
+        state.db.lock().unwrap().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    async fn post(state: &AppState, body: Vec<u8>, gzipped: bool) -> (StatusCode, serde_json::Value) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .wrap_fn(|req, srv| {
+                    req.extensions_mut().insert(Claims { sub: "alice".to_string(), exp: usize::MAX });
+                    srv.call(req)
+                })
+                .service(ingest),
+        )
+        .await;
+        let mut req = test::TestRequest::post().uri("/ingest").insert_header((CONTENT_TYPE, "application/x-ndjson"));
+        if gzipped {
+            req = req.insert_header((CONTENT_ENCODING, "gzip"));
+        }
+        let resp = test::call_service(&app, req.set_payload(body).to_request()).await;
+        let status = resp.status();
+        (status, test::read_body_json(resp).await)
+    }
+
+    #[actix_web::test]
+    async fn gzipped_ndjson_is_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 64 * 1024, 100);
+        let (status, body) = post(&state, gzip(&ndjson(3)), true).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["data"]["inserted"], 3);
+        assert_eq!(count(&state, "SELECT COUNT(*) FROM mq_data"), 3);
+    }
+
+    #[actix_web::test]
+    async fn the_limit_applies_to_the_decompressed_body() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 4096, 100_000);
+        let body = gzip(&ndjson(200));
+        assert!(body.len() < 4096);
+        let (status, _) = post(&state, body, true).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(count(&state, "SELECT COUNT(*) FROM mq_data"), 0);
+    }
+
+    #[actix_web::test]
+    async fn too_many_records_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 64 * 1024, 2);
+        let (status, _) = post(&state, ndjson(3).into_bytes(), false).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(count(&state, "SELECT COUNT(*) FROM mq_data"), 0);
+    }
+
+    #[actix_web::test]
+    async fn large_batches_mark_the_database_busy() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 16 * 1024 * 1024, 100_000);
+        state
+            .db
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE seen (operation TEXT);
+                 CREATE TRIGGER note AFTER INSERT ON maintenance BEGIN INSERT INTO seen VALUES (NEW.operation); END",
+            )
+            .unwrap();
+
+        let (status, _) = post(&state, ndjson(LARGE_IMPORT_ROWS - 1).into_bytes(), false).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(count(&state, "SELECT COUNT(*) FROM seen"), 0);
+
+        state.db.lock().unwrap().execute_batch("DELETE FROM mq_data").unwrap();
+        let (status, body) = post(&state, gzip(&ndjson(LARGE_IMPORT_ROWS)), true).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["inserted"], LARGE_IMPORT_ROWS);
+        assert_eq!(count(&state, "SELECT COUNT(*) FROM seen WHERE operation = 'ingest by alice'"), 1);
+        assert_eq!(count(&state, "SELECT COUNT(*) FROM maintenance"), 0);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::application::error::{AppError, FieldError};
+use crate::application::mq_log_usage_service::{get_mq_log_usage_inserted, get_system_name_list};
+use crate::domain::auth::Claims;
+use crate::infrastructure::app_state::AppState;
+use crate::interface::dto::{FunctionPath, LiveQuery, SearchMqLogResponse};
+use actix_web::rt::time::timeout;
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use chrono::Local;
+use futures_util::{Stream, stream};
+use log::{debug, error};
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::watch;
+
+const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+/// Reconnection delay suggested to `EventSource` clients.
+const RETRY_MS: u64 = 3000;
+
+/// One server-sent event; `data` must be a single line.
+fn sse_event(event: &str, id: Option<i64>, data: &str) -> web::Bytes {
+    let id = id.map(|id| format!("id: {}\n", id)).unwrap_or_default();
+    web::Bytes::from(format!("{}event: {}\ndata: {}\n\n", id, event, data))
+}
+
+/// New rows of one function (and system), sent as they land. The event
+/// id is the newest row id covered, so a reconnecting client resumes
+/// right after the rows it has seen.
+struct LiveStream {
+    app_state: web::Data<AppState>,
+    mq_function: String,
+    system_name: Option<String>,
+    after_id: i64,
+    receiver: watch::Receiver<i64>,
+    heartbeat: Duration,
+    started: bool,
+}
+
+impl LiveStream {
+    /// Reads the next rows up to row `up_to_id` and advances `after_id`;
+    /// `None` when none of them match the filter.
+    async fn next_batch(&mut self, up_to_id: i64) -> Result<Option<web::Bytes>, String> {
+        let app_state = self.app_state.clone();
+        let (mq_function, system_name) = (self.mq_function.clone(), self.system_name.clone());
+        let (after_id, batch_rows) = (self.after_id, self.app_state.live.batch_rows);
+        let rows = web::block(move || {
+            app_state
+                .metrics
+                .with_db(&app_state.db, "get_mq_log_usage_inserted", |connection| {
+                    get_mq_log_usage_inserted(
+                        connection,
+                        &mq_function,
+                        system_name.as_deref(),
+                        after_id,
+                        up_to_id,
+                        batch_rows,
+                    )
+                })
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        self.after_id = match rows.last() {
+            Some((id, _)) if rows.len() == batch_rows => *id,
+            _ => up_to_id,
+        };
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        let rows: Vec<SearchMqLogResponse> = rows.into_iter().map(|(_, row)| row.into()).collect();
+        let data = serde_json::to_string(&rows).map_err(|e| e.to_string())?;
+        Ok(Some(sse_event("usage", Some(self.after_id), &data)))
+    }
+}
+
+fn live_events(state: LiveStream) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
+    stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        if !state.started {
+            state.started = true;
+            return Some((Ok(web::Bytes::from(format!("retry: {}\n\n", RETRY_MS))), Some(state)));
+        }
+        loop {
+            let up_to_id = *state.receiver.borrow_and_update();
+            if up_to_id < state.after_id {
+                // The newest rows were deleted; their ids may be used again.
+                state.after_id = up_to_id;
+            }
+            if up_to_id > state.after_id {
+                match state.next_batch(up_to_id).await {
+                    Ok(Some(event)) => return Some((Ok(event), Some(state))),
+                    Ok(None) => {}
+                    Err(e) => {
+                        let error = AppError::internal("live stream", e);
+                        error!("{}", error);
+                        let data = json!({ "code": error.code(), "message": error.public_message() });
+                        return Some((Ok(sse_event("error", None, &data.to_string())), None));
+                    }
+                }
+                continue;
+            }
+            match timeout(state.heartbeat, state.receiver.changed()).await {
+                Ok(Ok(())) => {}
+                // The watch is gone: the server is shutting down.
+                Ok(Err(_)) => return None,
+                Err(_) => {
+                    let data = json!({ "time": Local::now().to_rfc3339() });
+                    return Some((Ok(sse_event("heartbeat", None, &data.to_string())), Some(state)));
+                }
+            }
+        }
+    })
+}
+
+/// Server-sent events with the per-minute rows of a function as they are
+/// written. `usage` events carry a JSON array of rows, `heartbeat` events
+/// keep idle connections open. Without a `Last-Event-ID` only rows written
+/// after connecting are sent. `EventSource` clients may authenticate with
+/// `?access_token=`.
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function"), LiveQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Event stream of `usage`, `heartbeat` and, before closing on a failure, `error` events", content(
+            (String = "text/event-stream"),
+        )),
+        (status = 400, description = "Unknown system or invalid `Last-Event-ID`", body = crate::interface::dto::ApiResponse<serde_json::Value>),
+        (status = 404, description = "Unknown function", body = crate::interface::dto::ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/live")]
+pub async fn mq_live(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+    query: web::Query<LiveQuery>,
+) -> Result<HttpResponse, AppError> {
+    let mq_function = path.function.trim().to_string();
+    let system_name = query.system.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .or(query.last_event_id.as_deref());
+    let after_id = match last_event_id.map(|id| id.trim().parse::<i64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => return Err(AppError::field("last_event_id", "invalid", "Last-Event-ID must be an event id")),
+        None => None,
+    };
+
+    let systems = app_state
+        .metrics
+        .with_db(&app_state.db, "get_system_name_list", |connection| {
+            get_system_name_list(connection, &mq_function)
+        })
+        .map_err(|e| AppError::internal("get_system_name_list", e))?;
+    if systems.is_empty() {
+        return Err(AppError::NotFound(format!("unknown mq_function '{}'", mq_function)));
+    }
+    if let Some(system_name) = &system_name
+        && !systems.contains(system_name)
+    {
+        let message = format!("unknown system '{}' for mq_function '{}'", system_name, mq_function);
+        return Err(AppError::fields(vec![FieldError::new("system", "unknown_system", message)]));
+    }
+
+    let after_id = after_id.unwrap_or_else(|| app_state.data_watch.latest());
+    debug!(
+        "mq_live: user: {}, mq_function: {}, system_name: {:?}, after_id: {}",
+        claims.sub, mq_function, system_name, after_id
+    );
+    let state = LiveStream {
+        receiver: app_state.data_watch.subscribe(),
+        heartbeat: Duration::from_secs(app_state.live.heartbeat_secs),
+        app_state: app_state.clone(),
+        mq_function,
+        system_name,
+        after_id,
+        started: false,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(EVENT_STREAM_CONTENT_TYPE)
+        .insert_header(("Cache-Control", "no-cache"))
+        // Keeps reverse proxies such as nginx from buffering the stream.
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(live_events(state)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::config::AppConfig;
+    use actix_web::body::MessageBody;
+    use actix_web::dev::Service;
+    use actix_web::http::StatusCode;
+    use actix_web::{App, HttpMessage, test};
+    use chrono::TimeZone;
+    use futures_util::StreamExt;
+    use rusqlite::params;
+    use std::pin::pin;
+
+    fn state(dir: &tempfile::TempDir, batch_rows: usize) -> AppState {
+        let mut config = AppConfig::default();
+        config.database.path = dir.path().join("mq.db");
+        config.live.batch_rows = batch_rows;
+        AppState::for_tests(&config)
+    }
+
+    /// Minute rows, in the order given; returns their ids.
+    fn insert(state: &AppState, rows: &[(&str, u32, &str)]) -> Vec<i64> {
+        let db = state.db.lock().unwrap();
+        let ids = rows
+            .iter()
+            .map(|(function, minute, system_name)| {
+                let date_time = Local.with_ymd_and_hms(2026, 10, 19, 8, *minute, 0).unwrap();
+                db.execute(
+                    "INSERT INTO mq_data (date_time, date, minute, system_name, mq_function, work_total, \
+                     trans_per_sec, granularity) VALUES (?1, '', '', ?2, ?3, 60, 1, 'minute')",
+                    params![date_time.to_rfc3339(), system_name, function],
+                )
+                .unwrap();
+                db.last_insert_rowid()
+            })
+            .collect();
+        drop(db);
+        state.data_watch.refresh(&state.db);
+        ids
+    }
+
+    /// (event, id, systems of the rows) of an SSE chunk.
+    fn parse(chunk: &[u8]) -> (String, Option<i64>, Vec<String>) {
+        let text = std::str::from_utf8(chunk).unwrap();
+        let field = |name: &str| text.lines().find_map(|line| line.strip_prefix(name)).map(str::to_string);
+        let systems = match field("data: ") {
+            Some(data) if field("event: ").as_deref() == Some("usage") => {
+                let rows: Vec<serde_json::Value> = serde_json::from_str(&data).unwrap();
+                rows.iter().map(|row| row["system_name"].as_str().unwrap().to_string()).collect()
+            }
+            _ => Vec::new(),
+        };
+        (field("event: ").unwrap_or_default(), field("id: ").map(|id| id.parse().unwrap()), systems)
+    }
+
+    async fn live(state: &AppState, last_event_id: Option<&str>) -> actix_web::dev::ServiceResponse {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .wrap_fn(|req, srv| {
+                    req.extensions_mut().insert(Claims { sub: "alice".to_string(), exp: usize::MAX });
+                    srv.call(req)
+                })
+                .service(mq_live),
+        )
+        .await;
+        let mut req = test::TestRequest::get().uri("/mq/PAY/live");
+        if let Some(id) = last_event_id {
+            req = req.insert_header(("Last-Event-ID", id));
+        }
+        test::call_service(&app, req.to_request()).await
+    }
+
+    /// The next `count` events of a stream, after its `retry:` preamble.
+    async fn events(resp: actix_web::dev::ServiceResponse, count: usize) -> Vec<(String, Option<i64>, Vec<String>)> {
+        let mut body = pin!(resp.into_body());
+        let mut next = async || std::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await.unwrap().unwrap();
+        assert_eq!(next().await, web::Bytes::from(format!("retry: {}\n\n", RETRY_MS)));
+        let mut events = Vec::new();
+        for _ in 0..count {
+            events.push(parse(&next().await));
+        }
+        events
+    }
+
+    #[actix_web::test]
+    async fn resuming_sends_only_later_rows_of_the_function() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 100);
+        let ids = insert(
+            &state,
+            &[("PAY", 1, "SYS-A"), ("PAY", 1, "SYS-B"), ("CARDS", 2, "SYS-X"), ("PAY", 2, "SYS-C"), ("PAY", 0, "SYS-D")],
+        );
+
+        let resp = live(&state, Some(&ids[1].to_string())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        // Insertion order, not time order, and the id of the newest row.
+        let expected = ("usage".to_string(), Some(ids[4]), vec!["SYS-C".to_string(), "SYS-D".to_string()]);
+        assert_eq!(events(resp, 1).await, [expected]);
+    }
+
+    #[actix_web::test]
+    async fn backlogs_are_sent_in_batches_each_with_its_last_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 2);
+        let ids = insert(&state, &[("PAY", 1, "SYS-A"), ("PAY", 2, "SYS-B"), ("PAY", 3, "SYS-C")]);
+
+        let events = events(live(&state, Some("0")).await, 2).await;
+        assert_eq!(
+            events,
+            [
+                ("usage".to_string(), Some(ids[1]), vec!["SYS-A".to_string(), "SYS-B".to_string()]),
+                ("usage".to_string(), Some(ids[2]), vec!["SYS-C".to_string()]),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn without_an_id_only_new_rows_are_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 100);
+        insert(&state, &[("PAY", 1, "SYS-A")]);
+
+        let resp = live(&state, None).await;
+        let writer = state.clone();
+        actix_web::rt::spawn(async move { insert(&writer, &[("PAY", 2, "SYS-B")]) });
+        let events = events(resp, 1).await;
+        assert_eq!(events[0].2, ["SYS-B"]);
+    }
+
+    #[actix_web::test]
+    async fn invalid_ids_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir, 100);
+        insert(&state, &[("PAY", 1, "SYS-A")]);
+        assert_eq!(live(&state, Some("seven")).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn idle_streams_get_heartbeats() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = web::Data::new(state(&dir, 100));
+        let stream = LiveStream {
+            receiver: state.data_watch.subscribe(),
+            heartbeat: Duration::from_millis(10),
+            app_state: state.clone(),
+            mq_function: "PAY".to_string(),
+            system_name: None,
+            after_id: state.data_watch.latest(),
+            started: true,
+        };
+        let events: Vec<_> = live_events(stream).take(2).map(|event| parse(&event.unwrap())).collect().await;
+        assert!(events.iter().all(|(event, id, _)| event == "heartbeat" && id.is_none()), "{:?}", events);
+    }
+}
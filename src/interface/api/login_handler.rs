@@ -1,18 +1,31 @@
 use crate::application::auth_service;
+use crate::application::error::AppError;
 use crate::infrastructure::app_state::AppState;
 use crate::interface::dto::{ApiResponse, LoginRequest, LoginResponse};
 use actix_web::{post, web};
 
+#[utoipa::path(
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Token for `Authorization: Bearer`", body = ApiResponse<LoginResponse>),
+        (status = 400, description = "Malformed request body", body = ApiResponse<serde_json::Value>),
+        (status = 401, description = "Invalid credentials", body = ApiResponse<serde_json::Value>),
+    )
+)]
 #[post("/auth/login")]
 pub async fn login(
     app_state: web::Data<AppState>,
     req: web::Json<LoginRequest>,
-) -> impl actix_web::Responder {
+) -> Result<ApiResponse<LoginResponse>, AppError> {
     match auth_service::login_user(req.into_inner(), &app_state) {
-        Some(resp) => ApiResponse::<LoginResponse>::success("Success", Some(resp)),
-        None => ApiResponse::<LoginResponse>::error(
-            "Invalid credentials",
-            actix_web::http::StatusCode::UNAUTHORIZED,
-        ),
+        Some(resp) => {
+            app_state.metrics.logins.inc(&["success"]);
+            Ok(ApiResponse::<LoginResponse>::success("Success", Some(resp)))
+        }
+        None => {
+            app_state.metrics.logins.inc(&["failure"]);
+            Err(AppError::Unauthorized("Invalid credentials".to_string()))
+        }
     }
 }
@@ -0,0 +1,82 @@
+use crate::application::error::AppError;
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::circuit_breaker::BreakerState;
+use crate::infrastructure::metrics::{PREFIX, write_header, write_sample};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{HttpRequest, HttpResponse, ResponseError, get, web};
+
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Checks `Authorization: Bearer <token>` when a metrics token is configured.
+pub fn authorized(req: &HttpRequest, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|given| given.trim() == token)
+}
+
+/// Appends a single unlabeled sample with its header.
+fn write_single(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let name = format!("{}_{}", PREFIX, name);
+    write_header(out, &name, kind, help);
+    write_sample(out, &name, &[], value);
+}
+
+/// Metrics about the viewer itself in the Prometheus text format. Protected
+/// by `metrics.token` instead of the user login when configured.
+#[utoipa::path(
+    tag = "metrics",
+    security((), ("metrics_token" = [])),
+    responses(
+        (status = 200, description = "Prometheus text format", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or wrong metrics token"),
+    )
+)]
+#[get("/metrics")]
+pub async fn metrics(req: HttpRequest, app_state: web::Data<AppState>) -> HttpResponse {
+    if !authorized(&req, app_state.metrics_token.as_deref()) {
+        return AppError::Unauthorized("Missing or wrong metrics token".to_string()).error_response();
+    }
+
+    let mut out = String::new();
+    let build_info = format!("{}_build_info", PREFIX);
+    write_header(&mut out, &build_info, "gauge", "Version of the running build.");
+    write_sample(
+        &mut out,
+        &build_info,
+        &[("version", env!("CARGO_PKG_VERSION")), ("git_hash", env!("MQV_GIT_HASH"))],
+        1.0,
+    );
+
+    app_state.metrics.encode(&mut out);
+
+    let stats = app_state.cache.stats().await;
+    let hits = format!("{}_cache_tier_hits_total", PREFIX);
+    write_header(&mut out, &hits, "counter", "Response cache hits by tier.");
+    write_sample(&mut out, &hits, &[("tier", "memory")], stats.memory_hits as f64);
+    write_sample(&mut out, &hits, &[("tier", "redis")], stats.redis_hits as f64);
+    write_single(&mut out, "cache_misses_total", "counter", "Lookups that missed every cache tier.", stats.misses as f64);
+    write_single(&mut out, "cache_redis_errors_total", "counter", "Failed or timed out Redis commands.", stats.errors as f64);
+    write_single(&mut out, "cache_generation", "gauge", "Current cache generation.", stats.generation as f64);
+    if let Some(memory) = &stats.memory {
+        write_single(&mut out, "cache_memory_entries", "gauge", "Entries in the in-process cache.", memory.entries as f64);
+        write_single(&mut out, "cache_memory_bytes", "gauge", "Bytes held by the in-process cache.", memory.bytes as f64);
+        write_single(&mut out, "cache_memory_evictions_total", "counter", "Entries evicted from the in-process cache.", memory.evictions as f64);
+    }
+    if let Some(breaker) = &stats.breaker {
+        let open = if breaker.state == BreakerState::Closed { 0.0 } else { 1.0 };
+        write_single(&mut out, "cache_redis_breaker_open", "gauge", "1 while the Redis circuit breaker is open or half-open.", open);
+    }
+    let retention_running = format!("{}_retention_running", PREFIX);
+    write_header(&mut out, &retention_running, "gauge", "1 while a retention run is in progress.");
+    for dataset in app_state.datasets.iter() {
+        let running = if dataset.retention.is_running() { 1.0 } else { 0.0 };
+        write_sample(&mut out, &retention_running, &[("dataset", dataset.name.as_str())], running);
+    }
+
+    HttpResponse::Ok().content_type(PROMETHEUS_CONTENT_TYPE).body(out)
+}
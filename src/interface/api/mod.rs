@@ -1,2 +1,14 @@
+pub(crate) mod admin_handler;
+pub(crate) mod catalog_handler;
+pub(crate) mod chargeback_handler;
+pub(crate) mod dataset_handler;
+pub(crate) mod grafana_handler;
+pub(crate) mod health_handler;
+pub(crate) mod ingest_handler;
+pub(crate) mod live_handler;
 pub(crate) mod login_handler;
+pub(crate) mod metrics_handler;
 pub(crate) mod mq_log_handler;
+pub(crate) mod mq_metrics_handler;
+pub(crate) mod redirect_handler;
+pub(crate) mod ws_handler;
@@ -1,182 +1,1512 @@
-use crate::application::mq_log_usage_service::{get_all_mq_log_tps_summary, get_mq_function_list, get_mq_log_tps_summary, get_mq_log_usage, get_system_name_list};
+use crate::application::mq_log_usage_service::{UsageFilter, get_all_mq_log_tps_summary, get_all_mq_log_tps_summary_by_function, get_bucketed_usage, get_dimension_values, get_mq_function_list, get_mq_log_tps_summary, get_mq_log_usage, get_mq_log_usage_page, get_system_name_list};
+use crate::application::error::{AppError, FieldError};
+use crate::application::catalog_service::metadata_by_name;
+use crate::application::queue_depth_service::get_queue_depth;
+use crate::domain::catalog::{CatalogKind, CatalogMetadata};
+use crate::domain::model::{DepthMetrics, MQLogUsage, QueueDimensions, SearchCursor, UsageDimension};
+use crate::domain::queue_depth::QueueDepth;
+use crate::domain::auth::Claims;
 use crate::infrastructure::app_state::AppState;
-use crate::interface::dto::{ApiResponse, SearchMqLogRequest, SearchMqLogResponse};
+use crate::infrastructure::cache::CacheRoute;
+use crate::infrastructure::metrics::RowCount;
+use crate::interface::dto::{ApiResponse, CatalogItem, CatalogNames, CompareQuery, DatasetSeries, FunctionPath, MetadataQuery, PageQuery, SearchMqLogPage, SearchMqLogRequest, SearchMqLogResponse, TagQuery, UsageQuery};
+use crate::interface::export::{
+    Cell, ExportFormat, ExportQuery, ExportTable, NDJSON_CONTENT_TYPE, file_response, ndjson_lines,
+};
 use actix_web::http::StatusCode;
-use actix_web::{get, post, web};
+use actix_web::http::header::{
+    self, CacheControl, CacheDirective, ETag, EntityTag, HeaderName, HeaderValue, IfNoneMatch, TryIntoHeaderPair,
+};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, get, post, web};
+use futures_util::{Stream, stream};
+use chrono::{Duration, Local, TimeZone};
+use sha2::{Digest, Sha256};
 use log::{debug, error};
-use redis;
-use serde_json;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 
 // Helper functions to reduce code duplication
 
 // Helper functions for common patterns
-fn handle_service_result<T>(
-    result: Result<Vec<T>, Box<dyn std::error::Error>>,
-    operation_name: &str,
-) -> ApiResponse<Vec<SearchMqLogResponse>>
+fn rows_response<T>(rows: Vec<T>) -> ApiResponse<Vec<SearchMqLogResponse>>
 where
     T: Into<SearchMqLogResponse>,
 {
-    match result {
-        Ok(data) => {
-            let response_data = data.into_iter().map(|item| item.into()).collect::<Vec<_>>();
-            ApiResponse::<Vec<SearchMqLogResponse>>::success("Success", Some(response_data))
-        }
-        Err(e) => {
-            let message = format!("Error in {}: {}", operation_name, e);
-            error!("{}", message);
-            ApiResponse::<Vec<SearchMqLogResponse>>::error(&message, StatusCode::INTERNAL_SERVER_ERROR)
+    let response_data = rows.into_iter().map(|item| item.into()).collect::<Vec<_>>();
+    ApiResponse::<Vec<SearchMqLogResponse>>::success("Success", Some(response_data))
+}
+
+/// Cache key parameters of a normalized request: instants compared in UTC
+/// and fields the route ignores left out.
+#[derive(Serialize)]
+struct SearchCacheParams<'a> {
+    from_ms: i64,
+    to_ms: i64,
+    mq_function: Option<&'a str>,
+    system_name: Option<&'a str>,
+    queue: Option<&'a QueueDimensions>,
+    group_by: Option<UsageDimension>,
+}
+
+impl<'a> SearchCacheParams<'a> {
+    fn new(request: &'a SearchMqLogRequest, with_function: bool) -> Self {
+        Self {
+            from_ms: request.from_datetime.timestamp_millis(),
+            to_ms: request.to_datetime.timestamp_millis(),
+            mq_function: with_function.then_some(request.mq_function_name.as_str()),
+            system_name: extract_system_name_option(request).filter(|_| with_function),
+            queue: with_function.then_some(&request.queue).filter(|queue| !queue.is_empty()),
+            group_by: request.group_by.filter(|_| with_function),
         }
     }
 }
 
-fn handle_string_list_result(
-    result: Result<Vec<String>, Box<dyn std::error::Error>>,
-    operation_name: &str,
-) -> ApiResponse<Vec<String>> {
-    match result {
-        Ok(data) => ApiResponse::<Vec<String>>::success("Success", Some(data)),
-        Err(e) => {
-            let message = format!("Error in {}: {}", operation_name, e);
-            error!("{}", message);
-            ApiResponse::<Vec<String>>::error(&message, StatusCode::INTERNAL_SERVER_ERROR)
-        }
+fn extract_system_name_option(request: &SearchMqLogRequest) -> Option<&str> {
+    request.system_name.as_deref()
+}
+
+/// Rows of the request's function, system and queue.
+fn usage_filter(request: &SearchMqLogRequest) -> UsageFilter<'_> {
+    UsageFilter {
+        mq_function: Some(&request.mq_function_name),
+        system_name: extract_system_name_option(request),
+        queue: &request.queue,
     }
 }
 
-fn try_get_from_cache(redis_client: &redis::Client, cache_key: &str) -> Option<Vec<String>> {
-    if let Ok(mut con) = redis_client.get_connection() {
-        let cached: redis::RedisResult<String> = redis::cmd("GET").arg(cache_key).query(&mut con);
-        if let Ok(json_str) = cached {
-            if let Ok(result) = serde_json::from_str::<Vec<String>>(&json_str) {
-                debug!("Cache hit: {}", cache_key);
-                return Some(result);
-            } else {
-                error!("Failed to deserialize cached data for key: {}", cache_key);
-            }
-        } else {
-            debug!("Cache miss for key: {}", cache_key);
+/// Text cell of an optional value, empty when absent.
+fn optional_cell<T: ToString>(value: Option<T>) -> Cell {
+    Cell::Text(value.map(|v| v.to_string()).unwrap_or_default())
+}
+
+/// Serves `route` from the response cache when possible, otherwise runs
+/// the service function `load` (named `function` in metrics) against the
+/// database and caches a successful result. The flag is `true` when the
+/// result came from the cache. Entries are kept per dataset and user.
+async fn cached_or_load<T, P>(
+    app_state: &AppState,
+    route: CacheRoute,
+    claims: &Claims,
+    params: &P,
+    function: &str,
+    load: impl FnOnce(&rusqlite::Connection) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<(T, bool), AppError>
+where
+    T: Serialize + DeserializeOwned + RowCount,
+    P: Serialize,
+{
+    let scope = format!("{}/{}", app_state.dataset, claims.sub);
+    if let Some(cached) = app_state.cache.get::<T, P>(route, &scope, params).await {
+        app_state.metrics.cache_requests.inc(&[route.as_str(), "hit"]);
+        return Ok((cached, true));
+    }
+    app_state.metrics.cache_requests.inc(&[route.as_str(), "miss"]);
+
+    let data = app_state
+        .metrics
+        .with_db(&app_state.db, function, load)
+        .map_err(|e| AppError::internal(function, e))?;
+    app_state.cache.set(route, &scope, params, &data).await;
+    Ok((data, false))
+}
+
+/// Field checks of a search request, including that the function exists
+/// when the route needs one.
+async fn validate_search(
+    app_state: &AppState,
+    claims: &Claims,
+    request: &SearchMqLogRequest,
+    with_function: bool,
+) -> Result<(), AppError> {
+    let max_range = Duration::days(app_state.search.max_range_days as i64);
+    let mut fields = request.field_errors(max_range, with_function);
+    let function = request.mq_function_name.trim();
+    if with_function && !fields.iter().any(|f| f.field == "mq_function_name") {
+        let (functions, _) =
+            cached_or_load(app_state, CacheRoute::Functions, claims, &(), "get_mq_function_list", get_mq_function_list)
+                .await?;
+        if !functions.iter().any(|f: &String| f == function) {
+            let message = format!("unknown mq_function '{}'", function);
+            fields.push(FieldError::new("mq_function_name", "unknown_function", message));
         }
-    } else {
-        error!("Redis connection failed");
     }
-    None
+    if fields.is_empty() { Ok(()) } else { Err(AppError::fields(fields)) }
 }
 
-fn try_set_cache(redis_client: &redis::Client, cache_key: &str, data: &Vec<String>) {
-    if let Ok(mut con) = redis_client.get_connection() {
-        let json_data = serde_json::to_string(data).unwrap_or_default();
-        let _: Result<(), redis::RedisError> = redis::cmd("SET")
-            .arg(cache_key)
-            .arg(json_data)
-            .query(&mut con);
+fn mark_cached<T: Serialize>(mut response: ApiResponse<T>, from_cache: bool) -> ApiResponse<T> {
+    if from_cache && response.success {
+        response.message = "Success (cache)".to_string();
     }
+    response
 }
 
-fn extract_system_name_option(request: &SearchMqLogRequest) -> Option<&str> {
-    request.system_name.as_ref().map(|s| s.as_str())
+/// Label used in exports for values summed over every system or function.
+const ALL_LABEL: &str = "ALL";
+
+fn export_filename(prefix: &str, request: &SearchMqLogRequest, mq_function: Option<&str>) -> String {
+    let mut name = prefix.to_string();
+    if let Some(mq_function) = mq_function {
+        name.push('_');
+        name.push_str(mq_function);
+    }
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!(
+        "{}_{}_{}",
+        name,
+        request.from_datetime.format("%Y%m%d%H%M"),
+        request.to_datetime.format("%Y%m%d%H%M")
+    )
+}
+
+fn search_table(mq_function: &str, rows: Vec<MQLogUsage>) -> ExportTable {
+    ExportTable {
+        sheet: mq_function.to_string(),
+        columns: vec![
+            "date_time",
+            "system_name",
+            "mq_function",
+            "work_total",
+            "trans_per_sec",
+            "queue_manager",
+            "queue_name",
+            "channel",
+            "cur_depth",
+            "max_depth",
+            "oldest_msg_age_secs",
+        ],
+        rows: rows
+            .into_iter()
+            .map(|row| {
+                vec![
+                    Cell::DateTime(row.date_time),
+                    Cell::Text(row.system_name),
+                    Cell::Text(row.mq_function),
+                    Cell::Number(row.work_total),
+                    Cell::Number(row.trans_per_sec),
+                    optional_cell(row.queue.queue_manager),
+                    optional_cell(row.queue.queue_name),
+                    optional_cell(row.queue.channel),
+                    optional_cell(row.depth.cur_depth),
+                    optional_cell(row.depth.max_depth),
+                    optional_cell(row.depth.oldest_msg_age_secs),
+                ]
+            })
+            .collect(),
+    }
 }
 
+/// TPS summary rows, labelled with the function and system they sum over
+/// and, when grouped by a queue dimension, with its value.
+fn summary_table(
+    mq_function: &str,
+    system_name: &str,
+    group_by: Option<UsageDimension>,
+    rows: Vec<MQLogUsage>,
+) -> ExportTable {
+    let queue_group = group_by.filter(|dimension| *dimension != UsageDimension::System);
+    let mut columns = vec!["date_time", "mq_function", "system_name"];
+    columns.extend(queue_group.map(|dimension| dimension.column()));
+    columns.push("trans_per_sec");
+    ExportTable {
+        sheet: mq_function.to_string(),
+        columns,
+        rows: rows
+            .into_iter()
+            .map(|row| {
+                let system_name = match group_by {
+                    Some(UsageDimension::System) => row.system_name.clone(),
+                    _ => system_name.to_string(),
+                };
+                let mut cells = vec![
+                    Cell::DateTime(row.date_time),
+                    Cell::Text(mq_function.to_string()),
+                    Cell::Text(system_name),
+                ];
+                cells.extend(queue_group.map(|dimension| optional_cell(dimension.value(&row))));
+                cells.push(Cell::Number(row.trans_per_sec));
+                cells
+            })
+            .collect(),
+    }
+}
+
+fn export_response(
+    app_state: &AppState,
+    format: ExportFormat,
+    filename: &str,
+    tables: Vec<ExportTable>,
+) -> Result<HttpResponse, AppError> {
+    file_response(format, filename, tables, app_state.search.max_xlsx_rows)
+}
+
+/// NDJSON of an already loaded result, for the summary routes whose
+/// results are small enough not to need streaming.
+fn ndjson_response(rows: Vec<MQLogUsage>, operation_name: &str) -> Result<HttpResponse, AppError> {
+    let body = ndjson_lines(rows.into_iter().map(SearchMqLogResponse::from))
+        .map_err(|e| AppError::internal(operation_name, e))?;
+    Ok(HttpResponse::Ok().content_type(NDJSON_CONTENT_TYPE).body(body))
+}
+
+/// Names filtered to the catalog entries of `kind` carrying every tag
+/// asked for, each with its metadata when asked for. Applied after the
+/// cache, so catalog edits show at once.
+fn with_catalog(
+    app_state: &AppState,
+    names: Vec<String>,
+    kind: CatalogKind,
+    tags: &TagQuery,
+    metadata: &MetadataQuery,
+) -> Result<CatalogNames, AppError> {
+    let tags = tags.tag_list();
+    if tags.is_empty() && !metadata.metadata {
+        return Ok(CatalogNames::Names(names));
+    }
+    let catalog = {
+        let connection = app_state.accounts.lock().unwrap();
+        metadata_by_name(&connection, kind).map_err(|e| AppError::internal("metadata_by_name", e))?
+    };
+    Ok(catalog_names(names, &catalog, &tags, metadata.metadata))
+}
+
+/// `names` carrying every one of `tags` in `catalog`, with their metadata
+/// when `with_metadata`. Names missing from the catalog only pass without
+/// tags, and then have no metadata.
+fn catalog_names(
+    names: Vec<String>,
+    catalog: &HashMap<String, CatalogMetadata>,
+    tags: &[String],
+    with_metadata: bool,
+) -> CatalogNames {
+    let names = names
+        .into_iter()
+        .filter(|name| tags.is_empty() || catalog.get(name).is_some_and(|entry| entry.has_tags(tags)));
+    if with_metadata {
+        CatalogNames::WithMetadata(
+            names.map(|name| CatalogItem { metadata: catalog.get(&name).cloned(), name }).collect(),
+        )
+    } else {
+        CatalogNames::Names(names.collect())
+    }
+}
+
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function"), TagQuery, MetadataQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Systems reporting the function; with `metadata=true` objects of name and catalog metadata", body = ApiResponse<CatalogNames>),
+        (status = 404, description = "Unknown function", body = ApiResponse<serde_json::Value>),
+    )
+)]
 #[get("/mq/{function}/systems")]
 pub async fn mq_function_systems(
     app_state: web::Data<AppState>,
-    path: web::Path<(String,)>
-) -> impl actix_web::Responder {
-    let connection = app_state.db.lock().unwrap();
-    let function = &path.0;
-    let result = get_system_name_list(&connection, function).await;
-    handle_string_list_result(result, "get_system_name_list")
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+    tags: web::Query<TagQuery>,
+    metadata: web::Query<MetadataQuery>,
+) -> Result<ApiResponse<CatalogNames>, AppError> {
+    let function = path.function.trim();
+    let (systems, from_cache) =
+        cached_or_load(&app_state, CacheRoute::Systems, &claims, &function, "get_system_name_list", |connection| {
+            get_system_name_list(connection, function)
+        })
+        .await?;
+    if systems.is_empty() {
+        return Err(AppError::NotFound(format!("unknown mq_function '{}'", function)));
+    }
+    let systems = with_catalog(&app_state, systems, CatalogKind::System, &tags, &metadata)?;
+    Ok(mark_cached(ApiResponse::success("Success", Some(systems)), from_cache))
 }
 
-#[get("/mq/functions")]
-pub async fn mq_functions(app_state: web::Data<AppState>) -> impl actix_web::Responder {
-    let cache_key = "mq_functions";
-    
-    // Try to get from cache first
-    if let Some(ref redis_client) = app_state.redis_client {
-        if let Some(cached_result) = try_get_from_cache(redis_client, cache_key) {
-            return ApiResponse::<Vec<String>>::success("Success (cache)", Some(cached_result));
-        }
-    }
-    
-    // If not in cache, query database
-    let connection = app_state.db.lock().unwrap();
-    let result = get_mq_function_list(&connection).await;
-    
-    match &result {
-        Ok(data) => {
-            // Cache the result if Redis is available
-            if let Some(ref redis_client) = app_state.redis_client {
-                try_set_cache(redis_client, cache_key, data);
-            }
-        }
-        Err(_) => {} // Error will be handled by handle_string_list_result
+/// Distinct values of a queue dimension of a known function; empty when
+/// its rows do not carry the dimension.
+async fn dimension_values(
+    app_state: &AppState,
+    claims: &Claims,
+    function: &str,
+    dimension: UsageDimension,
+) -> Result<ApiResponse<Vec<String>>, AppError> {
+    let (functions, _) =
+        cached_or_load(app_state, CacheRoute::Functions, claims, &(), "get_mq_function_list", get_mq_function_list)
+            .await?;
+    if !functions.iter().any(|f: &String| f == function) {
+        return Err(AppError::NotFound(format!("unknown mq_function '{}'", function)));
     }
-    
-    handle_string_list_result(result, "get_mq_function_list")
+    let params = (function, dimension);
+    let (values, from_cache) =
+        cached_or_load(app_state, CacheRoute::DimensionValues, claims, &params, "get_dimension_values", |connection| {
+            get_dimension_values(connection, function, dimension)
+        })
+        .await?;
+    Ok(mark_cached(ApiResponse::success("Success", Some(values)), from_cache))
+}
+
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Queue managers reporting the function", body = ApiResponse<Vec<String>>),
+        (status = 404, description = "Unknown function", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/queue_managers")]
+pub async fn mq_function_queue_managers(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+) -> Result<ApiResponse<Vec<String>>, AppError> {
+    dimension_values(&app_state, &claims, path.function.trim(), UsageDimension::QueueManager).await
+}
+
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Queues reporting the function", body = ApiResponse<Vec<String>>),
+        (status = 404, description = "Unknown function", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/queues")]
+pub async fn mq_function_queues(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+) -> Result<ApiResponse<Vec<String>>, AppError> {
+    dimension_values(&app_state, &claims, path.function.trim(), UsageDimension::Queue).await
+}
+
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Channels reporting the function", body = ApiResponse<Vec<String>>),
+        (status = 404, description = "Unknown function", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/channels")]
+pub async fn mq_function_channels(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+) -> Result<ApiResponse<Vec<String>>, AppError> {
+    dimension_values(&app_state, &claims, path.function.trim(), UsageDimension::Channel).await
 }
 
+#[utoipa::path(
+    tag = "mq",
+    params(TagQuery, MetadataQuery),
+    security(("bearer_auth" = [])),
+    responses((status = 200, description = "Known functions; with `metadata=true` objects of name and catalog metadata", body = ApiResponse<CatalogNames>))
+)]
+#[get("/mq/functions")]
+pub async fn mq_functions(
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    tags: web::Query<TagQuery>,
+    metadata: web::Query<MetadataQuery>,
+) -> Result<ApiResponse<CatalogNames>, AppError> {
+    let (functions, from_cache) =
+        cached_or_load(&app_state, CacheRoute::Functions, &claims, &(), "get_mq_function_list", get_mq_function_list)
+        .await?;
+    let functions = with_catalog(&app_state, functions, CatalogKind::Function, &tags, &metadata)?;
+    Ok(mark_cached(ApiResponse::success("Success", Some(functions)), from_cache))
+}
+
+#[utoipa::path(
+    tag = "mq",
+    request_body = SearchMqLogRequest,
+    params(ExportQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "TPS per minute summed over systems (or of one system)", content(
+            (ApiResponse<Vec<SearchMqLogResponse>> = "application/json"),
+            (SearchMqLogResponse = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 400, description = "Invalid request; `error.fields` names the fields", body = ApiResponse<serde_json::Value>),
+    )
+)]
 #[post("/mq/tps/summary")]
 pub async fn mq_tps_summary(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
     data: web::Json<SearchMqLogRequest>,
-) -> impl actix_web::Responder {
-    let connection = app_state.db.lock().unwrap();
+    export: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
     debug!(
         "mq_tps_summary: start_date: {}, end_date: {}, mq_function: {}",
         data.from_datetime, data.to_datetime, data.mq_function_name
     );
+    let data = data.into_inner().normalized();
+    validate_search(&app_state, &claims, &data, true).await?;
+    tps_summary_response(&app_state, &claims, &data, ExportFormat::negotiate(&req, &export)).await
+}
 
-    let system_name = extract_system_name_option(&data);
-    let result = get_mq_log_tps_summary(
-        &connection,
-        &data.from_datetime,
-        &data.to_datetime,
-        &data.mq_function_name,
-        system_name,
-    ).await;
+async fn tps_summary_response(
+    app_state: &AppState,
+    claims: &Claims,
+    data: &SearchMqLogRequest,
+    format: ExportFormat,
+) -> Result<HttpResponse, AppError> {
+    let params = SearchCacheParams::new(data, true);
+    let (rows, from_cache) =
+        cached_or_load(app_state, CacheRoute::TpsSummary, claims, &params, "get_mq_log_tps_summary", |connection| {
+            get_mq_log_tps_summary(connection, &data.from_datetime, &data.to_datetime, &usage_filter(data), data.group_by)
+        })
+        .await?;
 
-    handle_service_result(result, "mq_tps_summary")
+    match format {
+        ExportFormat::Json => Ok(HttpResponse::from(mark_cached(rows_response(rows), from_cache))),
+        ExportFormat::Ndjson => ndjson_response(rows, "mq_tps_summary"),
+        ExportFormat::Csv | ExportFormat::Xlsx => {
+            let function = data.mq_function_name.as_str();
+            let system_name = extract_system_name_option(data).unwrap_or(ALL_LABEL);
+            let filename = export_filename("mq_tps_summary", data, Some(function));
+            let tables = vec![summary_table(function, system_name, data.group_by, rows)];
+            export_response(app_state, format, &filename, tables)
+        }
+    }
 }
 
+/// Exports add one table per function after the total over all functions.
+#[utoipa::path(
+    tag = "mq",
+    request_body = SearchMqLogRequest,
+    params(ExportQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "TPS per minute summed over every function; `mq_function_name` is ignored", content(
+            (ApiResponse<Vec<SearchMqLogResponse>> = "application/json"),
+            (SearchMqLogResponse = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 400, description = "Invalid request; `error.fields` names the fields", body = ApiResponse<serde_json::Value>),
+    )
+)]
 #[post("/mq/tps/all_summary")]
 pub async fn all_mq_tps_summary(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
     data: web::Json<SearchMqLogRequest>,
-) -> impl actix_web::Responder {
-    let connection = app_state.db.lock().unwrap();
+    export: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
     debug!(
         "all_mq_tps_summary: start_date: {}, end_date: {}",
         data.from_datetime, data.to_datetime
     );
+    let data = data.into_inner().normalized();
+    validate_search(&app_state, &claims, &data, false).await?;
+    all_tps_summary_response(&app_state, &claims, &data, ExportFormat::negotiate(&req, &export)).await
+}
 
-    let result = get_all_mq_log_tps_summary(
-        &connection,
-        &data.from_datetime,
-        &data.to_datetime,
-    ).await;
+async fn all_tps_summary_response(
+    app_state: &AppState,
+    claims: &Claims,
+    data: &SearchMqLogRequest,
+    format: ExportFormat,
+) -> Result<HttpResponse, AppError> {
+    let params = SearchCacheParams::new(data, false);
+    let (total, from_cache) =
+        cached_or_load(app_state, CacheRoute::AllTpsSummary, claims, &params, "get_all_mq_log_tps_summary", |connection| {
+            get_all_mq_log_tps_summary(connection, &data.from_datetime, &data.to_datetime)
+        })
+        .await?;
 
-    handle_service_result(result, "all_mq_tps_summary")
+    match format {
+        ExportFormat::Json => Ok(HttpResponse::from(mark_cached(rows_response(total), from_cache))),
+        ExportFormat::Ndjson => ndjson_response(total, "all_mq_tps_summary"),
+        ExportFormat::Csv | ExportFormat::Xlsx => {
+            let function = "get_all_mq_log_tps_summary_by_function";
+            let by_function = app_state
+                .metrics
+                .with_db(&app_state.db, function, |connection| {
+                    get_all_mq_log_tps_summary_by_function(connection, &data.from_datetime, &data.to_datetime)
+                })
+                .map_err(|e| AppError::internal(function, e))?;
+            let mut tables = vec![summary_table(ALL_LABEL, ALL_LABEL, None, total)];
+            let mut rows = by_function.into_iter().peekable();
+            while let Some(first) = rows.next() {
+                let mq_function = first.mq_function.clone();
+                let mut group = vec![first];
+                while let Some(row) = rows.next_if(|row| row.mq_function == mq_function) {
+                    group.push(row);
+                }
+                tables.push(summary_table(&mq_function, ALL_LABEL, None, group));
+            }
+            let filename = export_filename("all_mq_tps_summary", data, None);
+            export_response(app_state, format, &filename, tables)
+        }
+    }
 }
+
+/// A search streamed as NDJSON, read from SQLite one page at a time so the
+/// connection is only held while a page is read.
+struct SearchStream {
+    app_state: web::Data<AppState>,
+    request: SearchMqLogRequest,
+    page_size: usize,
+    after: Option<SearchCursor>,
+}
+
+fn ndjson_stream(state: SearchStream) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
+    stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        let app_state = state.app_state.clone();
+        let request = state.request.clone();
+        let (page_size, after) = (state.page_size, state.after.take());
+        let page = web::block(move || {
+            app_state
+                .metrics
+                .with_db(&app_state.db, "get_mq_log_usage_page", |connection| {
+                    get_mq_log_usage_page(
+                        connection,
+                        &request.from_datetime,
+                        &request.to_datetime,
+                        &usage_filter(&request),
+                        after.as_ref(),
+                        page_size,
+                    )
+                })
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|page| page)
+        .and_then(|page| {
+            let lines = ndjson_lines(page.rows.into_iter().map(SearchMqLogResponse::from))?;
+            Ok((lines, page.next_cursor))
+        });
+        match page {
+            Ok((lines, next_cursor)) => {
+                let next = next_cursor.map(|cursor| {
+                    state.after = Some(cursor);
+                    state
+                });
+                Some((Ok(web::Bytes::from(lines)), next))
+            }
+            Err(e) => {
+                // Headers are already sent; the last line reports the failure.
+                let error = AppError::internal("mq_search stream", e);
+                error!("{}", error);
+                let line = serde_json::to_string(&ApiResponse::from(&error)).unwrap_or_default() + "\n";
+                Some((Ok(web::Bytes::from(line)), None))
+            }
+        }
+    })
+}
+
+/// Unpaginated JSON, CSV and XLSX responses are refused above
+/// `search.max_rows` rows. `?cursor=`/`?page_size=` return one page at a
+/// time and `format=ndjson` streams every row.
+#[utoipa::path(
+    tag = "mq",
+    request_body = SearchMqLogRequest,
+    params(ExportQuery, PageQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Rows of the function; with `cursor` or `page_size`, `data` is a `SearchMqLogPage`", content(
+            (ApiResponse<Vec<SearchMqLogResponse>> = "application/json"),
+            (SearchMqLogResponse = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 400, description = "Invalid request, cursor or page size, or more rows than `search.max_rows`", body = ApiResponse<serde_json::Value>),
+    )
+)]
 #[post("/mq/search")]
 pub async fn mq_search(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
     data: web::Json<SearchMqLogRequest>,
-) -> impl actix_web::Responder {
-    let connection = app_state.db.lock().unwrap();
+    export: web::Query<ExportQuery>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
     debug!(
         "mq_search: start_date: {}, end_date: {}, mq_function: {}",
         data.from_datetime, data.to_datetime, data.mq_function_name
     );
+    let data = data.into_inner().normalized();
+    validate_search(&app_state, &claims, &data, true).await?;
+    search_response(&app_state, &claims, data, ExportFormat::negotiate(&req, &export), &page).await
+}
+
+async fn search_response(
+    app_state: &web::Data<AppState>,
+    claims: &Claims,
+    data: SearchMqLogRequest,
+    format: ExportFormat,
+    page: &PageQuery,
+) -> Result<HttpResponse, AppError> {
+    let config = &app_state.search;
+    let page_size = page.page_size.unwrap_or(config.page_size);
+    if page_size == 0 || page_size > config.max_page_size {
+        let message = format!("page_size must be between 1 and {}", config.max_page_size);
+        return Err(AppError::field("page_size", "invalid", message));
+    }
+    let after = match page.cursor.as_deref().map(SearchCursor::decode) {
+        Some(None) => return Err(AppError::field("cursor", "invalid", "Invalid cursor")),
+        Some(cursor) => cursor,
+        None => None,
+    };
 
-    let system_name = extract_system_name_option(&data);
-    let result = get_mq_log_usage(
-        &connection,
-        &data.from_datetime,
-        &data.to_datetime,
-        &data.mq_function_name,
-        system_name,
-    ).await;
+    if format == ExportFormat::Ndjson {
+        let state = SearchStream {
+            app_state: app_state.clone(),
+            request: data,
+            page_size,
+            after,
+        };
+        return Ok(HttpResponse::Ok()
+            .content_type(NDJSON_CONTENT_TYPE)
+            .streaming(ndjson_stream(state)));
+    }
+
+    if page.is_paginated() {
+        if format != ExportFormat::Json {
+            return Err(AppError::invalid("cursor and page_size are only supported for JSON and NDJSON"));
+        }
+        let page = app_state
+            .metrics
+            .with_db(&app_state.db, "get_mq_log_usage_page", |connection| {
+                get_mq_log_usage_page(
+                    connection,
+                    &data.from_datetime,
+                    &data.to_datetime,
+                    &usage_filter(&data),
+                    after.as_ref(),
+                    page_size,
+                )
+            })
+            .map_err(|e| AppError::internal("get_mq_log_usage_page", e))?;
+        let response = ApiResponse::success(
+            "Success",
+            Some(SearchMqLogPage {
+                rows: page.rows.into_iter().map(Into::into).collect(),
+                next_cursor: page.next_cursor.map(|cursor| cursor.encode()),
+            }),
+        );
+        return Ok(HttpResponse::from(response));
+    }
+
+    let params = SearchCacheParams::new(&data, true);
+    let max_rows = config.max_rows;
+    let (rows, from_cache) =
+        cached_or_load(app_state, CacheRoute::Search, claims, &params, "get_mq_log_usage", |connection| {
+            get_mq_log_usage(connection, &data.from_datetime, &data.to_datetime, &usage_filter(&data), Some(max_rows + 1))
+        })
+        .await?;
+    if rows.len() > max_rows {
+        let message = format!(
+            "The search matches more than {} rows; use cursor/page_size or format=ndjson",
+            max_rows
+        );
+        return Err(AppError::field("to_datetime", "too_many_rows", message));
+    }
+
+    if format != ExportFormat::Json {
+        let function = data.mq_function_name.as_str();
+        let filename = export_filename("mq_search", &data, Some(function));
+        let tables = vec![search_table(function, rows)];
+        return export_response(app_state, format, &filename, tables);
+    }
+    Ok(HttpResponse::from(mark_cached(rows_response(rows), from_cache)))
+}
+
+/// Normalized parameters of a `GET` query route, hashed into its ETag.
+#[derive(Serialize)]
+struct EtagParams<'a> {
+    route: &'static str,
+    #[serde(flatten)]
+    search: SearchCacheParams<'a>,
+    bucket_secs: Option<i64>,
+    format: ExportFormat,
+    cursor: Option<&'a str>,
+    page_size: Option<usize>,
+}
+
+/// `ETag`, `Cache-Control` and `Vary` of a `GET` query route. The weak
+/// ETag changes with the parameters, the dataset, the cache generation
+/// (bumped by imports, retention and invalidation) and the newest row id
+/// known to [`DataWatch`](crate::infrastructure::data_watch::DataWatch),
+/// so computing it needs no query; the max-age is longer once the range can
+/// no longer receive data.
+struct Validators {
+    etag: EntityTag,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl Validators {
+    async fn new(app_state: &AppState, request: &SearchMqLogRequest, params: &EtagParams<'_>) -> Result<Self, AppError> {
+        let generation = app_state.cache.generation().await;
+        let max_id = app_state.data_watch.latest();
+        let material = serde_json::to_vec(&(params, &app_state.dataset, generation, max_id))
+            .map_err(|e| AppError::internal("etag", e))?;
+        let etag = EntityTag::new_weak(hex::encode(&Sha256::digest(&material)[..16]));
+
+        let config = &app_state.search;
+        let historical = Local::now() - request.to_datetime > Duration::hours(config.historical_after_hours as i64);
+        let max_age = if historical { config.http_historical_max_age_secs } else { config.http_max_age_secs };
+        let scope = if config.http_public { CacheDirective::Public } else { CacheDirective::Private };
+        let cache_control = CacheControl(vec![scope, CacheDirective::MaxAge(max_age.min(u32::MAX as u64) as u32)]);
+
+        let headers = [ETag(etag.clone()).try_into_pair(), cache_control.try_into_pair()]
+            .into_iter()
+            .chain([Ok((header::VARY, HeaderValue::from_static("Accept")))])
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::internal("etag", e))?;
+        Ok(Self { etag, headers })
+    }
+
+    /// Whether `If-None-Match` already names this representation.
+    fn matches(&self, req: &HttpRequest) -> bool {
+        match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+            None => false,
+        }
+    }
+
+    /// `304 Not Modified` when the client is current, otherwise the result
+    /// of `respond` with the validators added to a successful response.
+    async fn respond(
+        self,
+        req: &HttpRequest,
+        respond: impl Future<Output = Result<HttpResponse, AppError>>,
+    ) -> Result<HttpResponse, AppError> {
+        let mut response = if self.matches(req) { HttpResponse::NotModified().finish() } else { respond.await? };
+        if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+            for (name, value) in self.headers {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Bucketed usage as rows: each bucket at its start, with the `group_by`
+/// value in that dimension.
+async fn load_buckets(
+    app_state: &AppState,
+    claims: &Claims,
+    data: &SearchMqLogRequest,
+    bucket_secs: i64,
+    with_function: bool,
+    group_by: Option<UsageDimension>,
+) -> Result<(Vec<MQLogUsage>, bool), AppError> {
+    #[derive(Serialize)]
+    struct BucketCacheParams<'a> {
+        #[serde(flatten)]
+        search: SearchCacheParams<'a>,
+        bucket_secs: i64,
+        series: Option<UsageDimension>,
+    }
+    let params = BucketCacheParams { search: SearchCacheParams::new(data, with_function), bucket_secs, series: group_by };
+    let mq_function = params.search.mq_function;
+    let no_queue = QueueDimensions::default();
+    let filter = UsageFilter {
+        mq_function,
+        system_name: params.search.system_name,
+        queue: params.search.queue.unwrap_or(&no_queue),
+    };
+    let (buckets, from_cache) =
+        cached_or_load(app_state, CacheRoute::Buckets, claims, &params, "get_bucketed_usage", |connection| {
+            get_bucketed_usage(connection, &data.from_datetime, &data.to_datetime, bucket_secs, &filter, group_by)
+        })
+        .await?;
+    let rows = buckets
+        .into_iter()
+        .filter_map(|bucket| {
+            let date_time = Local.timestamp_opt(bucket.bucket_start, 0).single()?;
+            let depth = DepthMetrics {
+                cur_depth: bucket.cur_depth,
+                max_depth: None,
+                oldest_msg_age_secs: bucket.oldest_msg_age_secs,
+            };
+            let mut row = MQLogUsage::new(
+                date_time,
+                String::new(),
+                mq_function.unwrap_or_default().to_string(),
+                bucket.work_total,
+                bucket.trans_per_sec,
+            )
+            .with_depth(depth);
+            if let Some(dimension) = group_by {
+                dimension.set(&mut row, bucket.group);
+            }
+            Some(row)
+        })
+        .collect();
+    Ok((rows, from_cache))
+}
 
-    handle_service_result(result, "mq_search")
+/// Bucketed usage of the `GET` query routes. Without `mq_function` the
+/// buckets sum every function; `group_by` breaks them down by a dimension.
+async fn bucketed_response(
+    app_state: &AppState,
+    claims: &Claims,
+    data: &SearchMqLogRequest,
+    bucket_secs: i64,
+    with_function: bool,
+    group_by: Option<UsageDimension>,
+    format: ExportFormat,
+) -> Result<HttpResponse, AppError> {
+    let (rows, from_cache) = load_buckets(app_state, claims, data, bucket_secs, with_function, group_by).await?;
+    let search = SearchCacheParams::new(data, with_function);
+    let (mq_function, system_name) = (search.mq_function, search.system_name);
+
+    match format {
+        ExportFormat::Json => Ok(HttpResponse::from(mark_cached(rows_response(rows), from_cache))),
+        ExportFormat::Ndjson => ndjson_response(rows, "get_bucketed_usage"),
+        ExportFormat::Csv | ExportFormat::Xlsx => {
+            let filename = export_filename("mq_usage_buckets", data, mq_function);
+            let function = mq_function.unwrap_or(ALL_LABEL);
+            let table = if group_by == Some(UsageDimension::System) {
+                search_table(function, rows)
+            } else {
+                summary_table(function, system_name.unwrap_or(ALL_LABEL), group_by, rows)
+            };
+            export_response(app_state, format, &filename, vec![table])
+        }
+    }
+}
+
+/// Renames the body fields named by validation errors to the query
+/// parameters of the `GET` routes.
+fn query_field_names(error: AppError) -> AppError {
+    match error {
+        AppError::Validation { message, mut fields } => {
+            for field in &mut fields {
+                let renamed = match field.field.as_str() {
+                    "from_datetime" => "from",
+                    "to_datetime" => "to",
+                    "mq_function_name" => "function",
+                    "system_name" => "system",
+                    "queue_name" => "queue",
+                    _ => continue,
+                };
+                field.field = renamed.to_string();
+            }
+            AppError::Validation { message, fields }
+        }
+        error => error,
+    }
+}
+
+/// Buckets are refused with pagination, which only the raw rows support.
+fn reject_paginated_buckets(bucket_secs: Option<i64>, page: &PageQuery) -> Result<(), AppError> {
+    if bucket_secs.is_some() && page.is_paginated() {
+        return Err(AppError::field("bucket", "conflicting", "bucket cannot be combined with cursor or page_size"));
+    }
+    Ok(())
+}
+
+/// `GET` form of `POST /mq/search` for bookmarkable URLs. With `bucket`
+/// the rows are aggregated per bucket and system, or `group_by` value.
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function"), UsageQuery, ExportQuery, PageQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Rows of the function; `ETag` and `Cache-Control` are set", content(
+            (ApiResponse<Vec<SearchMqLogResponse>> = "application/json"),
+            (SearchMqLogResponse = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 304, description = "`If-None-Match` names the current representation"),
+        (status = 400, description = "Invalid query; `error.fields` names the parameters", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/usage")]
+pub async fn mq_usage(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+    query: web::Query<UsageQuery>,
+    export: web::Query<ExportQuery>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (data, bucket_secs) = query.to_request(path.function.trim(), Local::now())?;
+    validate_search(&app_state, &claims, &data, true).await.map_err(query_field_names)?;
+    reject_paginated_buckets(bucket_secs, &page)?;
+
+    let format = ExportFormat::negotiate(&req, &export);
+    let params = EtagParams {
+        route: "mq_usage",
+        search: SearchCacheParams::new(&data, true),
+        bucket_secs,
+        format,
+        cursor: page.cursor.as_deref(),
+        page_size: page.page_size,
+    };
+    let validators = Validators::new(&app_state, &data, &params).await?;
+    let response = async {
+        match bucket_secs {
+            Some(bucket_secs) => {
+                let per_system = data.system_name.is_none().then_some(UsageDimension::System);
+                let group_by = data.group_by.or(per_system);
+                bucketed_response(&app_state, &claims, &data, bucket_secs, true, group_by, format).await
+            }
+            // The row cap names `to`, and a bad cursor or page size their own parameter.
+            None => search_response(&app_state, &claims, data.clone(), format, &page).await.map_err(query_field_names),
+        }
+    };
+    validators.respond(&req, response).await
+}
+
+/// `GET` form of `POST /mq/tps/summary`; `bucket` averages the TPS over
+/// each bucket.
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function"), UsageQuery, ExportQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "TPS summed over systems (or of `system`); `ETag` and `Cache-Control` are set", content(
+            (ApiResponse<Vec<SearchMqLogResponse>> = "application/json"),
+            (SearchMqLogResponse = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 304, description = "`If-None-Match` names the current representation"),
+        (status = 400, description = "Invalid query; `error.fields` names the parameters", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/tps")]
+pub async fn mq_tps(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+    query: web::Query<UsageQuery>,
+    export: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (data, bucket_secs) = query.to_request(path.function.trim(), Local::now())?;
+    validate_search(&app_state, &claims, &data, true).await.map_err(query_field_names)?;
+
+    let format = ExportFormat::negotiate(&req, &export);
+    let params = EtagParams {
+        route: "mq_tps",
+        search: SearchCacheParams::new(&data, true),
+        bucket_secs,
+        format,
+        cursor: None,
+        page_size: None,
+    };
+    let validators = Validators::new(&app_state, &data, &params).await?;
+    let response = async {
+        match bucket_secs {
+            Some(bucket_secs) => {
+                bucketed_response(&app_state, &claims, &data, bucket_secs, true, data.group_by, format).await
+            }
+            None => tps_summary_response(&app_state, &claims, &data, format).await,
+        }
+    };
+    validators.respond(&req, response).await
+}
+
+/// `GET` form of `POST /mq/tps/all_summary`. `system`, the queue filters
+/// and `group_by` are refused: the total over every function is not broken
+/// down.
+#[utoipa::path(
+    tag = "mq",
+    params(UsageQuery, ExportQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "TPS summed over every function; `ETag` and `Cache-Control` are set", content(
+            (ApiResponse<Vec<SearchMqLogResponse>> = "application/json"),
+            (SearchMqLogResponse = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 304, description = "`If-None-Match` names the current representation"),
+        (status = 400, description = "Invalid query; `error.fields` names the parameters", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/tps")]
+pub async fn all_mq_tps(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    query: web::Query<UsageQuery>,
+    export: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
+    let unsupported: Vec<FieldError> = [
+        ("system", query.system.is_some()),
+        ("queue_manager", query.queue_manager.is_some()),
+        ("queue", query.queue.is_some()),
+        ("channel", query.channel.is_some()),
+        ("group_by", query.group_by.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, given)| *given)
+    .map(|(name, _)| {
+        let message = format!("{} is not supported here; use /mq/{{function}}/tps", name);
+        FieldError::new(name, "unsupported", message)
+    })
+    .collect();
+    if !unsupported.is_empty() {
+        return Err(AppError::fields(unsupported));
+    }
+    let (data, bucket_secs) = query.to_request("", Local::now())?;
+    validate_search(&app_state, &claims, &data, false).await.map_err(query_field_names)?;
+
+    let format = ExportFormat::negotiate(&req, &export);
+    let params = EtagParams {
+        route: "all_mq_tps",
+        search: SearchCacheParams::new(&data, false),
+        bucket_secs,
+        format,
+        cursor: None,
+        page_size: None,
+    };
+    let validators = Validators::new(&app_state, &data, &params).await?;
+    let response = async {
+        match bucket_secs {
+            Some(bucket_secs) => bucketed_response(&app_state, &claims, &data, bucket_secs, false, None, format).await,
+            None => all_tps_summary_response(&app_state, &claims, &data, format).await,
+        }
+    };
+    validators.respond(&req, response).await
+}
+
+/// Depth samples of the queues mapped to a function, loaded from runmqsc
+/// snapshots, to chart next to its TPS.
+#[utoipa::path(
+    tag = "mq",
+    params(("function" = String, Path, description = "MQ function"), UsageQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Samples ordered by time and queue", body = ApiResponse<Vec<QueueDepth>>),
+        (status = 400, description = "Invalid query; `bucket` is not supported", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/depth")]
+pub async fn mq_depth(
+    app_state: web::Data<AppState>,
+    path: web::Path<FunctionPath>,
+    query: web::Query<UsageQuery>,
+) -> Result<ApiResponse<Vec<QueueDepth>>, AppError> {
+    let (data, bucket_secs) = query.to_request(path.function.trim(), Local::now())?;
+    if bucket_secs.is_some() {
+        return Err(AppError::field("bucket", "invalid", "depth samples cannot be bucketed"));
+    }
+    if data.queue.channel.is_some() || data.group_by.is_some() {
+        let field = if data.group_by.is_some() { "group_by" } else { "channel" };
+        let message = format!("{} is not supported for depth samples", field);
+        return Err(AppError::field(field, "unsupported", message));
+    }
+    let max_range = Duration::days(app_state.search.max_range_days as i64);
+    let fields = data.field_errors(max_range, true);
+    if !fields.is_empty() {
+        return Err(query_field_names(AppError::fields(fields)));
+    }
+    let samples = app_state
+        .metrics
+        .with_db(&app_state.db, "get_queue_depth", |connection| {
+            get_queue_depth(
+                connection,
+                &data.mq_function_name,
+                data.system_name.as_deref(),
+                &data.queue,
+                &data.from_datetime,
+                &data.to_datetime,
+            )
+        })
+        .map_err(|e| AppError::internal("get_queue_depth", e))?;
+    Ok(ApiResponse::<Vec<QueueDepth>>::success("Success", Some(samples)))
+}
+
+/// One series of a function per dataset, e.g. to compare environments.
+/// Every dataset is filtered and bucketed alike, per minute without
+/// `bucket`; `group_by` is refused.
+#[utoipa::path(
+    tag = "datasets",
+    params(("function" = String, Path, description = "MQ function"), CompareQuery, UsageQuery, ExportQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Series in the requested (or name) order", content(
+            (ApiResponse<Vec<DatasetSeries>> = "application/json"),
+            (DatasetSeries = "application/x-ndjson"),
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        )),
+        (status = 400, description = "Invalid query, or a function no compared dataset knows", body = ApiResponse<serde_json::Value>),
+        (status = 403, description = "A named dataset is not readable by the user", body = ApiResponse<serde_json::Value>),
+        (status = 404, description = "Unknown dataset", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/{function}/compare")]
+pub async fn mq_compare(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<FunctionPath>,
+    compare: web::Query<CompareQuery>,
+    query: web::Query<UsageQuery>,
+    export: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
+    if query.group_by.is_some() {
+        return Err(AppError::field("group_by", "unsupported", "group_by is not supported here; series are per dataset"));
+    }
+    let (data, bucket_secs) = query.to_request(path.function.trim(), Local::now())?;
+    let max_range = Duration::days(app_state.search.max_range_days as i64);
+    let fields = data.field_errors(max_range, true);
+    if !fields.is_empty() {
+        return Err(query_field_names(AppError::fields(fields)));
+    }
+    let datasets = match compare.names() {
+        Some(names) if names.is_empty() => {
+            return Err(AppError::field("datasets", "required", "datasets must name at least one dataset"));
+        }
+        Some(names) => names
+            .into_iter()
+            .map(|name| app_state.datasets.resolve(Some(name), &claims.sub))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => app_state.datasets.iter().filter(|dataset| dataset.allows(&claims.sub)).collect(),
+    };
+
+    let function = data.mq_function_name.as_str();
+    let mut series = Vec::new();
+    for dataset in datasets {
+        let state = app_state.for_dataset(dataset);
+        let (functions, _) =
+            cached_or_load(&state, CacheRoute::Functions, &claims, &(), "get_mq_function_list", get_mq_function_list)
+                .await?;
+        let known_function = functions.iter().any(|f: &String| f == function);
+        let rows = if known_function {
+            load_buckets(&state, &claims, &data, bucket_secs.unwrap_or(60), true, None).await?.0
+        } else {
+            Vec::new()
+        };
+        series.push((dataset, known_function, rows));
+    }
+    if !series.is_empty() && !series.iter().any(|(_, known_function, _)| *known_function) {
+        let message = format!("unknown mq_function '{}'", function);
+        return Err(query_field_names(AppError::field("mq_function_name", "unknown_function", message)));
+    }
+
+    match ExportFormat::negotiate(&req, &export) {
+        format @ (ExportFormat::Csv | ExportFormat::Xlsx) => {
+            let tables: Vec<ExportTable> = series
+                .into_iter()
+                .map(|(dataset, _, rows)| ExportTable {
+                    sheet: dataset.name.clone(),
+                    columns: vec!["dataset", "date_time", "mq_function", "work_total", "trans_per_sec", "cur_depth"],
+                    rows: rows
+                        .into_iter()
+                        .map(|row| {
+                            vec![
+                                Cell::Text(dataset.name.clone()),
+                                Cell::DateTime(row.date_time),
+                                Cell::Text(row.mq_function),
+                                Cell::Number(row.work_total),
+                                Cell::Number(row.trans_per_sec),
+                                optional_cell(row.depth.cur_depth),
+                            ]
+                        })
+                        .collect(),
+                })
+                .collect();
+            let filename = export_filename("mq_compare", &data, Some(function));
+            export_response(&app_state, format, &filename, tables)
+        }
+        format => {
+            let series: Vec<DatasetSeries> = series
+                .into_iter()
+                .map(|(dataset, known_function, rows)| DatasetSeries {
+                    dataset: dataset.name.clone(),
+                    label: dataset.label.clone(),
+                    known_function,
+                    rows: rows.into_iter().map(SearchMqLogResponse::from).collect(),
+                })
+                .collect();
+            if format == ExportFormat::Ndjson {
+                let body = ndjson_lines(series).map_err(|e| AppError::internal("get_bucketed_usage", e))?;
+                return Ok(HttpResponse::Ok().content_type(NDJSON_CONTENT_TYPE).body(body));
+            }
+            Ok(HttpResponse::from(ApiResponse::<Vec<DatasetSeries>>::success("Success", Some(series))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::config::AppConfig;
+    use crate::interface::api::ingest_handler::ingest;
+    use crate::interface::dto::UsageQuery;
+    use actix_web::dev::Service;
+    use actix_web::App;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use rusqlite::params;
+    use serde_json::json;
+
+    fn catalog() -> HashMap<String, CatalogMetadata> {
+        let entry = |team: &str, tags: &[&str]| CatalogMetadata {
+            owner_team: Some(team.to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+        HashMap::from([
+            ("SYS-A".to_string(), entry("core", &["core", "tier-1"])),
+            ("SYS-B".to_string(), entry("cards", &["tier-2"])),
+        ])
+    }
+
+    fn names() -> Vec<String> {
+        ["SYS-A", "SYS-B", "SYS-C"].map(String::from).to_vec()
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn tags_keep_catalogued_names_carrying_all_of_them() {
+        let json = |names| serde_json::to_value(names).unwrap();
+        assert_eq!(json(catalog_names(names(), &catalog(), &[], false)), json!(["SYS-A", "SYS-B", "SYS-C"]));
+        assert_eq!(json(catalog_names(names(), &catalog(), &tags(&["tier-1"]), false)), json!(["SYS-A"]));
+        assert_eq!(json(catalog_names(names(), &catalog(), &tags(&["tier-1", "tier-2"]), false)), json!([]));
+    }
+
+    #[test]
+    fn metadata_is_attached_where_the_catalog_has_it() {
+        let enriched = serde_json::to_value(catalog_names(names(), &catalog(), &[], true)).unwrap();
+        assert_eq!(
+            enriched,
+            json!([
+                {"name": "SYS-A", "metadata": {"owner_team": "core", "tags": ["core", "tier-1"]}},
+                {"name": "SYS-B", "metadata": {"owner_team": "cards", "tags": ["tier-2"]}},
+                {"name": "SYS-C"},
+            ])
+        );
+    }
+
+    fn state(dir: &tempfile::TempDir) -> AppState {
+        let mut config = AppConfig::default();
+        config.database.path = dir.path().join("mq.db");
+        AppState::for_tests(&config)
+    }
+
+    fn at(minute: u32) -> chrono::DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 19, 8, minute, 0).unwrap()
+    }
+
+    /// Minute rows of `function`, inserted in the order given.
+    fn insert(state: &AppState, function: &str, rows: &[(u32, &str)]) {
+        let db = state.db.lock().unwrap();
+        for (minute, system_name) in rows {
+            db.execute(
+                "INSERT INTO mq_data (date_time, date, minute, system_name, mq_function, work_total, trans_per_sec, \
+                 granularity) VALUES (?1, '', '', ?2, ?3, 60, 1, 'minute')",
+                params![at(*minute).to_rfc3339(), system_name, function],
+            )
+            .unwrap();
+        }
+    }
+
+    async fn search(state: &AppState, query: &str) -> (StatusCode, serde_json::Value) {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .wrap_fn(|req, srv| {
+                    req.extensions_mut().insert(Claims { sub: "alice".to_string(), exp: usize::MAX });
+                    srv.call(req)
+                })
+                .service(mq_search),
+        )
+        .await;
+        let body = json!({"from_datetime": at(0), "to_datetime": at(59), "mq_function_name": "PAY"});
+        let req = TestRequest::post().uri(&format!("/mq/search{}", query)).set_json(body);
+        let resp = call_service(&app, req.to_request()).await;
+        let status = resp.status();
+        (status, read_body_json(resp).await)
+    }
+
+    /// (minute, system) of the rows of a response.
+    fn rows(rows: &serde_json::Value) -> Vec<(u32, String)> {
+        rows.as_array()
+            .unwrap()
+            .iter()
+            .map(|row| {
+                let date_time: chrono::DateTime<Local> = serde_json::from_value(row["date_time"].clone()).unwrap();
+                (date_time.format("%M").to_string().parse().unwrap(), row["system_name"].as_str().unwrap().to_string())
+            })
+            .collect()
+    }
+
+    /// Every page from the start, following `next_cursor`.
+    async fn pages(state: &AppState, page_size: usize) -> Vec<Vec<(u32, String)>> {
+        let mut pages = Vec::new();
+        let mut query = format!("?page_size={}", page_size);
+        loop {
+            let (status, body) = search(state, &query).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            pages.push(rows(&body["data"]["rows"]));
+            match body["data"]["next_cursor"].as_str() {
+                Some(cursor) => query = format!("?page_size={}&cursor={}", page_size, cursor),
+                None => return pages,
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn cursors_walk_the_whole_search_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        insert(&state, "PAY", &[(3, "SYS-A"), (1, "SYS-A"), (2, "SYS-A"), (5, "SYS-A"), (4, "SYS-A")]);
+        insert(&state, "CARDS", &[(2, "SYS-A")]);
+
+        let (status, body) = search(&state, "").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let all = rows(&body["data"]);
+        assert_eq!(all.iter().map(|row| row.0).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+
+        let paged = pages(&state, 2).await;
+        assert_eq!(paged.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        assert_eq!(paged.concat(), all);
+        // An exact multiple of the page size ends without an empty page.
+        assert_eq!(pages(&state, 5).await, [all]);
+    }
+
+    #[actix_web::test]
+    async fn rows_at_the_same_instant_are_split_across_pages_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        insert(&state, "PAY", &[(1, "SYS-C"), (1, "SYS-A"), (1, "SYS-D"), (1, "SYS-B"), (2, "SYS-A")]);
+
+        let paged = pages(&state, 2).await;
+        assert_eq!(paged.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        let systems = paged.concat().into_iter().map(|row| row.1).collect::<Vec<_>>();
+        // Insertion (id) order within the minute, each row exactly once.
+        assert_eq!(systems, ["SYS-C", "SYS-A", "SYS-D", "SYS-B", "SYS-A"]);
+    }
+
+    #[actix_web::test]
+    async fn malformed_cursors_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        insert(&state, "PAY", &[(1, "SYS-A")]);
+
+        let not_a_number = hex::encode(format!("x|{}", at(1).to_rfc3339()));
+        for cursor in ["zz", "abc", "", &hex::encode("no separator"), &not_a_number] {
+            let (status, body) = search(&state, &format!("?cursor={}", cursor)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", cursor, body);
+            assert_eq!(body["error"]["fields"][0]["field"], "cursor", "{}", cursor);
+        }
+    }
+
+    #[actix_web::test]
+    async fn edited_cursors_stay_within_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        insert(&state, "PAY", &[(1, "SYS-A"), (2, "SYS-A")]);
+        insert(&state, "CARDS", &[(0, "SYS-A"), (3, "SYS-A")]);
+
+        // Cursors are not signed: an edited one only moves the position,
+        // and the range and function of the request still apply.
+        let before_everything = SearchCursor { date_time: String::new(), id: 0 }.encode();
+        let (status, body) = search(&state, &format!("?cursor={}", before_everything)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(rows(&body["data"]["rows"]), [(1, "SYS-A".to_string()), (2, "SYS-A".to_string())]);
+        assert!(body["data"]["next_cursor"].is_null());
+
+        let past_the_end = SearchCursor { date_time: at(59).to_rfc3339(), id: i64::MAX }.encode();
+        let (status, body) = search(&state, &format!("?cursor={}", past_the_end)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["data"]["rows"], json!([]));
+    }
+
+    /// `GET /mq/PAY/usage` over the test hour, and its `ETag`.
+    async fn usage(state: &AppState, if_none_match: Option<&str>) -> (StatusCode, Option<String>) {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .wrap_fn(|req, srv| {
+                    req.extensions_mut().insert(Claims { sub: "alice".to_string(), exp: usize::MAX });
+                    srv.call(req)
+                })
+                .service(mq_usage),
+        )
+        .await;
+        let utc = |minute| at(minute).with_timezone(&chrono::Utc).format("%Y-%m-%dT%H:%M:%SZ");
+        let mut req = TestRequest::get().uri(&format!("/mq/PAY/usage?from={}&to={}", utc(0), utc(59)));
+        if let Some(etag) = if_none_match {
+            req = req.insert_header((header::IF_NONE_MATCH, etag));
+        }
+        let resp = call_service(&app, req.to_request()).await;
+        let etag = resp.headers().get(header::ETAG).map(|etag| etag.to_str().unwrap().to_string());
+        (resp.status(), etag)
+    }
+
+    #[actix_web::test]
+    async fn a_current_etag_is_not_modified_until_an_ingest() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        insert(&state, "PAY", &[(1, "SYS-A")]);
+        state.data_watch.refresh(&state.db);
+
+        let (status, etag) = usage(&state, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let etag = etag.expect("ETag");
+        assert_eq!(usage(&state, Some(&etag)).await, (StatusCode::NOT_MODIFIED, Some(etag.clone())));
+        assert_eq!(usage(&state, Some("W/\"stale\"")).await, (StatusCode::OK, Some(etag.clone())));
+
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .wrap_fn(|req, srv| {
+                    req.extensions_mut().insert(Claims { sub: "alice".to_string(), exp: usize::MAX });
+                    srv.call(req)
+                })
+                .service(ingest),
+        )
+        .await;
+        let row = json!({"date_time": at(2), "system_name": "SYS-A", "mq_function": "PAY", "work_total": 60});
+        let req = TestRequest::post()
+            .uri("/ingest")
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .set_payload(format!("{}\n", row));
+        assert_eq!(call_service(&app, req.to_request()).await.status(), StatusCode::OK);
+
+        let (status, new_etag) = usage(&state, Some(&etag)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(new_etag.expect("ETag"), etag);
+    }
+
+    #[test]
+    fn relative_ranges_end_at_the_start_of_the_minute() {
+        let query = |pairs: &str| web::Query::<UsageQuery>::from_query(pairs).unwrap().into_inner();
+        let now = at(30) + Duration::seconds(42);
+
+        let (request, bucket) = query("last=2h&bucket=5m").to_request("PAY", now).unwrap();
+        assert_eq!((request.from_datetime, request.to_datetime), (at(30) - Duration::hours(2), at(30)));
+        assert_eq!(bucket, Some(300));
+        let (request, _) = query("from=2026-10-19T06:00:00Z").to_request("PAY", now).unwrap();
+        assert_eq!(request.to_datetime, at(30));
+
+        let field = |pairs: &str| match query(pairs).to_request("PAY", now) {
+            Err(AppError::Validation { fields, .. }) => fields.into_iter().map(|f| f.field).collect::<Vec<_>>(),
+            other => panic!("{}: {:?}", pairs, other.map(|_| ())),
+        };
+        assert_eq!(field("last=2h&from=2026-10-19T06:00:00Z"), ["last"]);
+        assert_eq!(field("last=0h"), ["last"]);
+        assert_eq!(field("last=2x"), ["last"]);
+        assert_eq!(field(""), ["from"]);
+        assert_eq!(field("last=1h&bucket=90s"), ["bucket"]);
+    }
 }
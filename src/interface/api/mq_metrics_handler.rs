@@ -0,0 +1,184 @@
+use crate::application::error::AppError;
+use crate::application::mq_metrics_service::{
+    UsageRange, count_series_and_rows, get_latest_per_series, get_usage_by_series,
+};
+use crate::domain::model::{MQLogUsage, SeriesCount};
+use crate::domain::retention::Granularity;
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::metrics::{write_header, write_sample, write_sample_at};
+use crate::interface::api::metrics_handler::{PROMETHEUS_CONTENT_TYPE, authorized};
+use crate::interface::dto::ApiResponse;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, get, web};
+use chrono::{DateTime, Duration, Local};
+use log::warn;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const TPS_METRIC: &str = "mq_trans_per_sec";
+const WORK_METRIC: &str = "mq_work";
+const TPS_HELP: &str = "MQ transactions per second (trans_per_sec in mq_data).";
+const WORK_HELP: &str = "MQ work in the row's interval (work_total in mq_data).";
+
+fn series_labels(usage: &MQLogUsage) -> [(&str, &str); 2] {
+    [("mq_function", &usage.mq_function), ("system_name", &usage.system_name)]
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LatestQuery {
+    pub mq_function: Option<String>,
+}
+
+/// Latest per-minute TPS and work of every series of the default dataset
+/// as Prometheus gauges, for scraping into an existing monitoring stack.
+/// Requires the `/metrics` token, which the server will not start without
+/// while this route is enabled. At most `mq_metrics.max_series` series are
+/// exported.
+#[utoipa::path(
+    tag = "metrics",
+    params(LatestQuery),
+    security(("metrics_token" = [])),
+    responses(
+        (status = 200, description = "Prometheus text format", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or wrong metrics token"),
+    )
+)]
+#[get("/metrics/mq")]
+pub async fn mq_usage_metrics(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    query: web::Query<LatestQuery>,
+) -> HttpResponse {
+    if app_state.metrics_token.is_none() || !authorized(&req, app_state.metrics_token.as_deref()) {
+        return AppError::Unauthorized("Missing or wrong metrics token".to_string()).error_response();
+    }
+    let config = &app_state.mq_metrics;
+    let since = Local::now() - Duration::minutes(config.lookback_minutes as i64);
+    let mq_function = query.mq_function.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let result = app_state.metrics.with_db(&app_state.db, "get_latest_per_series", |connection| {
+        get_latest_per_series(connection, &since, mq_function)
+    });
+    let mut usages = match result {
+        Ok(usages) => usages,
+        Err(e) => return AppError::internal("get_latest_per_series", e).error_response(),
+    };
+    let dropped = usages.len().saturating_sub(config.max_series);
+    if dropped > 0 {
+        warn!("/metrics/mq: {} series over mq_metrics.max_series were dropped", dropped);
+        usages.truncate(config.max_series);
+    }
+
+    let mut out = String::new();
+    write_header(&mut out, TPS_METRIC, "gauge", TPS_HELP);
+    for usage in &usages {
+        write_sample(&mut out, TPS_METRIC, &series_labels(usage), usage.trans_per_sec);
+    }
+    write_header(&mut out, WORK_METRIC, "gauge", WORK_HELP);
+    for usage in &usages {
+        write_sample(&mut out, WORK_METRIC, &series_labels(usage), usage.work_total);
+    }
+    let timestamp = "mq_last_sample_timestamp_seconds";
+    write_header(&mut out, timestamp, "gauge", "Time of the newest minute row of the series.");
+    for usage in &usages {
+        write_sample(&mut out, timestamp, &series_labels(usage), usage.date_time.timestamp() as f64);
+    }
+    let dropped_metric = "mq_metrics_series_dropped";
+    write_header(&mut out, dropped_metric, "gauge", "Series left out because of mq_metrics.max_series.");
+    write_sample(&mut out, dropped_metric, &[], dropped as f64);
+
+    HttpResponse::Ok().content_type(PROMETHEUS_CONTENT_TYPE).body(out)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BackfillQuery {
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub mq_function: Option<String>,
+    pub system_name: Option<String>,
+    pub granularity: Option<Granularity>,
+}
+
+/// OpenMetrics exposition of a historical range with explicit timestamps,
+/// for `promtool tsdb create-blocks-from openmetrics`. Refuses ranges over
+/// the series or row limits instead of truncating them.
+#[utoipa::path(
+    tag = "metrics",
+    params(BackfillQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "OpenMetrics attachment ending with `# EOF`", body = String, content_type = "application/openmetrics-text"),
+        (status = 400, description = "Invalid range or over the series/row limits", body = ApiResponse<serde_json::Value>),
+    )
+)]
+#[get("/mq/export/openmetrics")]
+pub async fn openmetrics_backfill(
+    app_state: web::Data<AppState>,
+    query: web::Query<BackfillQuery>,
+) -> Result<HttpResponse, AppError> {
+    if query.from > query.to {
+        return Err(AppError::field("to", "range_inverted", "to must not be before from"));
+    }
+    let config = &app_state.mq_metrics;
+    let range = UsageRange {
+        from: query.from,
+        to: query.to,
+        granularity: query.granularity.unwrap_or(Granularity::Minute),
+        mq_function: query.mq_function.as_deref().map(str::trim).filter(|s| !s.is_empty()),
+        system_name: query.system_name.as_deref().map(str::trim).filter(|s| !s.is_empty()),
+    };
+
+    let SeriesCount { series, rows } = app_state
+        .metrics
+        .with_db(&app_state.db, "count_series_and_rows", |connection| count_series_and_rows(connection, &range))
+        .map_err(|e| AppError::internal("count_series_and_rows", e))?;
+    if series > config.max_series {
+        let message = format!(
+            "{} series exceed the limit of {}; filter by mq_function or system_name",
+            series, config.max_series
+        );
+        return Err(AppError::field("mq_function", "too_many_series", message));
+    }
+    if rows > config.max_backfill_rows {
+        let message = format!(
+            "{} rows exceed the limit of {}; export a shorter range",
+            rows, config.max_backfill_rows
+        );
+        return Err(AppError::field("to", "range_too_large", message));
+    }
+
+    let usages = app_state
+        .metrics
+        .with_db(&app_state.db, "get_usage_by_series", |connection| {
+            get_usage_by_series(connection, &range)
+        })
+        .map_err(|e| AppError::internal("get_usage_by_series", e))?;
+
+    // Every family must be contiguous and each series in time order.
+    let mut out = String::new();
+    write_header(&mut out, TPS_METRIC, "gauge", TPS_HELP);
+    for usage in &usages {
+        let timestamp = Some(usage.date_time.timestamp());
+        write_sample_at(&mut out, TPS_METRIC, &series_labels(usage), usage.trans_per_sec, timestamp);
+    }
+    write_header(&mut out, WORK_METRIC, "gauge", WORK_HELP);
+    for usage in &usages {
+        let timestamp = Some(usage.date_time.timestamp());
+        write_sample_at(&mut out, WORK_METRIC, &series_labels(usage), usage.work_total, timestamp);
+    }
+    out.push_str("# EOF\n");
+
+    let filename = format!(
+        "mq_usage_{}_{}.om",
+        query.from.format("%Y%m%d%H%M"),
+        query.to.format("%Y%m%d%H%M")
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(OPENMETRICS_CONTENT_TYPE)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .body(out))
+}
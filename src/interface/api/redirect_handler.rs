@@ -0,0 +1,26 @@
+use actix_web::http::header::LOCATION;
+use actix_web::{HttpRequest, HttpResponse, web};
+
+/// Port the HTTPS listener runs on, for building redirect targets.
+#[derive(Clone, Copy)]
+pub struct HttpsPort(pub u16);
+
+/// Default service of the plain HTTP listener: permanently redirects every
+/// request to the same path on HTTPS.
+pub async fn https_redirect(req: HttpRequest, https_port: web::Data<HttpsPort>) -> HttpResponse {
+    let connection_info = req.connection_info();
+    let host = connection_info.host();
+    // Strip the port of the HTTP listener, keeping bracketed IPv6 hosts intact.
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && !port.contains(']') => name,
+        _ => host,
+    };
+    let path = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let location = match https_port.0 {
+        443 => format!("https://{}{}", host, path),
+        port => format!("https://{}:{}{}", host, port, path),
+    };
+    HttpResponse::PermanentRedirect()
+        .insert_header((LOCATION, location))
+        .finish()
+}
@@ -0,0 +1,359 @@
+use crate::application::error::AppError;
+use crate::application::mq_log_usage_service::{
+    UsageFilter, get_bucketed_usage, get_bucketed_usage_inserted, get_system_name_list,
+};
+use crate::domain::auth::Claims;
+use crate::domain::model::{QueueDimensions, UsageBucket};
+use crate::infrastructure::app_state::AppState;
+use crate::infrastructure::metrics::RowCount;
+use crate::interface::dto::{WsClientMessage, WsPoint, WsSeriesUpdate, WsServerMessage, parse_span};
+use actix_web::rt::time::{Instant, interval, sleep_until, timeout};
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, Session};
+use chrono::{Duration as ChronoDuration, Local, TimeZone};
+use log::{debug, error};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Largest message a client may send.
+const MAX_CLIENT_MESSAGE_BYTES: usize = 64 * 1024;
+const MAX_SERIES_ID_LEN: usize = 64;
+/// Clients that answer no ping for this many heartbeats are disconnected.
+const MISSED_HEARTBEATS: u32 = 3;
+
+struct Series {
+    mq_function: String,
+    system_name: Option<String>,
+    bucket_secs: i64,
+}
+
+/// Why the connection loop ended, turned into the close frame.
+enum Closing {
+    /// The client closed or went away.
+    Client(Option<CloseReason>),
+    Server(CloseCode, &'static str),
+}
+
+/// One WebSocket client: its series and the newest row id already sent.
+struct LiveConnection {
+    app_state: web::Data<AppState>,
+    user: String,
+    session: Session,
+    series: BTreeMap<String, Series>,
+    after_id: i64,
+}
+
+fn points(buckets: Vec<UsageBucket>) -> Vec<WsPoint> {
+    buckets
+        .into_iter()
+        .filter_map(|bucket| {
+            Some(WsPoint {
+                time: Local.timestamp_opt(bucket.bucket_start, 0).single()?,
+                trans_per_sec: bucket.trans_per_sec,
+                work_total: bucket.work_total,
+            })
+        })
+        .collect()
+}
+
+/// Logs an internal error and returns what the client is told.
+fn internal(e: AppError) -> (&'static str, String) {
+    error!("{}", e);
+    (e.code(), e.public_message().to_string())
+}
+
+fn error_message(id: Option<&str>, code: &'static str, message: impl Into<String>) -> WsServerMessage {
+    WsServerMessage::Error {
+        id: id.map(str::to_string),
+        code,
+        message: message.into(),
+    }
+}
+
+impl LiveConnection {
+    /// Sends one message, giving up on clients that stop reading: the
+    /// session buffers only a few messages, so a full buffer blocks here.
+    async fn send(&mut self, message: &WsServerMessage) -> Result<(), Closing> {
+        let text = serde_json::to_string(message).map_err(|e| {
+            error!("{}", AppError::internal("live websocket", e));
+            Closing::Server(CloseCode::Error, "Internal server error")
+        })?;
+        let send_timeout = Duration::from_secs(self.app_state.live.send_timeout_secs);
+        match timeout(send_timeout, self.session.text(text)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(Closing::Client(None)),
+            Err(_) => {
+                debug!("live websocket: {} is not reading, disconnecting", self.user);
+                Err(Closing::Server(CloseCode::Again, "Client too slow"))
+            }
+        }
+    }
+
+    /// Runs `load` against the database off the async workers.
+    async fn query<T>(
+        &self,
+        function: &'static str,
+        load: impl FnOnce(&rusqlite::Connection) -> Result<T, Box<dyn std::error::Error>> + Send + 'static,
+    ) -> Result<T, AppError>
+    where
+        T: RowCount + Send + 'static,
+    {
+        let app_state = self.app_state.clone();
+        web::block(move || app_state.metrics.with_db(&app_state.db, function, load).map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| AppError::internal(function, e))?
+            .map_err(|e| AppError::internal(function, e))
+    }
+
+    async fn handle_text(&mut self, text: &str) -> Result<(), Closing> {
+        let message = match serde_json::from_str::<WsClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => return self.send(&error_message(None, "malformed_message", e.to_string())).await,
+        };
+        let reply = match message {
+            WsClientMessage::Subscribe { id, mq_function, system_name, bucket, last } => {
+                match self.subscribe(&id, mq_function, system_name, bucket, last).await {
+                    Ok(snapshot) => {
+                        self.send(&WsServerMessage::Subscribed { id: id.clone() }).await?;
+                        match snapshot {
+                            Some(points) => WsServerMessage::Update { series: vec![WsSeriesUpdate { id, points }] },
+                            None => return Ok(()),
+                        }
+                    }
+                    Err((code, message)) => error_message(Some(&id), code, message),
+                }
+            }
+            WsClientMessage::Unsubscribe { id } => match self.series.remove(&id) {
+                Some(_) => WsServerMessage::Unsubscribed { id },
+                None => error_message(Some(&id), "unknown_series", format!("no series '{}'", id)),
+            },
+        };
+        self.send(&reply).await
+    }
+
+    /// Adds the series and returns its snapshot when `last` is given.
+    async fn subscribe(
+        &mut self,
+        id: &str,
+        mq_function: String,
+        system_name: Option<String>,
+        bucket: Option<String>,
+        last: Option<String>,
+    ) -> Result<Option<Vec<WsPoint>>, (&'static str, String)> {
+        let live = &self.app_state.live;
+        if id.is_empty() || id.len() > MAX_SERIES_ID_LEN {
+            return Err(("invalid", format!("id must be 1 to {} characters", MAX_SERIES_ID_LEN)));
+        }
+        if !self.series.contains_key(id) && self.series.len() >= live.max_subscriptions {
+            let message = format!("at most {} series per connection", live.max_subscriptions);
+            return Err(("too_many_subscriptions", message));
+        }
+        let bucket_secs = match bucket.as_deref().map(parse_span) {
+            None => 60,
+            Some(Some(span)) if span.num_seconds() % 60 == 0 => span.num_seconds(),
+            Some(_) => return Err(("invalid", "bucket must be whole minutes, e.g. 1m, 5m or 1h".to_string())),
+        };
+        let max_range = ChronoDuration::days(self.app_state.search.max_range_days as i64);
+        let snapshot_span = match last.as_deref().map(parse_span) {
+            None => None,
+            Some(Some(span)) if span <= max_range => Some(span),
+            Some(_) => {
+                let message = format!("last must look like 30m, 24h or 7d and not exceed {} days", max_range.num_days());
+                return Err(("invalid", message));
+            }
+        };
+
+        let mq_function = mq_function.trim().to_string();
+        let system_name = system_name.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let function = mq_function.clone();
+        let systems = self
+            .query("get_system_name_list", move |connection| get_system_name_list(connection, &function))
+            .await
+            .map_err(internal)?;
+        if systems.is_empty() {
+            return Err(("unknown_function", format!("unknown mq_function '{}'", mq_function)));
+        }
+        if let Some(system_name) = &system_name
+            && !systems.contains(system_name)
+        {
+            return Err(("unknown_system", format!("unknown system '{}' for mq_function '{}'", system_name, mq_function)));
+        }
+
+        let snapshot = match snapshot_span {
+            Some(span) => {
+                let (function, system) = (mq_function.clone(), system_name.clone());
+                let end = Local::now();
+                let buckets = self
+                    .query("get_bucketed_usage", move |connection| {
+                        let filter = UsageFilter {
+                            mq_function: Some(&function),
+                            system_name: system.as_deref(),
+                            queue: &QueueDimensions::default(),
+                        };
+                        get_bucketed_usage(connection, &(end - span), &end, bucket_secs, &filter, None)
+                    })
+                    .await
+                    .map_err(internal)?;
+                Some(points(buckets))
+            }
+            None => None,
+        };
+        debug!(
+            "live websocket: {} subscribed {} to {} / {:?} every {} s",
+            self.user, id, mq_function, system_name, bucket_secs
+        );
+        self.series.insert(id.to_string(), Series { mq_function, system_name, bucket_secs });
+        Ok(snapshot)
+    }
+
+    /// Sends, in one message, the buckets of every series touched by rows
+    /// written after the last update and up to row `up_to_id`.
+    async fn push_updates(&mut self, up_to_id: i64) -> Result<(), Closing> {
+        if up_to_id < self.after_id {
+            // The newest rows were deleted; their ids may be used again.
+            self.after_id = up_to_id;
+        }
+        if up_to_id == self.after_id || self.series.is_empty() {
+            self.after_id = up_to_id;
+            return Ok(());
+        }
+        let series: Vec<_> = self
+            .series
+            .iter()
+            .map(|(id, series)| (id.clone(), series.mq_function.clone(), series.system_name.clone(), series.bucket_secs))
+            .collect();
+        let after_id = self.after_id;
+        let updates = self
+            .query("get_bucketed_usage_inserted", move |connection| {
+                let mut updates = Vec::new();
+                for (id, mq_function, system_name, bucket_secs) in series {
+                    let buckets = get_bucketed_usage_inserted(
+                        connection,
+                        &mq_function,
+                        system_name.as_deref(),
+                        bucket_secs,
+                        after_id,
+                        up_to_id,
+                    )?;
+                    if !buckets.is_empty() {
+                        updates.push(WsSeriesUpdate { id, points: points(buckets) });
+                    }
+                }
+                Ok(updates)
+            })
+            .await;
+        let updates = match updates {
+            Ok(updates) => updates,
+            Err(e) => {
+                error!("{}", e);
+                return Err(Closing::Server(CloseCode::Error, "Internal server error"));
+            }
+        };
+        self.after_id = up_to_id;
+        if updates.is_empty() {
+            return Ok(());
+        }
+        self.send(&WsServerMessage::Update { series: updates }).await
+    }
+
+    async fn run(mut self, mut messages: AggregatedMessageStream) {
+        let live = self.app_state.live.clone();
+        let mut receiver = self.app_state.data_watch.subscribe();
+        let heartbeat = Duration::from_secs(live.heartbeat_secs);
+        let update_interval = Duration::from_millis(live.update_interval_ms);
+        let mut heartbeats = interval(heartbeat);
+        let mut last_seen = Instant::now();
+        let mut next_update = Instant::now();
+
+        let closing = loop {
+            let step = tokio::select! {
+                message = messages.recv() => match message {
+                    Some(Ok(message)) => {
+                        last_seen = Instant::now();
+                        match message {
+                            AggregatedMessage::Text(text) => self.handle_text(&text).await,
+                            AggregatedMessage::Binary(_) => {
+                                self.send(&error_message(None, "malformed_message", "messages must be JSON text")).await
+                            }
+                            AggregatedMessage::Ping(bytes) => {
+                                self.session.pong(&bytes).await.map_err(|_| Closing::Client(None))
+                            }
+                            AggregatedMessage::Pong(_) => Ok(()),
+                            AggregatedMessage::Close(reason) => Err(Closing::Client(reason)),
+                        }
+                    }
+                    Some(Err(e)) => {
+                        debug!("live websocket: protocol error from {}: {}", self.user, e);
+                        Err(Closing::Server(CloseCode::Protocol, "Protocol error"))
+                    }
+                    None => Err(Closing::Client(None)),
+                },
+                changed = receiver.changed() => match changed {
+                    Ok(()) => {
+                        // Rows landing in quick succession go out in one update.
+                        sleep_until(next_update).await;
+                        next_update = Instant::now() + update_interval;
+                        let up_to_id = *receiver.borrow_and_update();
+                        self.push_updates(up_to_id).await
+                    }
+                    Err(_) => Err(Closing::Server(CloseCode::Away, "Server shutting down")),
+                },
+                _ = heartbeats.tick() => {
+                    if last_seen.elapsed() > heartbeat * MISSED_HEARTBEATS {
+                        Err(Closing::Server(CloseCode::Normal, "Idle timeout"))
+                    } else {
+                        self.session.ping(b"").await.map_err(|_| Closing::Client(None))
+                    }
+                }
+            };
+            if let Err(closing) = step {
+                break closing;
+            }
+        };
+
+        debug!("live websocket: {} disconnected", self.user);
+        let reason = match closing {
+            // Echo the client's close frame, as the protocol asks.
+            Closing::Client(reason) => reason,
+            Closing::Server(code, description) => Some(CloseReason { code, description: Some(description.to_string()) }),
+        };
+        let _ = self.session.close(reason).await;
+    }
+}
+
+/// WebSocket for live dashboards watching many series at once. Clients
+/// send `subscribe`/`unsubscribe` messages for (`mq_function`,
+/// `system_name`, `bucket`) series and receive `update` messages with the
+/// buckets touched by new rows, batched across series. Browsers may
+/// authenticate with `?access_token=`.
+#[utoipa::path(
+    tag = "mq",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol; messages are `WsClientMessage` and `WsServerMessage`"),
+        (status = 400, description = "Not a WebSocket upgrade request"),
+    )
+)]
+#[get("/mq/live/ws")]
+pub async fn mq_live_ws(
+    req: HttpRequest,
+    body: web::Payload,
+    app_state: web::Data<AppState>,
+    claims: web::ReqData<Claims>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    let messages = messages
+        .max_frame_size(MAX_CLIENT_MESSAGE_BYTES)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_CLIENT_MESSAGE_BYTES);
+    let connection = LiveConnection {
+        after_id: app_state.data_watch.latest(),
+        app_state,
+        user: claims.into_inner().sub,
+        session,
+        series: BTreeMap::new(),
+    };
+    debug!("live websocket: {} connected", connection.user);
+    actix_web::rt::spawn(connection.run(messages));
+    Ok(response)
+}
@@ -0,0 +1,330 @@
+use crate::application::{catalog_service, user_service};
+use crate::domain::import::{DuplicatePolicy, ImportReport, SourceFormat};
+use crate::infrastructure::cache::ResponseCache;
+use crate::infrastructure::cache::memory_store::MemorySettings;
+use crate::infrastructure::config::{AppConfig, ConfigOverrides, ConfigPurpose};
+use crate::infrastructure::file_import::import_file;
+use crate::infrastructure::parsers::catalog::{parse_catalog_csv, parse_catalog_yaml};
+use crate::infrastructure::parsers::load_queue_mapping;
+use crate::infrastructure::schema;
+use clap::{Args, Parser, Subcommand};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// Maximum number of rejected records printed per imported file.
+const MAX_REJECTIONS_SHOWN: usize = 20;
+
+#[derive(Debug, Parser)]
+#[command(name = "mqusageviewer", version, about = "MQ Usage Viewer")]
+pub struct Cli {
+    /// TOML configuration file (default: ./mqusageviewer.toml if present, or $MQV_CONFIG)
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Address to bind the HTTP server to
+    #[arg(long, global = true)]
+    pub bind: Option<String>,
+    /// Port to listen on
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    /// Path of the SQLite database
+    #[arg(long = "db", global = true)]
+    pub database: Option<PathBuf>,
+    /// Directory served as the web UI
+    #[arg(long, global = true)]
+    pub static_dir: Option<PathBuf>,
+    /// Redis URL for the shared response cache
+    #[arg(long, global = true)]
+    pub redis_url: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn overrides(&self) -> ConfigOverrides {
+        ConfigOverrides {
+            bind_address: self.bind.clone(),
+            port: self.port,
+            database_path: self.database.clone(),
+            static_dir: self.static_dir.clone(),
+            redis_url: self.redis_url.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Run the web server (default)
+    Serve,
+    /// Load usage rows (CSV, amqsevt) or queue depth snapshots (runmqsc) into the database
+    ///
+    /// Without Redis, a running server notices new usage rows within live.poll_interval_secs and
+    /// drops its cached responses then. Rows only replaced (--on-duplicate replace) and depth
+    /// samples are not noticed: invalidate its cache with POST /api/v1/admin/cache/invalidate.
+    Import(ImportArgs),
+    /// Apply pending schema migrations to every dataset and exit
+    Migrate,
+    /// Manage the metadata catalog of systems and functions
+    Catalog {
+        #[command(subcommand)]
+        action: CatalogCommand,
+    },
+    /// Manage login accounts stored in the database
+    User {
+        #[command(subcommand)]
+        action: UserCommand,
+    },
+    /// Validate the configuration and print the effective settings
+    CheckConfig,
+}
+
+impl Command {
+    pub fn purpose(&self) -> ConfigPurpose {
+        match self {
+            Command::Serve | Command::CheckConfig => ConfigPurpose::Serve,
+            Command::User { .. } => ConfigPurpose::Accounts,
+            _ => ConfigPurpose::Maintenance,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ImportArgs {
+    /// CSV files with a header row (date_time, system_name, mq_function, work_total, trans_per_sec),
+    /// `amqsevt -o json` dumps with --format amqsevt, or runmqsc output with --format runmqsc
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    /// File format: csv, amqsevt or runmqsc
+    #[arg(long, default_value = "csv")]
+    pub format: SourceFormat,
+    /// Queue mapping for amqsevt and runmqsc files (default: ingest.queue_mapping_path)
+    #[arg(long)]
+    pub queue_mapping: Option<PathBuf>,
+    /// What to do with rows that already exist: skip, replace or reject
+    #[arg(long, default_value = "skip")]
+    pub on_duplicate: DuplicatePolicy,
+    /// Validate and count without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Dataset to load into (default: database.default_dataset)
+    #[arg(long)]
+    pub dataset: Option<String>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum CatalogCommand {
+    /// Load entries from YAML (.yaml, .yml) or CSV files
+    Import {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// What to do with entries that already exist: skip, replace or reject
+        #[arg(long, default_value = "replace")]
+        on_duplicate: DuplicatePolicy,
+        /// Validate and count without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List entries as kind, name, owner team and tags
+    List,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum UserCommand {
+    /// Create an account (or reset its password if it exists)
+    Add {
+        username: String,
+        /// Password; read from stdin when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Change the password of an account
+    Passwd {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Delete an account
+    Remove { username: String },
+    /// List accounts
+    List,
+}
+
+fn read_password(password: Option<String>) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("password must not be empty".into());
+    }
+    Ok(password)
+}
+
+fn print_import_report(source: &str, record_unit: &str, report: &ImportReport) {
+    println!(
+        "{}: {} inserted, {} replaced, {} skipped, {} rejected",
+        source,
+        report.inserted,
+        report.replaced,
+        report.skipped,
+        report.rejected.len()
+    );
+    for rejected in report.rejected.iter().take(MAX_REJECTIONS_SHOWN) {
+        println!("  {} {}: {}", record_unit, rejected.index, rejected.reason);
+    }
+    if report.rejected.len() > MAX_REJECTIONS_SHOWN {
+        println!("  ... {} more", report.rejected.len() - MAX_REJECTIONS_SHOWN);
+    }
+}
+
+async fn import(args: ImportArgs, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mapping = if args.format.needs_queue_mapping() {
+        let path = args.queue_mapping.as_ref().or(config.ingest.queue_mapping_path.as_ref()).ok_or(
+            "this format needs a queue mapping: pass --queue-mapping or set ingest.queue_mapping_path",
+        )?;
+        Some(load_queue_mapping(path)?)
+    } else {
+        None
+    };
+    let name = args.dataset.as_deref().unwrap_or(config.default_dataset());
+    let dataset = config.datasets().remove(name).ok_or_else(|| format!("unknown dataset '{}'", name))?;
+    let mut connection = schema::open_database(&dataset.path)?;
+    let mut total = ImportReport::default();
+    for file in &args.files {
+        let imported =
+            import_file(&mut connection, file, args.format, mapping.as_ref(), args.on_duplicate, args.dry_run)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+        if !imported.unmapped.is_empty() {
+            let queues = imported.unmapped.into_iter().collect::<Vec<_>>();
+            println!("{}: no mapping for {} queue(s): {}", file.display(), queues.len(), queues.join(", "));
+        }
+        print_import_report(&file.display().to_string(), args.format.record_unit(), &imported.report);
+        total.merge(imported.report);
+    }
+    if args.files.len() > 1 {
+        print_import_report("total", args.format.record_unit(), &total);
+    }
+
+    if !args.dry_run && total.changed_rows() > 0 {
+        // Running servers see the bump through Redis; without it they notice
+        // new rows by polling. The in-process tier of this short-lived
+        // command is irrelevant.
+        let cache = ResponseCache::new(
+            config.redis.url.as_deref().and_then(|url| redis::Client::open(url).ok()),
+            config.cache.ttl.clone(),
+            config.redis.settings(),
+            MemorySettings { enabled: false, ..Default::default() },
+        );
+        cache.bump_generation().await;
+    }
+    if args.dry_run {
+        println!("Dry run: nothing was written");
+    }
+    Ok(())
+}
+
+fn catalog(action: CatalogCommand, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut connection = schema::open_database(&config.database.path)?;
+    match action {
+        CatalogCommand::Import { files, on_duplicate, dry_run } => {
+            let mut total = ImportReport::default();
+            for file in &files {
+                let yaml = file
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| matches!(extension.to_ascii_lowercase().as_str(), "yaml" | "yml"));
+                let records = if yaml {
+                    parse_catalog_yaml(&std::fs::read_to_string(file)?)
+                } else {
+                    parse_catalog_csv(std::fs::File::open(file)?)
+                }
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+                let report = catalog_service::import_entries(&mut connection, records, on_duplicate, dry_run)?;
+                print_import_report(&file.display().to_string(), if yaml { "entry" } else { "line" }, &report);
+                total.merge(report);
+            }
+            if files.len() > 1 {
+                print_import_report("total", "record", &total);
+            }
+            if dry_run {
+                println!("Dry run: nothing was written");
+            }
+        }
+        CatalogCommand::List => {
+            for entry in catalog_service::list_entries(&connection, None)? {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    entry.kind.as_str(),
+                    entry.name,
+                    entry.metadata.owner_team.as_deref().unwrap_or("-"),
+                    entry.metadata.tags.join(",")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn user(action: UserCommand, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let connection = schema::open_database(&config.database.path)?;
+    let salt_key = &config.auth.salt_key;
+    match action {
+        UserCommand::Add { username, password } => {
+            let password = read_password(password)?;
+            user_service::upsert_user(&connection, &username, &password, salt_key)?;
+            println!("User '{}' saved", username);
+        }
+        UserCommand::Passwd { username, password } => {
+            if user_service::find_user(&connection, &username)?.is_none() {
+                return Err(format!("user '{}' does not exist", username).into());
+            }
+            let password = read_password(password)?;
+            user_service::upsert_user(&connection, &username, &password, salt_key)?;
+            println!("Password of '{}' changed", username);
+        }
+        UserCommand::Remove { username } => {
+            if !user_service::delete_user(&connection, &username)? {
+                return Err(format!("user '{}' does not exist", username).into());
+            }
+            println!("User '{}' removed", username);
+        }
+        UserCommand::List => {
+            for user in user_service::list_users(&connection)? {
+                println!("{}\t{}", user.username, user.created_at.to_rfc3339());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs every subcommand except `serve`.
+pub async fn run(command: Command, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Import(args) => import(args, config).await,
+        Command::Migrate => {
+            let mut paths = vec![config.database.path.clone()];
+            for dataset in config.datasets().into_values() {
+                if !paths.contains(&dataset.path) {
+                    paths.push(dataset.path);
+                }
+            }
+            for path in paths {
+                let connection = schema::open_database(&path)?;
+                println!("Database {} is at schema version {}", path.display(), schema::schema_version(&connection)?);
+            }
+            Ok(())
+        }
+        Command::Catalog { action } => catalog(action, config),
+        Command::User { action } => user(action, config),
+        Command::CheckConfig => {
+            println!("# Configuration is valid\n");
+            print!("{}", toml::to_string_pretty(&config.redacted())?);
+            Ok(())
+        }
+    }
+}
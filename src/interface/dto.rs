@@ -1,10 +1,18 @@
-use crate::domain::model::MQLogUsage;
+use crate::application::error::{AppError, FieldError};
+use crate::application::maintenance_service::MaintenanceMarker;
+use crate::domain::catalog::{CatalogKind, CatalogMetadata};
+use crate::domain::chargeback::AllocationUnit;
+use crate::domain::import::DuplicatePolicy;
+use crate::domain::model::{DepthMetrics, MQLogUsage, QueueDimensions, UsageDimension};
+use crate::infrastructure::cache::redis_store::RedisHealth;
+use crate::domain::retention::{RetentionPolicy, RetentionReport};
 use actix_web::http::StatusCode;
-use actix_web::{HttpResponse, Responder};
-use chrono::{DateTime, Local};
+use actix_web::{HttpResponse, Responder, ResponseError};
+use chrono::{DateTime, Duration, DurationRound, Local, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct ApiResponse<T>
 where
     T: Serialize,
@@ -12,10 +20,24 @@ where
     pub data: Option<T>,
     pub success: bool,
     pub message: String,
+    /// Set on errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetail>,
     #[serde(skip_serializing)]
+    #[schema(ignore)]
     pub status_code: StatusCode,
 }
 
+/// Machine-readable part of an error response.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// Stable code: `validation_failed`, `not_found`, `unauthorized`,
+    /// `forbidden`, `conflict` or `internal_error`.
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
 impl<T> ApiResponse<T>
 where
     T: Serialize,
@@ -25,51 +47,97 @@ where
             success: true,
             message: message.to_string(),
             data,
+            error: None,
             status_code: StatusCode::OK,
         }
     }
-    pub fn error(message: &str, status_code: StatusCode) -> Self {
+}
+
+impl From<&AppError> for ApiResponse<()> {
+    fn from(e: &AppError) -> Self {
+        let fields = match e {
+            AppError::Validation { fields, .. } => fields.clone(),
+            _ => Vec::new(),
+        };
         Self {
-            success: false,
-            message: message.to_string(),
             data: None,
-            status_code,
+            success: false,
+            message: e.public_message().to_string(),
+            error: Some(ErrorDetail { code: e.code(), fields }),
+            status_code: e.status_code(),
         }
     }
 }
 
-impl<T: Serialize> Into<HttpResponse> for ApiResponse<T> {
-    fn into(self) -> HttpResponse {
-        HttpResponse::build(self.status_code).json(self)
+impl<T: Serialize> From<ApiResponse<T>> for HttpResponse {
+    fn from(response: ApiResponse<T>) -> Self {
+        HttpResponse::build(response.status_code).json(response)
     }
 }
 impl<T: Serialize> Responder for ApiResponse<T> {
     type Body = actix_web::body::BoxBody;
 
     fn respond_to(self, _: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
-        // Serialize the ApiResponse to JSON and return it with a 200 OK status
-        HttpResponse::Ok()
-            .content_type("application/json")
-            .json(self)
+        HttpResponse::from(self)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SearchMqLogRequest {
     pub from_datetime: DateTime<Local>,
     pub to_datetime: DateTime<Local>,
     pub mq_function_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system_name: Option<String>,
+    /// Restricts the rows to one queue manager, queue or channel.
+    #[serde(flatten)]
+    pub queue: QueueDimensions,
+    /// Breaks the TPS summary (and buckets) down by this dimension; the
+    /// plain search ignores it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<UsageDimension>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl SearchMqLogRequest {
+    /// Problems found without the database: an inverted or too long range
+    /// and, when the route needs one, a missing function.
+    pub fn field_errors(&self, max_range: Duration, with_function: bool) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if with_function && self.mq_function_name.trim().is_empty() {
+            errors.push(FieldError::new("mq_function_name", "required", "mq_function_name is required"));
+        }
+        if self.from_datetime > self.to_datetime {
+            errors.push(FieldError::new("to_datetime", "range_inverted", "to_datetime must not be before from_datetime"));
+        } else if self.to_datetime - self.from_datetime > max_range {
+            let message = format!("the range must not exceed {} days", max_range.num_days());
+            errors.push(FieldError::new("to_datetime", "range_too_large", message));
+        }
+        errors
+    }
+
+    /// Trimmed copy without a blank system or blank queue fields; cache
+    /// keys and queries both read this copy.
+    pub fn normalized(self) -> Self {
+        Self {
+            mq_function_name: self.mq_function_name.trim().to_string(),
+            system_name: self.system_name.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(String::from),
+            queue: self.queue.normalized(),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SearchMqLogResponse {
     pub date_time: DateTime<Local>,
     pub system_name: String,
     pub mq_function: String,
     pub work_total: f64,
     pub trans_per_sec: f64,
+    #[serde(flatten)]
+    pub queue: QueueDimensions,
+    #[serde(flatten)]
+    pub depth: DepthMetrics,
 }
 
 // ✅ Implement conversion
@@ -81,17 +149,449 @@ impl From<MQLogUsage> for SearchMqLogResponse {
             mq_function: item.mq_function,
             work_total: item.work_total,
             trans_per_sec: item.trans_per_sec,
+            queue: item.queue,
+            depth: item.depth,
         }
     }
 }
 
+/// `{function}` of the per-function routes, matched by name so that a
+/// `/datasets/{dataset}` prefix does not shift it.
 #[derive(Debug, Deserialize)]
+pub struct FunctionPath {
+    pub function: String,
+}
+
+/// Keyset pagination of `/mq/search`; either field turns it on.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub page_size: Option<usize>,
+}
+
+impl PageQuery {
+    pub fn is_paginated(&self) -> bool {
+        self.cursor.is_some() || self.page_size.is_some()
+    }
+}
+
+/// Query string of the `GET` usage and TPS routes. The range is `from`
+/// (with `to`, default now) or `last`, a span ending now. Instants are
+/// RFC 3339; encode `+` in offsets as `%2B` or use `Z`.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UsageQuery {
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+    /// Span such as `30m`, `24h`, `7d` or `2w`.
+    pub last: Option<String>,
+    pub system: Option<String>,
+    pub queue_manager: Option<String>,
+    pub queue: Option<String>,
+    pub channel: Option<String>,
+    /// Breaks summaries and buckets down by this dimension.
+    #[param(inline)]
+    pub group_by: Option<UsageDimension>,
+    /// Bucket width such as `5m`, `1h` or `1d`; per-minute rows when absent.
+    pub bucket: Option<String>,
+}
+
+/// Parses `<n><unit>` with unit `s`, `m`, `h`, `d` or `w`.
+pub fn parse_span(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit = value.chars().last()?;
+    let count: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 604_800,
+        _ => return None,
+    };
+    if count <= 0 {
+        return None;
+    }
+    Duration::try_seconds(count.checked_mul(seconds)?)
+}
+
+impl UsageQuery {
+    /// The equivalent search request and the bucket width in seconds.
+    /// Ranges ending now end at the start of the current minute, so a
+    /// bookmarked URL names the same range for a whole minute.
+    pub fn to_request(
+        &self,
+        mq_function: &str,
+        now: DateTime<Local>,
+    ) -> Result<(SearchMqLogRequest, Option<i64>), AppError> {
+        let now = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+        let mut errors = Vec::new();
+        let range = match (self.last.as_deref(), self.from) {
+            (Some(_), _) if self.from.is_some() || self.to.is_some() => {
+                errors.push(FieldError::new("last", "conflicting", "last cannot be combined with from or to"));
+                None
+            }
+            (Some(last), _) => match parse_span(last) {
+                Some(span) => Some((now - span, now)),
+                None => {
+                    errors.push(FieldError::new("last", "invalid", "last must look like 30m, 24h, 7d or 2w"));
+                    None
+                }
+            },
+            (None, Some(from)) => Some((from, self.to.unwrap_or(now))),
+            (None, None) => {
+                errors.push(FieldError::new("from", "required", "from or last is required"));
+                None
+            }
+        };
+        let bucket = match self.bucket.as_deref().map(parse_span) {
+            Some(Some(span)) if span.num_seconds() % 60 == 0 => Some(span.num_seconds()),
+            Some(_) => {
+                errors.push(FieldError::new("bucket", "invalid", "bucket must be whole minutes, e.g. 5m, 1h or 1d"));
+                None
+            }
+            None => None,
+        };
+        match range {
+            Some((from_datetime, to_datetime)) if errors.is_empty() => Ok((
+                SearchMqLogRequest {
+                    from_datetime,
+                    to_datetime,
+                    mq_function_name: mq_function.to_string(),
+                    system_name: self.system.clone(),
+                    queue: QueueDimensions {
+                        queue_manager: self.queue_manager.clone(),
+                        queue_name: self.queue.clone(),
+                        channel: self.channel.clone(),
+                    },
+                    group_by: self.group_by,
+                }
+                .normalized(),
+                bucket,
+            )),
+            _ => Err(AppError::fields(errors)),
+        }
+    }
+}
+
+/// Query of the live stream. `last_event_id` is for clients that cannot
+/// send the `Last-Event-ID` header, which wins when both are given.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LiveQuery {
+    pub system: Option<String>,
+    pub last_event_id: Option<String>,
+}
+
+/// Query of `POST /ingest`. `dry_run` validates the batch and rolls back.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct IngestQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub on_duplicate: DuplicatePolicy,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// `{kind}/{name}` of the catalog entry routes; `kind` is `system(s)` or
+/// `function(s)`.
+#[derive(Debug, Deserialize)]
+pub struct CatalogPath {
+    pub kind: CatalogKind,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CatalogQuery {
+    /// Only entries of this kind.
+    #[param(inline)]
+    pub kind: Option<CatalogKind>,
+}
+
+/// Tag filter of catalog-backed lists.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TagQuery {
+    /// Comma-separated tags, all of which an entry must have.
+    pub tags: Option<String>,
+}
+
+impl TagQuery {
+    /// The tags, normalized like stored ones.
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct MetadataQuery {
+    /// Return each name with its catalog metadata.
+    #[serde(default)]
+    pub metadata: bool,
+}
+
+fn replace_duplicates() -> DuplicatePolicy {
+    DuplicatePolicy::Replace
+}
+
+/// Query of `POST /catalog/import`. Existing entries are replaced unless
+/// `on_duplicate` says otherwise; `dry_run` validates and rolls back.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CatalogImportQuery {
+    #[serde(default = "replace_duplicates")]
+    #[param(inline)]
+    pub on_duplicate: DuplicatePolicy,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A system or function name with its catalog metadata, if any.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CatalogItem {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<CatalogMetadata>,
+}
+
+/// Names, or with `metadata=true` names with their metadata.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum CatalogNames {
+    Names(Vec<String>),
+    WithMetadata(Vec<CatalogItem>),
+}
+
+/// Message from a client of the live WebSocket.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    /// Starts (or replaces) the series `id`. `bucket` defaults to `1m`;
+    /// `last` first sends the buckets of that span, e.g. `1h`.
+    Subscribe {
+        id: String,
+        mq_function: String,
+        system_name: Option<String>,
+        bucket: Option<String>,
+        last: Option<String>,
+    },
+    Unsubscribe { id: String },
+}
+
+/// Message to a client of the live WebSocket.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    Subscribed { id: String },
+    Unsubscribed { id: String },
+    /// New or changed buckets of every series with new rows.
+    Update { series: Vec<WsSeriesUpdate> },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        code: &'static str,
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WsSeriesUpdate {
+    pub id: String,
+    /// Replace any point the client already has for the same `time`.
+    pub points: Vec<WsPoint>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WsPoint {
+    /// Start of the bucket.
+    pub time: DateTime<Local>,
+    pub trans_per_sec: f64,
+    pub work_total: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchMqLogPage {
+    pub rows: Vec<SearchMqLogResponse>,
+    /// Pass as `cursor` to get the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RetentionStatusResponse {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    pub policy: RetentionPolicy,
+    pub running: bool,
+    pub last_report: Option<RetentionReport>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub database: bool,
+    pub schema_version: Option<usize>,
+    pub expected_schema_version: usize,
+    pub maintenance: Vec<MaintenanceMarker>,
+    /// Informational only: Redis being down does not make the service unready.
+    pub redis: RedisHealth,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+}
+
+/// A dataset the user may read.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DatasetResponse {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Read by requests that name no dataset.
+    pub default: bool,
+    pub latest_data: Option<DateTime<Local>>,
+}
+
+/// Datasets compared by `GET /mq/{function}/compare`.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CompareQuery {
+    /// Comma-separated dataset names; every dataset the user may read when absent.
+    pub datasets: Option<String>,
+}
+
+impl CompareQuery {
+    pub fn names(&self) -> Option<Vec<&str>> {
+        let names = self.datasets.as_deref()?.split(',').map(str::trim).filter(|name| !name.is_empty());
+        Some(names.collect())
+    }
+}
+
+/// Query of `GET /chargeback`. `cost` or `rate` override the configured
+/// cost of the month.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChargebackQuery {
+    /// `YYYY-MM`; the previous month when absent.
+    pub month: Option<String>,
+    /// `team` or `system`; `chargeback.allocate_by` when absent.
+    pub by: Option<AllocationUnit>,
+    /// Platform cost of the month, split by work_total.
+    pub cost: Option<f64>,
+    /// Cost per unit of work_total.
+    pub rate: Option<f64>,
+}
+
+/// Buckets of one dataset in `GET /mq/{function}/compare`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DatasetSeries {
+    pub dataset: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Whether the dataset has rows of the function at all.
+    pub known_function: bool,
+    pub rows: Vec<SearchMqLogResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VersionResponse {
+    pub version: String,
+    pub git_hash: String,
+    pub build_time: Option<DateTime<Utc>>,
+    pub latest_data: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrafanaSearchRequest {
+    #[serde(default)]
+    pub target: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrafanaRange {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaTarget {
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub ref_id: String,
+    #[serde(default)]
+    pub hide: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrafanaAdhocFilter {
+    pub key: String,
+    pub operator: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaQueryRequest {
+    pub range: GrafanaRange,
+    pub interval_ms: Option<i64>,
+    pub max_data_points: Option<i64>,
+    pub targets: Vec<GrafanaTarget>,
+    #[serde(default)]
+    pub adhoc_filters: Vec<GrafanaAdhocFilter>,
+}
+
+/// A time series in the SimpleJSON format: `[value, unix_ms]` pairs.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrafanaTimeSeries {
+    pub target: String,
+    pub datapoints: Vec<(f64, i64)>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrafanaAnnotationQuery {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub query: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrafanaAnnotationRequest {
+    pub range: GrafanaRange,
+    pub annotation: GrafanaAnnotationQuery,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrafanaAnnotation {
+    pub annotation: Option<String>,
+    pub time: i64,
+    pub title: String,
+    pub text: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrafanaTagKey {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub text: &'static str,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrafanaTagValuesRequest {
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrafanaTagValue {
+    pub text: String,
+}
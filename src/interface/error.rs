@@ -0,0 +1,67 @@
+use crate::application::error::{AppError, FieldError};
+use crate::interface::dto::ApiResponse;
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError, web};
+use log::{debug, error};
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            AppError::Internal(detail) => error!("{}", detail),
+            _ => debug!("{}", self),
+        }
+        HttpResponse::from(ApiResponse::from(self))
+    }
+}
+
+/// `missing field `x`` messages of serde name the field.
+fn missing_field(message: &str) -> Option<&str> {
+    message.strip_prefix("missing field `")?.split_once('`').map(|(field, _)| field)
+}
+
+fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let field = match &err {
+        JsonPayloadError::Deserialize(e) => {
+            let message = e.to_string();
+            match missing_field(&message) {
+                Some(field) => FieldError::new(field, "required", format!("{} is required", field)),
+                None if e.is_data() => FieldError::new("body", "invalid", message),
+                None => FieldError::new("body", "malformed_json", message),
+            }
+        }
+        JsonPayloadError::ContentType => {
+            FieldError::new("body", "invalid_content_type", "Content-Type must be application/json")
+        }
+        _ => FieldError::new("body", "malformed_json", err.to_string()),
+    };
+    AppError::fields(vec![field]).into()
+}
+
+fn query_error(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    AppError::field("query", "invalid", err.to_string()).into()
+}
+
+fn path_error(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    AppError::field("path", "invalid", err.to_string()).into()
+}
+
+/// Extractor settings that answer malformed bodies, query strings and
+/// paths with the JSON error body instead of plain text.
+pub fn configure_extractors(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(json_error))
+        .app_data(web::QueryConfig::default().error_handler(query_error))
+        .app_data(web::PathConfig::default().error_handler(path_error));
+}
@@ -0,0 +1,253 @@
+use crate::application::error::AppError;
+use actix_web::http::header::{
+    ACCEPT, ContentDisposition, DispositionParam, DispositionType,
+};
+use actix_web::{HttpRequest, HttpResponse, web};
+use chrono::{DateTime, Local};
+use futures_util::{Stream, stream};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+pub const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// Date format of CSV cells; Excel and LibreOffice parse it as a date.
+const CSV_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const XLSX_DATE_FORMAT: &str = "yyyy-mm-dd hh:mm:ss";
+/// Excel's limit on sheet name length.
+const MAX_SHEET_NAME: usize = 31;
+/// Leading characters that make a spreadsheet read a CSV cell as a formula.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    /// Newline-delimited JSON, one row per line.
+    Ndjson,
+    Csv,
+    Xlsx,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportQuery {
+    pub format: Option<ExportFormat>,
+}
+
+impl ExportFormat {
+    /// The `format` query parameter wins over the `Accept` header; JSON is
+    /// the default.
+    pub fn negotiate(req: &HttpRequest, query: &ExportQuery) -> Self {
+        if let Some(format) = query.format {
+            return format;
+        }
+        let accept = req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default();
+        if accept.contains(NDJSON_CONTENT_TYPE) {
+            ExportFormat::Ndjson
+        } else if accept.contains(XLSX_CONTENT_TYPE) {
+            ExportFormat::Xlsx
+        } else if accept.contains("text/csv") {
+            ExportFormat::Csv
+        } else {
+            ExportFormat::Json
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Cell {
+    DateTime(DateTime<Local>),
+    Text(String),
+    Number(f64),
+}
+
+/// One worksheet of an export. CSV exports concatenate the rows of every
+/// table under the header of the first.
+#[derive(Debug, Clone)]
+pub struct ExportTable {
+    pub sheet: String,
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// CSV text of a cell. Text a spreadsheet would take for a formula is
+/// prefixed with `'`, so opening an export cannot run it.
+fn csv_field(cell: &Cell) -> String {
+    match cell {
+        Cell::DateTime(date_time) => date_time.format(CSV_DATE_FORMAT).to_string(),
+        Cell::Text(text) if text.starts_with(FORMULA_PREFIXES) => format!("'{}", text),
+        Cell::Text(text) => text.clone(),
+        Cell::Number(number) => number.to_string(),
+    }
+}
+
+fn csv_line<I>(record: I) -> Result<web::Bytes, actix_web::Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record).map_err(actix_web::error::ErrorInternalServerError)?;
+    let line = writer.into_inner().map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(web::Bytes::from(line))
+}
+
+/// The header of the first table, then the rows of every table, encoded
+/// one line at a time as the response is sent.
+fn csv_stream(tables: Vec<ExportTable>) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
+    let header = tables.first().map(|table| csv_line(&table.columns));
+    let rows = tables
+        .into_iter()
+        .flat_map(|table| table.rows)
+        .map(|row| csv_line(row.iter().map(csv_field)));
+    stream::iter(header.into_iter().chain(rows))
+}
+
+/// Excel forbids some characters in sheet names, limits their length and
+/// requires them to be unique (case-insensitively).
+fn sheet_name(name: &str, used: &mut Vec<String>) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\') { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim_matches('\'');
+    let base: String = if cleaned.is_empty() { "Sheet".to_string() } else { cleaned.chars().take(MAX_SHEET_NAME).collect() };
+    let mut candidate = base.clone();
+    let mut counter = 2;
+    while used.iter().any(|u| u.eq_ignore_ascii_case(&candidate)) {
+        let suffix = format!(" ({})", counter);
+        let keep = MAX_SHEET_NAME - suffix.len();
+        candidate = format!("{}{}", base.chars().take(keep).collect::<String>(), suffix);
+        counter += 1;
+    }
+    used.push(candidate.clone());
+    candidate
+}
+
+fn to_xlsx(tables: &[ExportTable]) -> Result<Vec<u8>, String> {
+    let mut workbook = Workbook::new();
+    let header = Format::new().set_bold();
+    let date = Format::new().set_num_format(XLSX_DATE_FORMAT);
+    let mut used = Vec::new();
+    for table in tables {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet_name(&table.sheet, &mut used)).map_err(|e| e.to_string())?;
+        for (col, column) in table.columns.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, *column, &header).map_err(|e| e.to_string())?;
+        }
+        for (idx, row) in table.rows.iter().enumerate() {
+            let row_num = idx as u32 + 1;
+            for (col, cell) in row.iter().enumerate() {
+                let col = col as u16;
+                match cell {
+                    Cell::DateTime(date_time) => {
+                        worksheet.write_datetime_with_format(row_num, col, date_time.naive_local(), &date)
+                    }
+                    Cell::Text(text) => worksheet.write_string(row_num, col, text),
+                    Cell::Number(number) => worksheet.write_number(row_num, col, *number),
+                }
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        worksheet.set_freeze_panes(1, 0).map_err(|e| e.to_string())?;
+        worksheet.autofit();
+    }
+    if tables.is_empty() {
+        workbook.add_worksheet();
+    }
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
+
+/// Serializes `rows` one per line.
+pub fn ndjson_lines<T: Serialize>(rows: impl IntoIterator<Item = T>) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut body, &row).map_err(|e| e.to_string())?;
+        body.push(b'\n');
+    }
+    Ok(body)
+}
+
+/// Renders `tables` as a CSV or XLSX attachment named `<filename>.csv` or
+/// `<filename>.xlsx`. CSV is streamed; a workbook can only be built in
+/// memory, so XLSX is refused above `max_xlsx_rows` rows in total.
+pub fn file_response(
+    format: ExportFormat,
+    filename: &str,
+    tables: Vec<ExportTable>,
+    max_xlsx_rows: usize,
+) -> Result<HttpResponse, AppError> {
+    let extension = match format {
+        ExportFormat::Csv => "csv",
+        ExportFormat::Xlsx => "xlsx",
+        ExportFormat::Json | ExportFormat::Ndjson => {
+            return Err(AppError::internal("export", "JSON is not a file export format"));
+        }
+    };
+    let mut response = HttpResponse::Ok();
+    response.insert_header(ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(format!("{}.{}", filename, extension))],
+    });
+    if format == ExportFormat::Csv {
+        return Ok(response.content_type(CSV_CONTENT_TYPE).streaming(csv_stream(tables)));
+    }
+    let rows: usize = tables.iter().map(|table| table.rows.len()).sum();
+    if rows > max_xlsx_rows {
+        let message = format!(
+            "{} rows exceed the XLSX export limit of {}; use format=csv, which is streamed",
+            rows, max_xlsx_rows
+        );
+        return Err(AppError::field("format", "too_many_rows", message));
+    }
+    let body = to_xlsx(&tables).map_err(|e| AppError::internal("xlsx export", e))?;
+    Ok(response.content_type(XLSX_CONTENT_TYPE).body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::MessageBody;
+    use actix_web::http::StatusCode;
+
+    fn table(sheet: &str, names: &[&str]) -> ExportTable {
+        ExportTable {
+            sheet: sheet.to_string(),
+            columns: vec!["name", "work_total"],
+            rows: names.iter().map(|name| vec![Cell::Text(name.to_string()), Cell::Number(-1.5)]).collect(),
+        }
+    }
+
+    #[test]
+    fn formula_like_text_is_escaped() {
+        for formula in ["=HYPERLINK(\"x\")", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(csv_field(&Cell::Text(formula.to_string())), format!("'{}", formula));
+        }
+        assert_eq!(csv_field(&Cell::Text("PAY".to_string())), "PAY");
+        assert_eq!(csv_field(&Cell::Number(-1.5)), "-1.5");
+    }
+
+    #[actix_web::test]
+    async fn csv_is_streamed_under_one_header() {
+        let tables = vec![table("A", &["PAY", "=1+1"]), table("B", &["a,b"])];
+        let response = file_response(ExportFormat::Csv, "export", tables, 1).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "name,work_total\nPAY,-1.5\n'=1+1,-1.5\n\"a,b\",-1.5\n");
+    }
+
+    #[test]
+    fn xlsx_over_the_row_limit_is_refused() {
+        let tables = vec![table("A", &["PAY", "QRY"]), table("B", &["PAY"])];
+        let error = file_response(ExportFormat::Xlsx, "export", tables.clone(), 2).unwrap_err();
+        assert!(matches!(error, AppError::Validation { .. }));
+        let response = file_response(ExportFormat::Xlsx, "export", tables, 3).unwrap();
+        assert!(response.into_body().try_into_bytes().is_ok_and(|body| body.starts_with(b"PK")));
+    }
+}
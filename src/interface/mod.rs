@@ -1,2 +1,6 @@
 pub mod api;
+pub mod cli;
 pub mod dto;
+pub mod error;
+pub mod export;
+pub mod openapi;
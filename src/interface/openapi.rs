@@ -0,0 +1,189 @@
+//! OpenAPI document of the HTTP API, generated from the handler and DTO
+//! annotations. Served at `/api/v1/openapi.json` with Swagger UI at
+//! `/api/v1/docs/`.
+
+use crate::domain::catalog::{CatalogKind, Criticality};
+use crate::domain::import::DuplicatePolicy;
+use crate::domain::model::{DepthMetrics, QueueDimensions, UsageDimension};
+use crate::domain::retention::Granularity;
+use crate::interface::api::{
+    admin_handler, catalog_handler, chargeback_handler, dataset_handler, grafana_handler, health_handler, ingest_handler, live_handler, login_handler, metrics_handler, mq_log_handler,
+    mq_metrics_handler, ws_handler,
+};
+use crate::interface::dto::{CatalogItem, SearchMqLogPage, WsClientMessage, WsServerMessage};
+use crate::interface::export::ExportFormat;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+pub const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+pub const DOCS_PATH: &str = "/api/v1/docs";
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Token from `POST /auth/login`"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "metrics_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("`metrics.token`, when configured"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Routes of the authenticated `/api/v1` scope. Except `/datasets`,
+/// `/catalog` and `/mq/{function}/compare`, each is also served under
+/// `/api/v1/datasets/{dataset}`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        mq_log_handler::mq_search,
+        mq_log_handler::mq_functions,
+        mq_log_handler::mq_tps_summary,
+        mq_log_handler::all_mq_tps_summary,
+        mq_log_handler::mq_function_systems,
+        mq_log_handler::mq_function_queue_managers,
+        mq_log_handler::mq_function_queues,
+        mq_log_handler::mq_function_channels,
+        mq_log_handler::mq_usage,
+        mq_log_handler::mq_tps,
+        mq_log_handler::all_mq_tps,
+        mq_log_handler::mq_depth,
+        mq_log_handler::mq_compare,
+        dataset_handler::list_datasets,
+        catalog_handler::list_catalog,
+        catalog_handler::get_catalog_entry,
+        catalog_handler::put_catalog_entry,
+        catalog_handler::delete_catalog_entry,
+        catalog_handler::import_catalog,
+        chargeback_handler::chargeback,
+        live_handler::mq_live,
+        ws_handler::mq_live_ws,
+        ingest_handler::ingest,
+        grafana_handler::grafana_test,
+        grafana_handler::grafana_search,
+        grafana_handler::grafana_query,
+        grafana_handler::grafana_annotations,
+        grafana_handler::grafana_tag_keys,
+        grafana_handler::grafana_tag_values,
+        mq_metrics_handler::openmetrics_backfill,
+        admin_handler::retention_status,
+        admin_handler::run_retention,
+        admin_handler::cache_stats,
+        admin_handler::invalidate_cache,
+    ),
+    components(schemas(
+        SearchMqLogPage,
+        ExportFormat,
+        Granularity,
+        DuplicatePolicy,
+        QueueDimensions,
+        DepthMetrics,
+        UsageDimension,
+        CatalogKind,
+        Criticality,
+        CatalogItem,
+        WsClientMessage,
+        WsServerMessage
+    ))
+)]
+struct ApiV1Doc;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "MQUsageViewer API"),
+    paths(
+        login_handler::login,
+        health_handler::redis_health,
+        health_handler::healthz,
+        health_handler::readyz,
+        health_handler::version,
+        metrics_handler::metrics,
+        mq_metrics_handler::mq_usage_metrics,
+    ),
+    nest((path = "/api/v1", api = ApiV1Doc)),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "auth", description = "Login"),
+        (name = "health", description = "Probes and build information"),
+        (name = "metrics", description = "Prometheus and OpenMetrics exports"),
+        (name = "mq", description = "MQ usage queries"),
+        (name = "grafana", description = "Grafana JSON datasource"),
+        (name = "datasets", description = "Named datasets, e.g. one per environment"),
+        (name = "catalog", description = "Metadata of systems and functions"),
+        (name = "chargeback", description = "Monthly platform cost allocation"),
+        (name = "admin", description = "Retention and cache administration"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    const ROUTE_MACROS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+    /// `(method, path)` of every actix route attribute in `src/interface/api`.
+    fn declared_routes() -> BTreeSet<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/interface/api");
+        let mut routes = BTreeSet::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            for line in source.lines().map(str::trim) {
+                for method in ROUTE_MACROS {
+                    if let Some(rest) = line.strip_prefix(&format!("#[{}(\"", method))
+                        && let Some((path, _)) = rest.split_once('"')
+                    {
+                        routes.insert((method.to_string(), path.to_string()));
+                    }
+                }
+            }
+        }
+        routes
+    }
+
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, item) in document["paths"].as_object().unwrap() {
+            for method in ROUTE_MACROS {
+                if item.get(*method).is_some() {
+                    routes.insert((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let documented = documented_routes();
+        let undocumented: Vec<_> = declared_routes()
+            .into_iter()
+            .filter(|(method, path)| {
+                // `#[routes]` may list a trailing-slash alias of a documented path.
+                let path = if path.len() > 1 { path.trim_end_matches('/') } else { path };
+                ![path.to_string(), format!("/api/v1{}", path)]
+                    .iter()
+                    .any(|candidate| documented.contains(&(method.clone(), candidate.clone())))
+            })
+            .collect();
+        assert!(undocumented.is_empty(), "routes missing from the OpenAPI document: {:?}", undocumented);
+    }
+}
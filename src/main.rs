@@ -1,8 +1,22 @@
-use crate::infrastructure::middleware::auth_middleware::AuthMiddleware;
+use crate::infrastructure::cache::ResponseCache;
+use crate::infrastructure::config::{AppConfig, WatchConfig};
+use crate::infrastructure::datasets::Datasets;
+use crate::infrastructure::ingest_watcher::IngestWatcher;
+use crate::infrastructure::metrics::Metrics;
+use crate::infrastructure::middleware::auth_middleware::{AuthMiddleware, redacted_request_line};
+use crate::infrastructure::middleware::dataset_middleware::DatasetMiddleware;
+use crate::infrastructure::middleware::metrics_middleware::MetricsMiddleware;
+use crate::infrastructure::parsers::load_queue_mapping;
+use crate::interface::api::redirect_handler::{self, HttpsPort};
+use crate::interface::cli::{Cli, Command};
 use actix_files::Files;
 use actix_web::{App, HttpServer, web};
+use clap::Parser;
 use log::{error, info};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 use redis::Client as RedisClient;
 
 mod application;
@@ -10,70 +24,241 @@ mod domain;
 mod infrastructure;
 mod interface;
 
-#[actix_web::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    pretty_env_logger::init();
-    dotenv::dotenv().ok();
-
-    let port: u16 = std::env::var("PORT")
-        .unwrap_or_else(|_| "8888".to_string())
-        .parse()
-        .expect("PORT must be a number");
-
-    let message = format!("Starting MQ Usage Viewer (Demo) on http://localhost:{}", port);
+async fn serve(config: AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let message = format!(
+        "Starting MQ Usage Viewer (Demo) on {}://{}:{}",
+        if config.tls.enabled { "https" } else { "http" },
+        config.server.bind_address,
+        config.server.port
+    );
     info!("{}", message);
 
-    let user_name = std::env::var("USER_NAME").expect("USER_NAME must be set");
-    let password = std::env::var("PASSWORD").expect("PASSWORD must be set");
-    let secret_value = std::env::var("SECRET_VALUE").expect("SECRET_VALUE must be set");
-    let salt_key = std::env::var("SALT_KEY").expect("SALT_KEY must be set");
-
-    let connection = rusqlite::Connection::open("datasets/mqdata_v2.db").expect("Failed to open database");
+    let connection = infrastructure::schema::open_database(&config.database.path)
+        .map_err(|e| format!("Failed to open database {}: {}", config.database.path.display(), e))?;
+    let accounts = Arc::new(Mutex::new(connection));
 
-    let redis_client = match std::env::var("REDIS_URL") {
-        Ok(redis_url) => match RedisClient::open(redis_url) {
+    let redis_client = match &config.redis.url {
+        Some(redis_url) => match RedisClient::open(redis_url.as_str()) {
             Ok(client) => {
-                info!("Connected to Redis cache");
+                info!("Redis cache configured");
                 Some(client)
             },
             Err(e) => {
-                error!("Redis not available: {}. Continue without cache.", e);
+                error!("Redis not available: {}. Using in-process cache only.", e);
                 None
             }
         },
-        Err(_) => {
-            error!("REDIS_URL not set. Continue without cache.");
+        None => {
+            info!("REDIS_URL not set. Using in-process cache only.");
             None
         }
     };
 
-    let app_state = infrastructure::app_state::AppState {
-        db: Arc::new(Mutex::new(connection)),
-        user_name,
-        password,
-        secret_value,
-        salt_key,
+    let cache = Arc::new(ResponseCache::new(
         redis_client,
+        config.cache.ttl.clone(),
+        config.redis.settings(),
+        config.cache.memory.clone(),
+    ));
+    let metrics = Arc::new(Metrics::new());
+    let datasets = Arc::new(Datasets::open(&config, &accounts, &metrics)?);
+    info!(
+        "Serving datasets {} (default: {})",
+        datasets.iter().map(|dataset| dataset.name.as_str()).collect::<Vec<_>>().join(", "),
+        datasets.default_dataset().name
+    );
+    datasets.spawn(&cache, Duration::from_secs(config.live.poll_interval_secs));
+    if config.ingest.watch.enabled {
+        let mapping = match &config.ingest.queue_mapping_path {
+            Some(path) => Some(load_queue_mapping(path)?),
+            None => None,
+        };
+        // One watcher per dataset, over the inboxes loading into it
+        for dataset in datasets.iter() {
+            let inboxes: Vec<_> = config
+                .ingest
+                .watch
+                .inboxes
+                .iter()
+                .filter(|inbox| inbox.dataset.as_deref().unwrap_or(config.default_dataset()) == dataset.name)
+                .cloned()
+                .collect();
+            if inboxes.is_empty() {
+                continue;
+            }
+            let watch = WatchConfig { inboxes, ..config.ingest.watch.clone() };
+            let watcher = Arc::new(IngestWatcher::new(watch, mapping.clone(), metrics.clone()));
+            watcher.spawn(dataset.db.clone(), cache.clone(), dataset.data_watch.clone());
+        }
+    } else {
+        info!("Inbox watcher disabled (set ingest.watch.enabled / INGEST_WATCH_ENABLED=true to enable)");
+    }
+
+    let default_dataset = datasets.default_dataset().clone();
+    let app_state = infrastructure::app_state::AppState {
+        db: default_dataset.db.clone(),
+        accounts,
+        auth: config.auth.clone(),
+        cache,
+        retention: default_dataset.retention.clone(),
+        metrics,
+        metrics_token: config.metrics.token.clone(),
+        mq_metrics: config.mq_metrics.clone(),
+        search: config.search.clone(),
+        live: config.live.clone(),
+        data_watch: default_dataset.data_watch.clone(),
+        ingest: config.ingest.clone(),
+        chargeback: config.chargeback.clone(),
+        datasets,
+        dataset: default_dataset.name.clone(),
     };
-    HttpServer::new(move || {
+    let metrics_enabled = config.metrics.enabled;
+    let mq_metrics_enabled = config.mq_metrics.enabled;
+    let static_dir = config.server.static_dir.clone();
+    let openapi = interface::openapi::ApiDoc::openapi();
+    let mut server = HttpServer::new(move || {
+        // Routes reading one dataset, served with and without a dataset prefix.
+        let api_v1 = move |cfg: &mut web::ServiceConfig| {
+            cfg.service(interface::api::mq_log_handler::mq_search)
+                .service(interface::api::mq_log_handler::mq_functions)
+                .service(interface::api::chargeback_handler::chargeback)
+                .service(interface::api::mq_log_handler::mq_tps_summary)
+                .service(interface::api::mq_log_handler::all_mq_tps_summary)
+                .service(interface::api::mq_log_handler::mq_function_systems)
+                .service(interface::api::mq_log_handler::mq_function_queue_managers)
+                .service(interface::api::mq_log_handler::mq_function_queues)
+                .service(interface::api::mq_log_handler::mq_function_channels)
+                .service(interface::api::mq_log_handler::mq_usage)
+                .service(interface::api::mq_log_handler::mq_tps)
+                .service(interface::api::mq_log_handler::all_mq_tps)
+                .service(interface::api::mq_log_handler::mq_depth)
+                .service(interface::api::live_handler::mq_live)
+                .service(interface::api::ws_handler::mq_live_ws)
+                .service(interface::api::ingest_handler::ingest)
+                .service(interface::api::grafana_handler::grafana_test)
+                .service(interface::api::grafana_handler::grafana_search)
+                .service(interface::api::grafana_handler::grafana_query)
+                .service(interface::api::grafana_handler::grafana_annotations)
+                .service(interface::api::grafana_handler::grafana_tag_keys)
+                .service(interface::api::grafana_handler::grafana_tag_values);
+            if mq_metrics_enabled {
+                cfg.service(interface::api::mq_metrics_handler::openmetrics_backfill);
+            }
+            cfg.service(interface::api::admin_handler::retention_status)
+                .service(interface::api::admin_handler::run_retention)
+                .service(interface::api::admin_handler::cache_stats)
+                .service(interface::api::admin_handler::invalidate_cache);
+        };
         App::new()
-            .wrap(actix_web::middleware::Logger::default())
+            .wrap(MetricsMiddleware::new(app_state.metrics.clone()))
+            .wrap(
+                // The default format, with `access_token` masked in the request line.
+                actix_web::middleware::Logger::new(r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#)
+                    .custom_request_replace("request_line", redacted_request_line),
+            )
             .app_data(web::Data::new(app_state.clone()))
+            .configure(interface::error::configure_extractors)
+            .configure(|cfg| {
+                if metrics_enabled {
+                    cfg.service(interface::api::metrics_handler::metrics);
+                }
+                if mq_metrics_enabled {
+                    cfg.service(interface::api::mq_metrics_handler::mq_usage_metrics);
+                }
+            })
             .service(interface::api::login_handler::login)
+            .service(interface::api::health_handler::redis_health)
+            .service(interface::api::health_handler::healthz)
+            .service(interface::api::health_handler::readyz)
+            .service(interface::api::health_handler::version)
+            // Registered before the /api/v1 scope so the docs need no login.
+            .service(
+                SwaggerUi::new(format!("{}/{{_:.*}}", interface::openapi::DOCS_PATH))
+                    .url(interface::openapi::OPENAPI_PATH, openapi.clone()),
+            )
+            // Registered before the /api/v1 scope, which would otherwise match first.
+            .service(
+                web::scope("/api/v1/datasets/{dataset}")
+                    .wrap(DatasetMiddleware::new(web::Data::new(app_state.clone())))
+                    .wrap(AuthMiddleware::new(web::Data::new(app_state.clone())))
+                    .configure(api_v1),
+            )
             .service(
                 web::scope("/api/v1")
                     .wrap(AuthMiddleware::new(web::Data::new(app_state.clone())))
-                    .service(interface::api::mq_log_handler::mq_search)
-                    .service(interface::api::mq_log_handler::mq_functions)
-                    .service(interface::api::mq_log_handler::mq_tps_summary)
-                    .service(interface::api::mq_log_handler::all_mq_tps_summary)
-                    .service(interface::api::mq_log_handler::mq_function_systems),
+                    .service(interface::api::dataset_handler::list_datasets)
+                    .service(interface::api::catalog_handler::list_catalog)
+                    .service(interface::api::catalog_handler::import_catalog)
+                    .service(interface::api::catalog_handler::get_catalog_entry)
+                    .service(interface::api::catalog_handler::put_catalog_entry)
+                    .service(interface::api::catalog_handler::delete_catalog_entry)
+                    .service(interface::api::mq_log_handler::mq_compare)
+                    .service(
+                        web::scope("")
+                            .wrap(DatasetMiddleware::new(web::Data::new(app_state.clone())))
+                            .configure(api_v1),
+                    ),
             )
-            .service(Files::new("/", "./statics").index_file("index.html"))
+            .service(Files::new("/", static_dir.clone()).index_file("index.html"))
     })
-    .bind(("0.0.0.0", 8888))?
-    .run()
-    .await?;
+    .on_connect(infrastructure::tls::client_identity_hook(config.tls.client_cert_users.clone()));
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+    let address = (config.server.bind_address.as_str(), config.server.port);
+    let server = if config.tls.enabled {
+        let (tls_config, resolver) = infrastructure::tls::server_config(&config.tls)?;
+        resolver.spawn(Duration::from_secs(config.tls.reload_interval_secs));
+        server.bind_rustls_0_23(address, tls_config)?.run()
+    } else {
+        server.bind(address)?.run()
+    };
+
+    match config.tls.redirect_http_port.filter(|_| config.tls.enabled) {
+        Some(redirect_port) => {
+            info!("Redirecting http://{}:{} to HTTPS", config.server.bind_address, redirect_port);
+            let https_port = HttpsPort(config.server.port);
+            let redirect = HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(https_port))
+                    .default_service(web::to(redirect_handler::https_redirect))
+            })
+            .workers(1)
+            .bind((config.server.bind_address.as_str(), redirect_port))?
+            .run();
+            futures_util::future::try_join(server, redirect).await?;
+        }
+        None => server.await?,
+    }
 
     Ok(())
 }
+
+#[actix_web::main]
+async fn main() {
+    pretty_env_logger::init();
+    dotenv::dotenv().ok();
+
+    let cli = Cli::parse();
+    let command = cli.command.clone().unwrap_or(Command::Serve);
+
+    let config = match AppConfig::load(cli.config.as_deref(), &cli.overrides())
+        .and_then(|config| config.validate(command.purpose()).map(|_| config))
+    {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let result = match command {
+        Command::Serve => serve(config).await,
+        command => interface::cli::run(command, &config).await,
+    };
+    if let Err(e) = result {
+        error!("{}", e);
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}